@@ -0,0 +1,52 @@
+#[macro_use]
+extern crate cfg_aliases;
+
+fn main() {
+    // The platform lists several modules gate on, kept in one place.
+    cfg_aliases! {
+        // Platforms whose `Backend::Default` is a `flock` lock rather than a
+        // `fcntl` one.
+        default_flock: { any(
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "dragonfly",
+            target_os = "haiku",
+            target_os = "redox",
+            target_os = "fuchsia",
+            target_os = "nto",
+            target_os = "illumos",
+            target_env = "ohos"
+        ) },
+        // `Backend::Flock`.
+        flock_backend: { all(feature = "flock", any(target_os = "linux", default_flock)) },
+        // Platforms taking `flock` locks, by default or with `Backend::Flock`.
+        flock: { any(default_flock, flock_backend) },
+        // `Backend::Semaphore`.
+        posix_semaphore: { all(feature = "semaphore", any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "dragonfly",
+            target_os = "haiku",
+            target_os = "nto",
+            target_os = "aix",
+            target_os = "illumos",
+            target_os = "solaris"
+        )) },
+        // `Backend::SysvSemaphore`.
+        sysv_semaphore: { all(feature = "sysv-semaphore", any(target_os = "linux", target_os = "macos", target_os = "aix")) },
+        // `Backend::AbstractSocket`, also the default on linux and android.
+        abstract_socket: { all(
+            feature = "abstract-socket",
+            any(target_os = "linux", target_os = "android"),
+            not(target_env = "ohos")
+        ) },
+    }
+}
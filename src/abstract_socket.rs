@@ -0,0 +1,71 @@
+//! Unix domain sockets bound in the abstract namespace.
+
+use nix::sys::socket::{self, UnixAddr};
+use nix::unistd;
+use std::fs;
+use std::os::unix::prelude::{AsRawFd, RawFd};
+
+/// A bound socket, closed when dropped.
+pub struct AbstractSocket {
+    fd: RawFd,
+}
+
+/// Returns a socket bound to `name`, or `None` if another instance holds it.
+pub fn try_bind(name: &str) -> nix::Result<Option<AbstractSocket>> {
+    let addr = UnixAddr::new_abstract(name.as_bytes())?;
+    let fd = socket::socket(
+        socket::AddressFamily::Unix,
+        socket::SockType::Stream,
+        // If we fork and exec, then make sure the child process doesn't
+        // hang on to this file descriptor.
+        socket::SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+
+    match socket::bind(fd, &socket::SockAddr::Unix(addr)) {
+        Ok(()) => Ok(Some(AbstractSocket { fd })),
+        Err(nix::errno::Errno::EADDRINUSE) => {
+            let _ = unistd::close(fd);
+            Ok(None)
+        }
+        Err(e) => {
+            let _ = unistd::close(fd);
+            Err(e)
+        }
+    }
+}
+
+/// Returns the PID of the process holding the socket bound to `name`, or
+/// `None` if it cannot be told. The socket's inode is looked up in
+/// `/proc/net/unix` and then among the open files in `/proc`, which only tells
+/// processes of the same user.
+pub fn holder_pid(name: &str) -> Option<u32> {
+    let suffix = format!(" @{}", name);
+    let sockets = fs::read_to_string("/proc/net/unix").ok()?;
+    let inode = sockets
+        .lines()
+        .skip(1)
+        .find(|line| line.ends_with(&suffix))?
+        .split_whitespace()
+        .nth(6)?;
+    let target = format!("socket:[{}]", inode);
+    fs::read_dir("/proc").ok()?.flatten().find_map(|process| {
+        let pid = process.file_name().to_str()?.parse().ok()?;
+        let mut fds = fs::read_dir(process.path().join("fd")).ok()?.flatten();
+        fds.any(|fd| fs::read_link(fd.path()).is_ok_and(|link| link.as_os_str() == target.as_str()))
+            .then_some(pid)
+    })
+}
+
+impl AsRawFd for AbstractSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for AbstractSocket {
+    fn drop(&mut self) {
+        // Intentionally discard any close errors.
+        let _ = unistd::close(self.fd);
+    }
+}
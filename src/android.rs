@@ -0,0 +1,17 @@
+//! Android specific configuration.
+//!
+//! Apps can write neither to their working directory nor to `/tmp`, so by
+//! default names are bound as abstract sockets. Once a lock directory is set
+//! with `set_lock_dir`, e.g. the path returned by `Context.getFilesDir()`
+//! handed over through JNI, names are resolved into it and locked with `flock`
+//! instead.
+
+use std::path::PathBuf;
+
+pub use lock_dir::{lock_dir, set_lock_dir};
+
+/// Returns the lock file path for `name`, or `None` if no lock directory is
+/// set and an abstract socket should be used.
+pub(crate) fn lock_path(name: &str) -> Option<PathBuf> {
+    lock_dir().map(|dir| ::lock_dir::resolve(&dir, name))
+}
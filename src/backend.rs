@@ -0,0 +1,217 @@
+//! Selection of the mechanism an instance name is claimed with.
+
+use error::{Result, SingleInstanceError};
+use std::error::Error;
+
+/// The mechanism used to claim an instance name, passed to
+/// `SingleInstance::with_backend`. Which variants exist depends on the
+/// platform and the cargo features enabled, so matches need a wildcard arm.
+///
+/// Every backend but `Default` and `FileLock` is behind a cargo feature named
+/// after it, such as `tcp-port` or `named-pipe`. These are enabled by default,
+/// unlike `dbus` and `bundle-id`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backend {
+    /// The platform's default mechanism, as used by `SingleInstance::new`.
+    ///
+    /// On windows this is a named mutex, created through the Win32 API on
+    /// cygwin too, including MSYS2, so that instances are also detected
+    /// across cygwin and native windows builds. Native builds own the mutex
+    /// on a thread of their own, so that `SingleInstance::wait_until_single`
+    /// can wait on it. On linux it is an abstract
+    /// socket, and so on android unless a lock directory has been set with
+    /// `android::set_lock_dir`. Without the `abstract-socket` feature both
+    /// lock files instead.
+    ///
+    /// On macos, ios, the BSDs, illumos, haiku, redox, fuchsia, qnx and
+    /// openharmony it is a `flock` lock on the file at the path given as
+    /// name, and on solaris, aix and any other unix a `fcntl` write lock over
+    /// the whole file. Names without a path separator are resolved into
+    /// `XDG_RUNTIME_DIR`, or else the temporary directory suffixed with the
+    /// user's ID, and on ios into the app's temporary directory or the one
+    /// set with `ios::set_lock_dir`. On openharmony and fuchsia the path has
+    /// to point into a directory the app may write to, such as its sandbox.
+    ///
+    /// On wasm32 in the browser it is a lock of the Web Locks API, shared by
+    /// the tabs and workers of an origin. Locks are only granted
+    /// asynchronously there, so names are claimed with
+    /// `SingleInstance::new_async` of the `wasm` feature, and
+    /// `SingleInstance::new` fails.
+    ///
+    /// On wasi, which has no advisory locks, the file at the path given as
+    /// name is created exclusively and removed on drop. It has to be inside a
+    /// preopened directory, and names without a path separator are resolved
+    /// into the first of `TMPDIR`, `/tmp` and the current directory that is
+    /// preopened. The holder writes an identity into the file and keeps
+    /// touching it, so one left behind by a crashed instance is replaced once
+    /// it has gone untouched for a few seconds. Where threads are missing, as
+    /// on wasm32-wasip1, it cannot be touched and has to be removed by hand.
+    #[default]
+    Default,
+
+    /// An advisory lock on the file at the path given as name, created if
+    /// needed.
+    ///
+    /// On linux this is a `fcntl` write lock over the whole file, taken as an
+    /// open file description lock so that closing other descriptors of the
+    /// file does not release it, falling back to a classic one on kernels
+    /// before 3.15. On windows it is a `LockFileEx` lock past its end,
+    /// elsewhere the same lock files are claimed with by default.
+    ///
+    /// The holder records itself in the file unless it has other contents,
+    /// see `SingleInstance::read_lock_info`.
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "cygwin"))))]
+    FileLock,
+
+    /// A `flock` lock on the file at the path given as name, created if
+    /// needed.
+    ///
+    /// Unlike a `fcntl` lock it belongs to the open file rather than the
+    /// process, so it is not lost when another descriptor of the file is
+    /// closed.
+    #[cfg(flock_backend)]
+    Flock,
+
+    /// A unix domain socket bound in the abstract namespace.
+    ///
+    /// Nothing is left behind on the file system, so this also works on a
+    /// read only one.
+    #[cfg(abstract_socket)]
+    AbstractSocket,
+
+    /// A POSIX named semaphore with a count of one, named after the name
+    /// given with a leading `/` added if missing.
+    ///
+    /// No file system path is involved. Names are limited to 31 characters on
+    /// macos and ios. The semaphore is not released if its holder crashes, it
+    /// then has to be removed with `sem_unlink`.
+    #[cfg(posix_semaphore)]
+    Semaphore,
+
+    /// A System V semaphore, keyed by a hash of the name given.
+    ///
+    /// No file system path is involved. The semaphore is taken with
+    /// `SEM_UNDO`, so it is released even if its holder crashes.
+    #[cfg(sysv_semaphore)]
+    SysvSemaphore,
+
+    /// A robust process shared mutex in the POSIX shared memory object named
+    /// after the name given, with a leading `/` added if missing.
+    ///
+    /// When the holder crashes the mutex is handed to the next instance,
+    /// whose `SingleInstance::owner_died` then returns `true`. The mutex
+    /// belongs to the thread that took it, so the instance has to be created
+    /// and dropped on a thread that lives as long as it should be held.
+    #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+    RobustMutex,
+
+    /// The well-known name given, e.g. `org.example.MyApp`, owned on the
+    /// D-Bus session bus.
+    ///
+    /// This is how desktop apps usually claim to be the single instance, and
+    /// lets the running one be activated through the bus. The name is
+    /// released when its holder exits. Requires the `dbus` feature.
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    DBus,
+
+    /// A lock on the file named after the name given in the service's
+    /// runtime directory, see `systemd::lock_path`.
+    ///
+    /// systemd removes the directory once the service stopped, so lock files
+    /// neither pile up nor outlive a restart.
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    Systemd,
+
+    /// The bundle identifier given, e.g. `com.example.MyApp`, not being used
+    /// by any other running app, see `macos`.
+    ///
+    /// Nothing is claimed, so apps launched at the same moment may each see
+    /// the other running. Requires the `bundle-id` feature.
+    #[cfg(all(feature = "bundle-id", target_os = "macos"))]
+    BundleId,
+
+    /// A TCP port on the loopback interface, see `tcp::port`.
+    ///
+    /// Nothing is left behind on the file system, the port is released when
+    /// its holder exits, and other instances can connect to the one holding
+    /// it. Any other program using the port also counts as an instance.
+    #[cfg(all(feature = "tcp-port", any(unix, windows)))]
+    TcpPort,
+
+    /// A unix domain socket bound in the file system, see
+    /// `unix_socket::path`.
+    ///
+    /// Other instances can connect to the one holding it. A socket file left
+    /// behind by a crashed instance is detected by nobody accepting
+    /// connections on it, and replaced.
+    #[cfg(all(feature = "unix-socket", unix, not(target_os = "cygwin")))]
+    UnixSocket,
+
+    /// A PID file at the path given as name, holding the process ID of the
+    /// instance and removed when it is dropped.
+    ///
+    /// No lock is held, so this works on file systems without lock support,
+    /// and the running instance's process ID can be read from the file. A
+    /// file naming a process that no longer runs is replaced. With
+    /// `same_executable` set, so is one naming a process running another
+    /// executable, which only linux and android can tell. Instances replacing
+    /// the same stale file at the same time may both end up single.
+    #[cfg(all(feature = "pid-file", unix, not(target_os = "cygwin")))]
+    PidFile {
+        /// Whether the process named by the file must also run the same
+        /// executable to count as an instance.
+        same_executable: bool,
+    },
+
+    /// A named event instead of the default named mutex, for deployments
+    /// that forbid creating mutexes but allow events.
+    #[cfg(all(feature = "event", any(target_os = "windows", target_os = "cygwin")))]
+    Event,
+
+    /// The first instance of a local named pipe, see `windows::pipe_name`.
+    ///
+    /// Other instances can open the pipe to talk to the one holding it.
+    #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+    NamedPipe,
+}
+
+/// A mechanism supplied by the application, e.g. a client of a lock service,
+/// passed to `SingleInstance::with_custom_backend`.
+pub trait CustomBackend: Send + Sync {
+    /// Claims `name`, returning `false` if another instance holds it.
+    fn acquire(&mut self, name: &str) -> std::result::Result<bool, Box<dyn Error + Send + Sync>>;
+
+    /// Returns whether the name claimed by `acquire` is still held, e.g.
+    /// `false` once a lease expired.
+    fn is_held(&self) -> bool;
+
+    /// Releases the name claimed by `acquire`. Called once when the instance
+    /// is dropped, and only if `acquire` returned `true`.
+    fn release(&mut self);
+}
+
+/// A name claimed with a custom backend, released when dropped.
+pub(crate) struct Custom(Box<dyn CustomBackend>);
+
+impl Custom {
+    /// Claims `name` with `backend`, or returns `None` if another instance
+    /// holds it.
+    pub(crate) fn acquire(mut backend: Box<dyn CustomBackend>, name: &str) -> Result<Option<Self>> {
+        match backend.acquire(name) {
+            Ok(true) => Ok(Some(Custom(backend))),
+            Ok(false) => Ok(None),
+            Err(e) => Err(SingleInstanceError::Custom(e)),
+        }
+    }
+
+    pub(crate) fn is_held(&self) -> bool {
+        self.0.is_held()
+    }
+}
+
+impl Drop for Custom {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
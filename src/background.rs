@@ -0,0 +1,77 @@
+//! Blocking calls run on a thread of their own for async callers.
+//!
+//! Claiming a name or waiting for it may block for as long as the holder
+//! runs, which must not happen on a worker of the caller's runtime. The
+//! futures here are woken by that thread once it is done instead, through
+//! nothing but the standard library, so that they work with any executor,
+//! such as tokio, async-std or smol. Dropping a future cancels the thread's
+//! wait, for the closures that wait, see `Cancel`.
+
+use std::future::Future;
+use cancel::Cancel;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// What the thread returned, once it did, and the waker of the last poll.
+struct Shared<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// A future resolving to what a closure returned on a thread of its own, see
+/// `spawn`.
+pub(crate) struct Background<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    cancel: Arc<Cancel>,
+}
+
+/// Runs `f` on a thread of its own, returning a future resolving to what it
+/// returns. `f` is passed a `Cancel` set once the future is dropped. A panic
+/// of `f` is resumed when the future is polled.
+pub(crate) fn spawn<T, F>(f: F) -> Background<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Arc<Cancel>) -> T + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        waker: None,
+    }));
+    let cancel = Arc::new(Cancel::new());
+    let done = shared.clone();
+    let waiting = cancel.clone();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&waiting)));
+        let mut done = done.lock().unwrap_or_else(|e| e.into_inner());
+        done.result = Some(result);
+        if let Some(waker) = done.waker.take() {
+            waker.wake();
+        }
+    });
+    Background { shared, cancel }
+}
+
+impl<T> Future for Background<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match shared.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Background<T> {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
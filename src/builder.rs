@@ -0,0 +1,232 @@
+//! Configuring a `SingleInstance` before claiming the name.
+//!
+//! `SingleInstanceBuilder` combines the options the constructors of
+//! `SingleInstance` take one at a time, and sets up the holder's handlers as
+//! soon as the name is claimed:
+//!
+//! ```no_run
+//! use single_instance::{Backend, SingleInstance};
+//!
+//! let instance = SingleInstance::builder("my-app")
+//!     .backend(Backend::FileLock)
+//!     .on_activate(|sender| println!("activated by {}", sender.pid))
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use backend::{Backend, CustomBackend};
+use error::Result;
+#[cfg(all(unix, not(target_os = "cygwin")))]
+use std::fs;
+#[cfg(all(unix, not(target_os = "cygwin")))]
+use std::os::unix::fs::{self as unix_fs, PermissionsExt};
+use SingleInstance;
+#[cfg(any(unix, windows))]
+use {Scope, SecondInstance};
+
+#[cfg(any(unix, windows))]
+type Handler = Box<dyn FnMut(SecondInstance) + Send>;
+
+/// Options for claiming a name, returned by `SingleInstance::builder`.
+pub struct SingleInstanceBuilder {
+    name: String,
+    backends: Vec<Backend>,
+    custom: Option<Box<dyn CustomBackend>>,
+    limit: Option<usize>,
+    #[cfg(any(unix, windows))]
+    scope: Option<Scope>,
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    file_mode: Option<u32>,
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    file_group: Option<u32>,
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "cygwin"))))]
+    persist_lock_file: bool,
+    #[cfg(any(unix, windows))]
+    allow_other_users: bool,
+    #[cfg(any(unix, windows))]
+    max_message_size: Option<usize>,
+    #[cfg(any(unix, windows))]
+    on_second_instance: Option<Handler>,
+    #[cfg(any(unix, windows))]
+    on_activate: Option<Handler>,
+}
+
+impl SingleInstanceBuilder {
+    /// Returns the options for claiming `name` with `Backend::Default`.
+    pub fn new(name: &str) -> Self {
+        SingleInstanceBuilder {
+            name: name.to_owned(),
+            backends: Vec::new(),
+            custom: None,
+            limit: None,
+            #[cfg(any(unix, windows))]
+            scope: None,
+            #[cfg(all(unix, not(target_os = "cygwin")))]
+            file_mode: None,
+            #[cfg(all(unix, not(target_os = "cygwin")))]
+            file_group: None,
+            #[cfg(any(target_os = "windows", all(unix, not(target_os = "cygwin"))))]
+            persist_lock_file: false,
+            #[cfg(any(unix, windows))]
+            allow_other_users: false,
+            #[cfg(any(unix, windows))]
+            max_message_size: None,
+            #[cfg(any(unix, windows))]
+            on_second_instance: None,
+            #[cfg(any(unix, windows))]
+            on_activate: None,
+        }
+    }
+
+    /// Claims the name with `backend`, or with the first that works of all
+    /// given, in order, like `SingleInstance::with_backends`.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backends.push(backend);
+        self
+    }
+
+    /// Claims the name with `backend` instead, like
+    /// `SingleInstance::with_custom_backend`.
+    pub fn custom_backend(mut self, backend: Box<dyn CustomBackend>) -> Self {
+        self.custom = Some(backend);
+        self
+    }
+
+    /// Takes one of `limit` slots for the name instead, like
+    /// `SingleInstance::with_limit`. Ignored with a custom backend.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Claims the name within `scope`, like `SingleInstance::with_scope`.
+    #[cfg(any(unix, windows))]
+    pub fn scope(mut self, scope: Scope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Sets the mode of the lock file or socket once this instance claimed
+    /// the name, see `SingleInstance::lock_path`, such as `0o640` for a lock
+    /// file monitoring tools in its group may read. Files are otherwise
+    /// created with the mode the umask leaves.
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    pub fn file_mode(mut self, mode: u32) -> Self {
+        self.file_mode = Some(mode);
+        self
+    }
+
+    /// Sets the group owning the lock file or socket, by its ID, once this
+    /// instance claimed the name, which the process has to be a member of
+    /// unless it is privileged.
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    pub fn file_group(mut self, gid: u32) -> Self {
+        self.file_group = Some(gid);
+        self
+    }
+
+    /// Keeps the lock file as it is once the name is released, see
+    /// `SingleInstance::persist_lock_file`.
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "cygwin"))))]
+    pub fn persist_lock_file(mut self, persist: bool) -> Self {
+        self.persist_lock_file = persist;
+        self
+    }
+
+    /// Listens to instances run by other users too, see
+    /// `SingleInstance::allow_other_users`.
+    #[cfg(any(unix, windows))]
+    pub fn allow_other_users(mut self, allow: bool) -> Self {
+        self.allow_other_users = allow;
+        self
+    }
+
+    /// Rejects messages larger than `size`, see
+    /// `SingleInstance::set_max_message_size`.
+    #[cfg(any(unix, windows))]
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = Some(size);
+        self
+    }
+
+    /// Sets the handler for instances failing to claim the name, see
+    /// `SingleInstance::on_second_instance`.
+    #[cfg(any(unix, windows))]
+    pub fn on_second_instance<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(SecondInstance) + Send + 'static,
+    {
+        self.on_second_instance = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the handler for instances asking to activate this one, see
+    /// `SingleInstance::on_activate`.
+    #[cfg(any(unix, windows))]
+    pub fn on_activate<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(SecondInstance) + Send + 'static,
+    {
+        self.on_activate = Some(Box::new(handler));
+        self
+    }
+
+    /// Claims the name with the options given. The file options, listening
+    /// options and handlers only take effect if this instance is single,
+    /// otherwise they are dropped.
+    pub fn build(self) -> Result<SingleInstance> {
+        #[cfg(any(unix, windows))]
+        let name = match self.scope {
+            Some(ref scope) => scope.apply(&self.name)?,
+            None => self.name,
+        };
+        #[cfg(not(any(unix, windows)))]
+        let name = self.name;
+        #[cfg(any(unix, windows))]
+        let backends = match self.scope {
+            Some(ref scope) if self.backends.is_empty() => vec![scope.backend()],
+            _ => self.backends,
+        };
+        #[cfg(not(any(unix, windows)))]
+        let backends = self.backends;
+        let instance = match (self.custom, self.limit) {
+            (Some(custom), _) => SingleInstance::with_custom_backend(&name, custom),
+            (None, Some(limit)) => SingleInstance::with_limit(&name, limit),
+            (None, None) => SingleInstance::with_backends(&name, &backends),
+        };
+        #[cfg(any(unix, windows))]
+        let instance = match self.scope {
+            Some(ref scope) => scope.claimed(&name, instance),
+            None => instance,
+        };
+        #[cfg_attr(not(any(unix, windows)), allow(unused_mut))]
+        let mut instance = instance?;
+        #[cfg(all(unix, not(target_os = "cygwin")))]
+        if let (true, Some(path)) = (instance.is_single(), instance.lock_path()) {
+            if let Some(gid) = self.file_group {
+                unix_fs::chown(&path, None, Some(gid))?;
+            }
+            if let Some(mode) = self.file_mode {
+                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+            }
+        }
+        #[cfg(any(target_os = "windows", all(unix, not(target_os = "cygwin"))))]
+        instance.persist_lock_file(self.persist_lock_file);
+        #[cfg(any(unix, windows))]
+        if instance.is_single() {
+            if self.allow_other_users {
+                instance.allow_other_users(true)?;
+            }
+            if let Some(size) = self.max_message_size {
+                instance.set_max_message_size(size)?;
+            }
+            if let Some(handler) = self.on_second_instance {
+                instance.on_second_instance(handler)?;
+            }
+            if let Some(handler) = self.on_activate {
+                instance.on_activate(handler)?;
+            }
+        }
+        Ok(instance)
+    }
+}
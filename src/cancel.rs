@@ -0,0 +1,148 @@
+//! Giving up waits for a name, e.g. once the future of
+//! `SingleInstance::wait_until_single_async` is dropped.
+//!
+//! Locks that can be waited for block in the system rather than being polled
+//! for. Waits on windows objects also wait on the event of a `Cancel`, and
+//! other waits run on a thread of their own that the waiter leaves behind,
+//! see `wait_on_thread`.
+
+use std::sync::{Condvar, Mutex};
+#[cfg(all(unix, not(target_os = "cygwin")))]
+use std::sync::Arc;
+#[cfg(all(unix, not(target_os = "cygwin")))]
+use std::thread;
+#[cfg(all(unix, not(target_os = "cygwin")))]
+use std::time::Instant;
+
+#[cfg(target_os = "windows")]
+use std::ptr;
+#[cfg(target_os = "windows")]
+use winapi::um::handleapi::CloseHandle;
+#[cfg(target_os = "windows")]
+use winapi::um::synchapi::{CreateEventW, SetEvent};
+#[cfg(target_os = "windows")]
+use winapi::um::winnt::HANDLE;
+
+/// A flag set to give up waiting, which wakes the waits.
+pub(crate) struct Cancel {
+    cancelled: Mutex<bool>,
+    changed: Condvar,
+    /// A manual-reset event set along with the flag, for waits with
+    /// `WaitForMultipleObjects`, or null if it could not be created.
+    #[cfg(target_os = "windows")]
+    event: HANDLE,
+}
+
+#[cfg(target_os = "windows")]
+unsafe impl Send for Cancel {}
+#[cfg(target_os = "windows")]
+unsafe impl Sync for Cancel {}
+
+impl Cancel {
+    pub(crate) fn new() -> Self {
+        Cancel {
+            cancelled: Mutex::new(false),
+            changed: Condvar::new(),
+            #[cfg(target_os = "windows")]
+            event: unsafe { CreateEventW(ptr::null_mut(), 1, 0, ptr::null()) },
+        }
+    }
+
+    /// Sets the flag, waking the waits.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))] // Only needed for `background`.
+    pub(crate) fn cancel(&self) {
+        *self.cancelled.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.changed.notify_all();
+        #[cfg(target_os = "windows")]
+        {
+            if !self.event.is_null() {
+                unsafe { SetEvent(self.event) };
+            }
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        *self.cancelled.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the event set along with the flag, or null if there is none.
+    #[cfg(target_os = "windows")]
+    pub(crate) fn event(&self) -> HANDLE {
+        self.event
+    }
+
+    /// Wakes the waits to check what they wait for.
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    fn wake(&self) {
+        let _cancelled = self.cancelled.lock().unwrap_or_else(|e| e.into_inner());
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for Cancel {
+    fn drop(&mut self) {
+        if !self.event.is_null() {
+            unsafe { CloseHandle(self.event) };
+        }
+    }
+}
+
+/// What a wait on a thread of its own returned, and whether the waiter gave
+/// up on it.
+#[cfg(all(unix, not(target_os = "cygwin")))]
+struct Slot<T> {
+    value: Option<T>,
+    abandoned: bool,
+}
+
+/// Runs the blocking `wait` on a thread of its own and returns what it
+/// returned, or `None` if `deadline` passed or `cancel` was set first.
+///
+/// A wait given up on keeps blocking, as nothing interrupts it, and what it
+/// returns is dropped: a lock it took once the holder released it is
+/// released right away. So is one taken just before `cancel` was set.
+#[cfg(all(unix, not(target_os = "cygwin")))]
+pub(crate) fn wait_on_thread<T, F>(wait: F, deadline: Option<Instant>, cancel: &Arc<Cancel>) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let slot = Arc::new(Mutex::new(Slot {
+        value: None,
+        abandoned: false,
+    }));
+    let filled = slot.clone();
+    let waker = cancel.clone();
+    thread::spawn(move || {
+        let value = wait();
+        {
+            let mut slot = filled.lock().unwrap_or_else(|e| e.into_inner());
+            if slot.abandoned {
+                return;
+            }
+            slot.value = Some(value);
+        }
+        waker.wake();
+    });
+
+    let mut cancelled = cancel.cancelled.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        {
+            let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+            let expired = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if slot.value.is_some() || *cancelled || expired {
+                slot.abandoned = true;
+                let cancelled = *cancelled;
+                return slot.value.take().filter(|_| !cancelled);
+            }
+        }
+        cancelled = match deadline {
+            Some(deadline) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                cancel.changed.wait_timeout(cancelled, timeout).unwrap_or_else(|e| e.into_inner()).0
+            }
+            None => cancel.changed.wait(cancelled).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
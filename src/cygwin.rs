@@ -0,0 +1,37 @@
+//! Win32 bindings for cygwin.
+//!
+//! Cygwin programs can call into the Win32 API, so names are claimed as the
+//! same named mutex as on windows rather than as lock files, whose location
+//! would depend on the current directory and on how the cygwin or MSYS2 root
+//! is mounted. Instances are thus detected across cygwin, MSYS2 and native
+//! windows builds of a program.
+
+use std::os::raw::{c_int, c_void};
+
+pub type HANDLE = *mut c_void;
+
+pub const ERROR_ACCESS_DENIED: u32 = 5;
+pub const ERROR_INVALID_HANDLE: u32 = 6;
+pub const ERROR_ALREADY_EXISTS: u32 = 183;
+pub const WAIT_OBJECT_0: u32 = 0;
+pub const WAIT_TIMEOUT: u32 = 258;
+
+#[link(name = "kernel32")]
+extern "system" {
+    #[cfg(feature = "event")]
+    pub fn CreateEventW(
+        attributes: *mut c_void,
+        manual_reset: c_int,
+        initial_state: c_int,
+        name: *const u16,
+    ) -> HANDLE;
+    pub fn CreateMutexW(attributes: *mut c_void, initial_owner: c_int, name: *const u16) -> HANDLE;
+    pub fn CreateSemaphoreW(attributes: *mut c_void, initial_count: c_int, maximum_count: c_int, name: *const u16)
+        -> HANDLE;
+    pub fn WaitForSingleObject(handle: HANDLE, milliseconds: u32) -> u32;
+    pub fn ReleaseSemaphore(handle: HANDLE, release_count: c_int, previous_count: *mut c_int) -> c_int;
+    pub fn GetLastError() -> u32;
+    pub fn CloseHandle(handle: HANDLE) -> c_int;
+    pub fn GetCurrentProcessId() -> u32;
+    pub fn ProcessIdToSessionId(process_id: u32, session_id: *mut u32) -> c_int;
+}
@@ -0,0 +1,108 @@
+//! Well-known D-Bus names on the session bus.
+//!
+//! The connection owning a name can also serve the `org.freedesktop.Application`
+//! interface, through which desktop environments activate applications whose
+//! desktop file sets `DBusActivatable=true`, ask them to open files and
+//! activate their actions. The interface is served at the object path derived
+//! from the name, with each `.` turned into a `/` and each `-` into a `_`.
+
+use notify::{Dispatcher, SecondInstance};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use zbus::blocking::Connection;
+use zbus::fdo::{RequestNameFlags, RequestNameReply};
+use zbus::zvariant::OwnedValue;
+
+/// Returns a session bus connection owning the well-known name `name`, or
+/// `None` if another connection owns it.
+///
+/// The name is released when the connection is dropped or its process exits.
+pub fn try_own(name: &str) -> zbus::Result<Option<Connection>> {
+    let conn = Connection::session()?;
+    match conn.request_name_with_flags(name, RequestNameFlags::DoNotQueue.into())? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(Some(conn)),
+        RequestNameReply::InQueue | RequestNameReply::Exists => Ok(None),
+    }
+}
+
+/// Serves `org.freedesktop.Application` for the name `name` owned by `conn`,
+/// passing what is asked for to the handlers of `dispatcher`.
+pub(crate) fn serve_application(conn: &Connection, name: &str, dispatcher: Dispatcher) -> zbus::Result<()> {
+    conn.object_server().at(object_path(name), Application { dispatcher })?;
+    Ok(())
+}
+
+/// Returns the object path the application named `name` is served at.
+fn object_path(name: &str) -> String {
+    format!("/{}", name.replace('.', "/").replace('-', "_"))
+}
+
+/// The `org.freedesktop.Application` interface.
+struct Application {
+    dispatcher: Dispatcher,
+}
+
+#[zbus::interface(name = "org.freedesktop.Application")]
+impl Application {
+    fn activate(&self, platform_data: HashMap<String, OwnedValue>) {
+        self.dispatcher.activate(sender(&platform_data));
+    }
+
+    fn open(&self, uris: Vec<String>, _hint: String, platform_data: HashMap<String, OwnedValue>) {
+        let (paths, urls): (Vec<_>, Vec<_>) = uris.into_iter().partition(|uri| file_path(uri).is_some());
+        let sender = sender(&platform_data);
+        if !paths.is_empty() {
+            let paths = paths.iter().filter_map(|uri| file_path(uri)).collect();
+            self.dispatcher.open(sender.clone(), paths);
+        }
+        if !urls.is_empty() {
+            self.dispatcher.urls(sender, urls);
+        }
+    }
+
+    fn activate_action(&self, action_name: String, parameter: Vec<OwnedValue>, platform_data: HashMap<String, OwnedValue>) {
+        self.dispatcher.action(sender(&platform_data), action_name, parameter.into_iter().next());
+    }
+}
+
+/// Describes the launcher asking with `platform_data`, of which only the
+/// activation token is known.
+fn sender(platform_data: &HashMap<String, OwnedValue>) -> SecondInstance {
+    let token = ["activation-token", "desktop-startup-id"]
+        .iter()
+        .filter_map(|key| platform_data.get(*key))
+        .find_map(|value| value.downcast_ref::<&str>().ok().map(str::to_owned));
+    SecondInstance {
+        pid: 0,
+        cwd: PathBuf::new(),
+        env: Vec::new(),
+        activation_token: token.filter(|token| !token.is_empty()),
+    }
+}
+
+/// Returns the path a local `file://` URI points to, percent-decoded.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    // Only the local host is understood.
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    if !path.starts_with('/') {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    Some(PathBuf::from(OsStr::from_bytes(&bytes)))
+}
@@ -0,0 +1,25 @@
+//! Desktop notifications shown by instances failing to claim a name.
+//!
+//! Notifications go through the notification service of the desktop over
+//! D-Bus on linux, the notification center on macos and toasts on windows.
+
+use notify_rust::Notification;
+use std::sync::Mutex;
+
+/// The text shown when an instance fails to claim a name.
+static TEXT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Sets the text shown from now on, or shows nothing if `None`.
+pub(crate) fn set(text: Option<String>) {
+    *TEXT.lock().unwrap_or_else(|e| e.into_inner()) = text;
+}
+
+/// Shows the notification set, if any.
+pub(crate) fn show() {
+    let text = TEXT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(text) = text {
+        // Intentionally discard any errors, there may be no notification
+        // service to show it.
+        let _ = Notification::new().summary(&text).show();
+    }
+}
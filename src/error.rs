@@ -1,22 +1,98 @@
 use thiserror::Error;
 
+/// Which variants exist depends on the platform and the cargo features
+/// enabled, so matches need a wildcard arm.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum SingleInstanceError {
-    #[cfg(any(target_os = "linux", target_os="android"))]
+    #[cfg(abstract_socket)]
     #[error("new abstract addr error")]
     Nix(#[from] nix::Error),
 
-    #[cfg(target_os = "macos")]
+    #[cfg(any(unix, windows, target_os = "wasi"))]
     #[error("file open or create error")]
     Io(#[from] std::io::Error),
 
-    #[cfg(target_os = "windows")]
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    #[error("D-Bus error")]
+    DBus(#[from] zbus::Error),
+
+    #[cfg(any(target_os = "windows", target_os = "cygwin"))]
     #[error("wide string null error")]
-    Nul(#[from] widestring::NulError<widestring::WideChar>),
+    Nul(#[from] widestring::NulError<u16>),
 
-    #[cfg(target_os = "windows")]
+    #[cfg(any(target_os = "windows", target_os = "cygwin"))]
     #[error("CreateMutex failed with error code {0}")]
     MutexError(u32),
+
+    #[cfg(any(target_os = "windows", target_os = "cygwin"))]
+    #[error("taking a semaphore failed with error code {0}")]
+    SemaphoreError(u32),
+
+    #[cfg(all(feature = "event", any(target_os = "windows", target_os = "cygwin")))]
+    #[error("CreateEvent failed with error code {0}")]
+    EventError(u32),
+
+    #[cfg(feature = "serde")]
+    #[error("message serialization error")]
+    Serde(#[from] serde_json::Error),
+
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack encoding error")]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack decoding error")]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+
+    #[error("another instance holds the name")]
+    AlreadyRunning {
+        /// The PID of the instance holding the name, if known, see
+        /// `SingleInstance::holder_pid`.
+        pid: Option<u32>,
+    },
+
+    /// The name cannot be claimed with the backend, and sanitizing it is
+    /// disabled, see `names`.
+    #[error("invalid name: {0}")]
+    InvalidName(String),
+
+    /// The name cannot be claimed in the scope asked for, such as
+    /// `Scope::Machine`, with the rights of this process.
+    #[cfg(any(unix, windows))]
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// `SingleInstance::kill_other` was asked to stop the instance holding
+    /// the name, which is this process.
+    #[cfg(any(unix, windows))]
+    #[error("the holder of the name is this process")]
+    HolderIsSelf,
+
+    /// `SingleInstance::kill_other` left the process holding the name alone,
+    /// as it runs another executable than this process, e.g. since its PID
+    /// was reused.
+    #[cfg(any(unix, windows))]
+    #[error("process {pid} holding the name runs another executable")]
+    DifferentExecutable {
+        /// The PID of the process holding the name.
+        pid: u32,
+    },
+
+    /// Names are claimed asynchronously in the browser, with
+    /// `SingleInstance::new_async` of the `wasm` feature.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    #[error("names can only be claimed asynchronously here")]
+    Asynchronous,
+
+    /// The Web Locks API is missing, e.g. outside a secure context, or
+    /// failed.
+    #[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+    #[error("Web Locks API error: {0}")]
+    WebLocks(String),
+
+    #[error("custom backend error")]
+    Custom(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
 pub type Result<T> = std::result::Result<T, SingleInstanceError>;
\ No newline at end of file
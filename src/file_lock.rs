@@ -0,0 +1,280 @@
+//! Advisory locks on a whole file, shared by the unix backends.
+
+use holder;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A held lock, released when dropped.
+pub struct FileLock {
+    _file: File,
+    /// Device and inode of a file locked with a classic `fcntl` lock, see
+    /// `try_fcntl`.
+    fcntl_id: Option<(u64, u64)>,
+    /// Whether this process is recorded as the holder in the file, see
+    /// `holder`.
+    recorded: bool,
+    /// Whether the record is kept when dropped, see `set_persist`.
+    persist: bool,
+}
+
+/// Files locked with classic `fcntl` locks by this process.
+static HELD: Mutex<Vec<(u64, u64)>> = Mutex::new(Vec::new());
+
+/// The platform's default lock. Linux keeps the `fcntl` lock its file lock
+/// backend has always used.
+#[cfg(default_flock)]
+pub use self::{try_flock as try_lock, wait_flock as wait_lock};
+#[cfg(not(default_flock))]
+pub use self::{try_fcntl as try_lock, wait_fcntl as wait_lock};
+
+/// Returns the `flock` lock on the file at `path`, creating the file if
+/// needed, or `None` if another instance holds it.
+///
+/// The lock belongs to the open file rather than the process, so it is
+/// neither shared between instances within one process nor released when
+/// another descriptor of the file is closed. The holder is recorded in the
+/// file, unless it has other contents or may only be read, see `holder`.
+#[cfg(flock)]
+pub fn try_flock(path: &Path) -> io::Result<Option<FileLock>> {
+    flock_file(path, false)
+}
+
+/// Returns the `flock` lock on the file at `path` like `try_flock`, waiting
+/// for the holder to release it.
+#[cfg(flock)]
+pub fn wait_flock(path: &Path) -> io::Result<Option<FileLock>> {
+    flock_file(path, true)
+}
+
+#[cfg(flock)]
+fn flock_file(path: &Path, wait: bool) -> io::Result<Option<FileLock>> {
+    use libc::{flock, EINTR, EWOULDBLOCK, LOCK_EX, LOCK_NB};
+    use std::fs::OpenOptions;
+
+    // A file of another user, e.g. one claimed machine-wide, may only be
+    // readable, which is enough to lock it.
+    let file = match OpenOptions::new().read(true).write(true).open(path) {
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?,
+        Err(ref error) if error.kind() == io::ErrorKind::PermissionDenied => File::open(path)?,
+        file => file?,
+    };
+    let operation = if wait { LOCK_EX } else { LOCK_EX | LOCK_NB };
+    while unsafe { flock(file.as_raw_fd(), operation) } != 0 {
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(EINTR) if wait => {}
+            Some(EWOULDBLOCK) => return Ok(None),
+            _ => return Err(err),
+        }
+    }
+    Ok(Some(FileLock {
+        recorded: holder::record(&file).unwrap_or(false),
+        _file: file,
+        fcntl_id: None,
+        persist: false,
+    }))
+}
+
+/// Returns the `fcntl` write lock over the whole file at `path`, creating
+/// the file if needed, or `None` if another instance holds it.
+///
+/// Solaris has no `flock`, AIX only provides it through libbsd and other
+/// unix platforms are not known to have it, so this is their default lock,
+/// which POSIX requires everywhere.
+///
+/// On linux an open file description lock is taken, which belongs to the
+/// descriptor like a `flock` lock and still conflicts with classic record
+/// locks taken by other programs. The holder is also recorded in the file,
+/// unless it has other contents, see `holder`.
+///
+/// Classic record locks belong to the process rather than the descriptor:
+/// locking the same file twice from one process succeeds, and closing any
+/// descriptor of the file drops the lock. Files locked this way are
+/// therefore tracked by device and inode and checked before being opened.
+#[cfg(not(default_flock))]
+pub fn try_fcntl(path: &Path) -> io::Result<Option<FileLock>> {
+    use libc::{fcntl, EACCES, EAGAIN, F_SETLK};
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    if let Ok(meta) = fs::metadata(path) {
+        if held.contains(&(meta.dev(), meta.ino())) {
+            return Ok(None);
+        }
+    }
+
+    let file = open_fcntl(path)?;
+    let meta = file.metadata()?;
+    let id = (meta.dev(), meta.ino());
+    let lock = whole_file();
+
+    #[cfg(target_os = "linux")]
+    {
+        if unsafe { fcntl(file.as_raw_fd(), libc::F_OFD_SETLK, &lock) } == 0 {
+            let recorded = holder::record(&file).unwrap_or(false);
+            return Ok(Some(FileLock {
+                _file: file,
+                fcntl_id: None,
+                recorded,
+                persist: false,
+            }));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(EAGAIN) | Some(EACCES) => return Ok(None),
+            // Kernels before 3.15 know no open file description locks.
+            Some(libc::EINVAL) => {}
+            _ => return Err(err),
+        }
+    }
+
+    if unsafe { fcntl(file.as_raw_fd(), F_SETLK, &lock) } != 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(EAGAIN) | Some(EACCES) => Ok(None),
+            _ => Err(err),
+        };
+    }
+
+    held.push(id);
+    Ok(Some(FileLock {
+        recorded: holder::record(&file).unwrap_or(false),
+        _file: file,
+        fcntl_id: Some(id),
+        persist: false,
+    }))
+}
+
+/// Returns the open file description lock on the file at `path` like
+/// `try_fcntl`, waiting for the holder to release it with `F_OFD_SETLKW`, or
+/// `None` right away if there are only classic record locks. Those belong to
+/// the process, which other instances in it would then share while this one
+/// waits, and are polled for with `try_fcntl` instead.
+#[cfg(not(default_flock))]
+pub fn wait_fcntl(path: &Path) -> io::Result<Option<FileLock>> {
+    #[cfg(target_os = "linux")]
+    {
+        use libc::{fcntl, EINTR, F_OFD_SETLKW};
+
+        let file = open_fcntl(path)?;
+        let lock = whole_file();
+        while unsafe { fcntl(file.as_raw_fd(), F_OFD_SETLKW, &lock) } != 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(EINTR) => {}
+                Some(libc::EINVAL) => return Ok(None),
+                _ => return Err(err),
+            }
+        }
+        Ok(Some(FileLock {
+            recorded: holder::record(&file).unwrap_or(false),
+            _file: file,
+            fcntl_id: None,
+            persist: false,
+        }))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        Ok(None)
+    }
+}
+
+/// Opens the file at `path` to lock it with `fcntl`, creating it if needed.
+#[cfg(not(default_flock))]
+fn open_fcntl(path: &Path) -> io::Result<File> {
+    use std::fs::OpenOptions;
+
+    // Only create the file if it is missing: linux refuses `O_CREAT` on
+    // files of other users in sticky directories such as `/tmp` with
+    // `fs.protected_regular` set.
+    match OpenOptions::new().read(true).write(true).open(path) {
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path),
+        file => file,
+    }
+}
+
+/// Returns a write lock over the whole file for `fcntl`.
+#[cfg(not(default_flock))]
+fn whole_file() -> libc::flock {
+    use libc::{F_WRLCK, SEEK_SET};
+    use std::mem;
+
+    let mut lock: libc::flock = unsafe { mem::zeroed() };
+    lock.l_type = F_WRLCK as libc::c_short;
+    lock.l_whence = SEEK_SET as libc::c_short;
+    lock
+}
+
+/// Returns the PID of the process holding a classic `fcntl` lock on the file
+/// at `path`, as `F_GETLK` reports it, or `None` if there is none. Open file
+/// description and `flock` locks belong to no process and are not reported.
+pub fn holder_pid(path: &Path) -> Option<u32> {
+    use libc::{fcntl, F_GETLK, F_UNLCK, F_WRLCK, SEEK_SET};
+    use std::fs;
+    use std::mem;
+    use std::os::unix::fs::MetadataExt;
+    use std::process;
+
+    // Closing a descriptor of a file this process locked drops the lock, and
+    // `F_GETLK` never reports the caller's own locks.
+    let held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    let meta = fs::metadata(path).ok()?;
+    if held.contains(&(meta.dev(), meta.ino())) {
+        return Some(process::id());
+    }
+
+    let file = File::open(path).ok()?;
+    let mut lock: libc::flock = unsafe { mem::zeroed() };
+    lock.l_type = F_WRLCK as libc::c_short;
+    lock.l_whence = SEEK_SET as libc::c_short;
+    if unsafe { fcntl(file.as_raw_fd(), F_GETLK, &mut lock) } != 0 {
+        return None;
+    }
+    let unlocked = lock.l_type == F_UNLCK as libc::c_short;
+    if unlocked || lock.l_pid <= 0 {
+        return None;
+    }
+    Some(lock.l_pid as u32)
+}
+
+impl FileLock {
+    /// Keeps the holder recorded in the file when dropped, like a PID file
+    /// left behind, if `persist` is set.
+    pub fn set_persist(&mut self, persist: bool) {
+        self.persist = persist;
+    }
+}
+
+impl AsRawFd for FileLock {
+    fn as_raw_fd(&self) -> RawFd {
+        self._file.as_raw_fd()
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if self.recorded && !self.persist {
+            // Intentionally discard any errors, the record is then stale.
+            let _ = holder::clear(&self._file);
+        }
+        if let Some(fcntl_id) = self.fcntl_id {
+            let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+            held.retain(|id| *id != fcntl_id);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! Stable hashing of instance names.
+
+/// Returns the 32 bit FNV-1a hash of `name`, which unlike the hashers in std
+/// is the same in every build of every program.
+pub fn hash(name: &str) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
@@ -0,0 +1,79 @@
+//! Holding a name, as a type of its own.
+//!
+//! A `SingleInstance` may or may not hold its name, which callers have to
+//! check with `is_single`. An `InstanceGuard` is only handed out for one that
+//! does, by `SingleInstance::acquire`, `SingleInstance::try_acquire` or
+//! `SingleInstance::into_guard`, so that holding one proves it:
+//!
+//! ```no_run
+//! use single_instance::SingleInstance;
+//!
+//! let guard = SingleInstance::try_acquire("my-app").unwrap();
+//! std::thread::spawn(move || {
+//!     // The name is held until the guard is dropped here.
+//!     drop(guard);
+//! });
+//! ```
+
+use std::ops::{Deref, DerefMut};
+#[cfg(all(unix, not(target_os = "cygwin")))]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(target_os = "windows")]
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use SingleInstance;
+
+/// An instance holding its name, released when dropped.
+///
+/// It derefs to the `SingleInstance`, whose holder methods, such as
+/// `SingleInstance::on_activate`, can be called through it. Calling
+/// `SingleInstance::release` through it gives the name up all the same.
+pub struct InstanceGuard(SingleInstance);
+
+impl InstanceGuard {
+    /// Returns the instance, which still holds the name.
+    pub fn into_inner(self) -> SingleInstance {
+        self.0
+    }
+}
+
+impl SingleInstance {
+    /// Returns a guard for this instance if it holds the name, or this
+    /// instance back if it does not.
+    // The instance is handed back as is, like `Arc::try_unwrap` does.
+    #[allow(clippy::result_large_err)]
+    pub fn into_guard(self) -> Result<InstanceGuard, SingleInstance> {
+        if self.is_single() {
+            Ok(InstanceGuard(self))
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl Deref for InstanceGuard {
+    type Target = SingleInstance;
+
+    fn deref(&self) -> &SingleInstance {
+        &self.0
+    }
+}
+
+impl DerefMut for InstanceGuard {
+    fn deref_mut(&mut self) -> &mut SingleInstance {
+        &mut self.0
+    }
+}
+
+#[cfg(all(unix, not(target_os = "cygwin")))]
+impl AsRawFd for InstanceGuard {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl AsRawHandle for InstanceGuard {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.0.as_raw_handle()
+    }
+}
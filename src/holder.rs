@@ -0,0 +1,203 @@
+//! The process holding a lock file, recorded in the file on unix.
+//!
+//! The holder of a lock file writes its `LockInfo` into it, unless the file
+//! has other contents, and empties it again when releasing the lock.
+//! Other instances check the start time of the process it names, so that a
+//! reused PID is never mistaken for the holder, and on linux open a pidfd
+//! for it. Where the start time cannot be told, it is recorded as 0 and only
+//! whether the process still runs is checked.
+
+use libc::pid_t;
+use lock_info::LockInfo;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process;
+#[cfg(target_os = "linux")]
+use std::cmp;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+#[cfg(target_os = "linux")]
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+/// How much of a lock file is read to find a record in it, enough for the
+/// path of an executable.
+const RECORD_LEN: u64 = 4096;
+
+/// The process holding a lock file.
+#[cfg(target_os = "linux")]
+pub struct Holder {
+    pid: pid_t,
+    start_time: u64,
+    /// Missing on kernels before 5.3.
+    pidfd: Option<OwnedFd>,
+}
+
+#[cfg(target_os = "linux")]
+impl Holder {
+    /// Returns the running process `pid`, or `None` if it exited.
+    pub fn open(pid: u32) -> Option<Holder> {
+        let pid = pid as pid_t;
+        let pidfd = pidfd_open(pid);
+        // Once the pidfd is open, the PID cannot be reused anymore, so the
+        // start time read after it is the one of the process it refers to.
+        let start_time = start_time(pid).ok()?;
+        Some(Holder { pid, start_time, pidfd })
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid as u32
+    }
+
+    /// Waits up to `timeout` for the holder to exit, returning whether it did.
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let pidfd = match self.pidfd {
+            Some(ref pidfd) => pidfd,
+            None => return self.poll_proc(deadline),
+        };
+        loop {
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now()).as_millis();
+                    cmp::min(remaining, i32::MAX as u128) as i32
+                }
+                None => -1,
+            };
+            let mut poll = libc::pollfd {
+                fd: pidfd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            match unsafe { libc::poll(&mut poll, 1, remaining) } {
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                -1 => return Err(io::Error::last_os_error()),
+                0 => return Ok(false),
+                _ => return Ok(true),
+            }
+        }
+    }
+
+    /// Waits for the holder to exit by polling `/proc`, without a pidfd.
+    fn poll_proc(&self, deadline: Option<Instant>) -> io::Result<bool> {
+        loop {
+            if start_time(self.pid).ok() != Some(self.start_time) {
+                return Ok(true);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Records this process as the holder of the locked `file`, returning `false`
+/// if the file has other contents and was left alone.
+pub fn record(file: &File) -> io::Result<bool> {
+    let mut contents = String::new();
+    let mut file = file;
+    if file.take(RECORD_LEN).read_to_string(&mut contents).is_err()
+        || !(contents.is_empty() || LockInfo::parse(&contents).is_some())
+    {
+        return Ok(false);
+    }
+    let record = LockInfo::current(start_time(process::id() as pid_t)?).encode();
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(record.as_bytes())?;
+    Ok(true)
+}
+
+/// Removes the record from the locked `file`.
+pub fn clear(file: &File) -> io::Result<()> {
+    file.set_len(0)
+}
+
+/// Returns the record of the holder of the lock file at `path`, or `None` if
+/// there is no record or the process it names exited.
+pub fn read(path: &Path) -> Option<LockInfo> {
+    let info = read_record(path)?;
+    if !is_running(info.pid as pid_t) || start_time(info.pid as pid_t).ok()? != info.start_time {
+        return None;
+    }
+    Some(info)
+}
+
+/// Returns the process recorded as the holder of the lock file at `path`, or
+/// `None` if there is no record or the process it names exited.
+#[cfg(target_os = "linux")]
+pub fn find(path: &Path) -> Option<Holder> {
+    let info = read_record(path)?;
+    let holder = Holder::open(info.pid)?;
+    // The PID may have been reused since it was recorded, which the start
+    // time tells.
+    if holder.start_time != info.start_time {
+        return None;
+    }
+    Some(holder)
+}
+
+/// Returns the record in the lock file at `path`, if any.
+fn read_record(path: &Path) -> Option<LockInfo> {
+    let mut contents = String::new();
+    File::open(path)
+        .ok()?
+        .take(RECORD_LEN)
+        .read_to_string(&mut contents)
+        .ok()?;
+    LockInfo::parse(&contents)
+}
+
+/// Returns whether the process `pid` exists, also if it belongs to another
+/// user.
+fn is_running(pid: pid_t) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+}
+
+/// Returns the start time of the process `pid` in clock ticks since boot.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn start_time(pid: pid_t) -> io::Result<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    // The command name in parentheses may contain spaces, the start time is
+    // the 20th field after it.
+    stat.rsplit_once(')')
+        .and_then(|(_, fields)| fields.split_whitespace().nth(19))
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed /proc stat"))
+}
+
+/// Returns the start time of the process `pid` in microseconds since 1970.
+#[cfg(target_os = "macos")]
+fn start_time(pid: pid_t) -> io::Result<u64> {
+    use std::mem;
+
+    let mut info: libc::proc_bsdinfo = unsafe { mem::zeroed() };
+    let size = mem::size_of::<libc::proc_bsdinfo>() as libc::c_int;
+    let len = unsafe { libc::proc_pidinfo(pid, libc::PROC_PIDTBSDINFO, 0, &mut info as *mut _ as *mut _, size) };
+    if len != size {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(info.pbi_start_tvsec * 1_000_000 + info.pbi_start_tvusec)
+}
+
+/// Returns 0, as the start time of a process cannot be told here.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn start_time(_pid: pid_t) -> io::Result<u64> {
+    Ok(0)
+}
+
+/// Returns a pidfd for the process `pid`, or `None` if the kernel has none.
+#[cfg(target_os = "linux")]
+fn pidfd_open(pid: pid_t) -> Option<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        None
+    } else {
+        Some(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+    }
+}
@@ -0,0 +1,63 @@
+//! What the process holding a name is, for telling users about it.
+//!
+//! `SingleInstance::holder_pid` only tells which process holds the name. With
+//! the `sysinfo` feature, `SingleInstance::holder_info` looks that process up
+//! for a message such as "already running as PID 1234, started 2h ago by bob":
+//!
+//! ```no_run
+//! use single_instance::SingleInstance;
+//!
+//! let instance = SingleInstance::new("my-app").unwrap();
+//! if let Some(info) = instance.holder_info() {
+//!     let age = info.start_time.and_then(|time| time.elapsed().ok());
+//!     eprintln!("already running as PID {} for {:?}", info.pid, age);
+//! }
+//! ```
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sysinfo::{Pid, ProcessRefreshKind, System, UpdateKind, Users};
+use SingleInstance;
+
+/// The process holding a name, see `SingleInstance::holder_info`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HolderInfo {
+    /// The PID of the process.
+    pub pid: u32,
+    /// The path of its executable, if it may be read.
+    pub exe: Option<PathBuf>,
+    /// When it started, to the second.
+    pub start_time: Option<SystemTime>,
+    /// The name of the user running it.
+    pub user: Option<String>,
+}
+
+impl SingleInstance {
+    /// Returns the PID, executable, start time and user of the instance
+    /// holding the name, or `None` if its PID is not known, see `holder_pid`,
+    /// or the process already exited.
+    pub fn holder_info(&self) -> Option<HolderInfo> {
+        let pid = self.holder_pid()?;
+        let mut system = System::new();
+        let refresh = ProcessRefreshKind::new()
+            .with_exe(UpdateKind::OnlyIfNotSet)
+            .with_user(UpdateKind::OnlyIfNotSet);
+        if !system.refresh_process_specifics(Pid::from_u32(pid), refresh) {
+            return None;
+        }
+        let process = system.process(Pid::from_u32(pid))?;
+        let user = process.user_id().and_then(|uid| {
+            Users::new_with_refreshed_list()
+                .get_user_by_id(uid)
+                .map(|user| user.name().to_owned())
+        });
+        Some(HolderInfo {
+            pid,
+            exe: process.exe().map(PathBuf::from),
+            start_time: Some(process.start_time())
+                .filter(|secs| *secs != 0)
+                .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            user,
+        })
+    }
+}
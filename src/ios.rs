@@ -0,0 +1,77 @@
+//! iOS specific configuration.
+//!
+//! Apps only have write access to their own container, so names are resolved
+//! into the container's temporary directory by default. Processes sharing an
+//! app group, such as an app and its extensions or helpers, can coordinate by
+//! resolving names into the group container instead:
+//!
+//! ```rust,no_run
+//! use single_instance::ios;
+//!
+//! if let Some(dir) = ios::app_group_container("group.com.example.app") {
+//!     ios::set_lock_dir(dir);
+//! }
+//! ```
+
+use std::env;
+use std::ffi::{CStr, CString, OsStr};
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+pub use lock_dir::{lock_dir, set_lock_dir};
+
+type Id = *mut c_void;
+type Sel = *mut c_void;
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Sel;
+    fn objc_msgSend();
+}
+
+#[link(name = "Foundation", kind = "framework")]
+extern "C" {}
+
+/// Returns the shared container directory of the app group `group_id`, or
+/// `None` if the app is not entitled to use that group.
+pub fn app_group_container(group_id: &str) -> Option<PathBuf> {
+    let group_id = CString::new(group_id).ok()?;
+    unsafe {
+        let send: unsafe extern "C" fn(Id, Sel) -> Id =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let send_arg: unsafe extern "C" fn(Id, Sel, *const c_void) -> Id =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+        let manager = send(
+            objc_getClass(b"NSFileManager\0".as_ptr() as _),
+            sel_registerName(b"defaultManager\0".as_ptr() as _),
+        );
+        let group_id = send_arg(
+            objc_getClass(b"NSString\0".as_ptr() as _),
+            sel_registerName(b"stringWithUTF8String:\0".as_ptr() as _),
+            group_id.as_ptr() as _,
+        );
+        let url = send_arg(
+            manager,
+            sel_registerName(b"containerURLForSecurityApplicationGroupIdentifier:\0".as_ptr() as _),
+            group_id,
+        );
+        if url.is_null() {
+            return None;
+        }
+        let path = send(url, sel_registerName(b"fileSystemRepresentation\0".as_ptr() as _)) as *const c_char;
+        if path.is_null() {
+            return None;
+        }
+        Some(PathBuf::from(OsStr::from_bytes(CStr::from_ptr(path).to_bytes())))
+    }
+}
+
+/// Returns the lock file path for `name`.
+pub(crate) fn lock_path(name: &str) -> PathBuf {
+    let dir = lock_dir().unwrap_or_else(env::temp_dir);
+    ::lock_dir::resolve(&dir, name)
+}
@@ -0,0 +1,131 @@
+//! Newline delimited JSON for processes not written in Rust.
+//!
+//! The instance holding a name accepts JSON lines once it called
+//! `SingleInstance::json_lines`, on the same socket as other instances'
+//! messages, whose address `address` returns:
+//!
+//! - on linux and android an abstract unix socket, written with a leading `@`
+//!   that stands for the NUL byte the name starts with,
+//! - on other unix systems a unix socket at a path,
+//! - on windows a TCP port on `127.0.0.1`.
+//!
+//! Every line sent is a JSON object with the fields `pid` and `cwd`
+//! describing the sender, both optional, and `data` holding any JSON value.
+//! Where the process ID of the connection is known, lines with another `pid`
+//! are refused. The holder answers each line in order with a line of its own, either
+//! `{"reply":value}`, where the value is `null` if it gave none, or
+//! `{"error":"description"}` if the line was not understood. Lines are UTF-8
+//! and end with `\n`, and the JSON in them must not contain line breaks.
+//!
+//! On unix the address is the calling user's, as holders listen for every
+//! user of their own. From Python on linux, for example, with the address for
+//! the name and user 1000:
+//!
+//! ```python
+//! import json, os, socket
+//!
+//! s = socket.socket(socket.AF_UNIX)
+//! s.connect("\0single-instance-1000-1a2b3c4d")
+//! s.sendall(json.dumps({"pid": os.getpid(), "data": ["open", "a.txt"]}).encode() + b"\n")
+//! print(json.loads(s.makefile().readline()))
+//! ```
+
+use notify::{self, SecondInstance, Stream};
+use peer;
+use serde_json::{self, Value};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+
+/// Returns the address of the socket the instance holding `name` accepts
+/// JSON lines on from this user.
+pub fn address(name: &str) -> String {
+    notify::address(name)
+}
+
+/// A JSON line received from `SingleInstance::json_lines`.
+pub struct JsonMessage {
+    /// The process that sent the line, as far as it told, with the process
+    /// ID of the connection where it is known.
+    pub sender: SecondInstance,
+    /// The `data` of the line.
+    pub data: Value,
+    reply: Sender<Value>,
+}
+
+impl JsonMessage {
+    /// Answers the line with `value`, which is otherwise answered with `null`
+    /// once the message is dropped.
+    pub fn reply(self, value: Value) {
+        // Intentionally discard any errors, the sender may be gone.
+        let _ = self.reply.send(value);
+    }
+}
+
+/// Parses a line into its sender and data, sent by the process `peer` if
+/// known.
+fn parse(line: &[u8], peer: Option<u32>) -> Result<(SecondInstance, Value), String> {
+    let mut line = match serde_json::from_slice(line).map_err(|e| e.to_string())? {
+        Value::Object(line) => line,
+        _ => return Err("expected a JSON object".to_owned()),
+    };
+    let pid = match line.get("pid") {
+        None | Some(Value::Null) => 0,
+        Some(pid) => pid
+            .as_u64()
+            .and_then(|pid| u32::try_from(pid).ok())
+            .ok_or("expected a process ID in `pid`")?,
+    };
+    let pid = match peer {
+        Some(peer) if pid != 0 && pid != peer => return Err("`pid` is not the sender's process ID".to_owned()),
+        Some(peer) => peer,
+        None => pid,
+    };
+    let cwd = match line.get("cwd") {
+        None | Some(Value::Null) => PathBuf::new(),
+        Some(cwd) => PathBuf::from(cwd.as_str().ok_or("expected a path in `cwd`")?),
+    };
+    let data = line.remove("data").unwrap_or(Value::Null);
+    Ok((
+        SecondInstance {
+            pid,
+            cwd,
+            env: Vec::new(),
+            activation_token: None,
+        },
+        data,
+    ))
+}
+
+/// Passes the lines sent on `stream`, after the bytes `start` already read
+/// from it, to `messages` and writes the answers back.
+pub(crate) fn serve(start: Vec<u8>, stream: Stream, messages: Sender<JsonMessage>) {
+    // Intentionally discard any errors, the connection is then closed.
+    let _ = (|| -> io::Result<()> {
+        let peer = peer::pid(&stream)?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(io::Cursor::new(start).chain(stream));
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            let answer = match parse(&line, peer) {
+                Ok((sender, data)) => {
+                    let (reply, replied) = mpsc::channel();
+                    let message = JsonMessage { sender, data, reply };
+                    if messages.send(message).is_err() {
+                        return Ok(());
+                    }
+                    serde_json::json!({ "reply": replied.recv().unwrap_or(Value::Null) })
+                }
+                Err(e) => serde_json::json!({ "error": e }),
+            };
+            let mut answer = serde_json::to_vec(&answer)?;
+            answer.push(b'\n');
+            writer.write_all(&answer)?;
+        }
+    })();
+}
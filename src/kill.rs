@@ -0,0 +1,256 @@
+//! Stopping the instance holding a name, e.g. for updaters replacing it or
+//! kiosk launchers restarting it.
+//!
+//! `SingleInstance::kill_other` signals the process `holder_pid` names, but
+//! only once it checked that the process runs the same executable as this
+//! one, so that a PID reused by an unrelated process since is never hit:
+//!
+//! ```no_run
+//! use single_instance::{KillSignal, SingleInstance};
+//!
+//! let instance = SingleInstance::new("my-app").unwrap();
+//! if !instance.is_single() {
+//!     instance.kill_other(KillSignal::Terminate).unwrap();
+//! }
+//! ```
+//!
+//! `SingleInstance::acquire_or_steal` combines it with
+//! `SingleInstance::request_takeover` for kiosks and watchdogs, which have to
+//! get the name within a bounded time however the holder behaves:
+//!
+//! ```no_run
+//! use single_instance::{KillSignal, SingleInstance};
+//! use std::time::Duration;
+//!
+//! let mut instance = SingleInstance::new("my-app").unwrap();
+//! let timeout = Duration::from_secs(5);
+//! assert!(instance.acquire_or_steal(timeout, Some(KillSignal::Kill)).unwrap());
+//! ```
+
+use error::{Result, SingleInstanceError};
+use std::io;
+use std::process;
+use std::time::Duration;
+use SingleInstance;
+
+#[cfg(windows)]
+use std::env;
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use winapi::shared::minwindef::{BOOL, DWORD, LPARAM, TRUE};
+#[cfg(windows)]
+use winapi::shared::windef::HWND;
+#[cfg(windows)]
+use winapi::um::handleapi::CloseHandle;
+#[cfg(windows)]
+use winapi::um::processthreadsapi::{OpenProcess, TerminateProcess};
+#[cfg(windows)]
+use winapi::um::winbase::QueryFullProcessImageNameW;
+#[cfg(windows)]
+use winapi::um::winnt::{HANDLE, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_TERMINATE};
+#[cfg(windows)]
+use winapi::um::winuser::{EnumWindows, GetWindowThreadProcessId, IsWindowVisible, PostMessageW, WM_CLOSE};
+
+/// How `SingleInstance::kill_other` stops the holder of a name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillSignal {
+    /// Asks it to exit: `SIGTERM` on unix, and `WM_CLOSE` posted to its
+    /// visible top-level windows on windows, which fails for a process
+    /// without any.
+    Terminate,
+    /// Stops it at once, as a last resort for a holder ignoring `Terminate`:
+    /// `SIGKILL` on unix, and `TerminateProcess` on windows.
+    Kill,
+}
+
+impl SingleInstance {
+    /// Stops the instance holding the name, as `holder_pid` tells it, with
+    /// `signal`, returning once it was sent; `wait_for_exit` then waits for
+    /// the holder to exit. Fails if the holder is not known, with
+    /// `SingleInstanceError::HolderIsSelf` if it is this process and with
+    /// `SingleInstanceError::DifferentExecutable` if it runs another
+    /// executable than this process, as a PID may have been reused, and
+    /// where that cannot be told, on platforms other than linux, android,
+    /// cygwin, macos and windows.
+    pub fn kill_other(&self, signal: KillSignal) -> Result<()> {
+        let pid = self
+            .holder_pid()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the holder of the name is not known"))?;
+        if pid == process::id() {
+            return Err(SingleInstanceError::HolderIsSelf);
+        }
+        if !same_executable(pid)? {
+            return Err(SingleInstanceError::DifferentExecutable { pid });
+        }
+        Ok(kill(pid, signal)?)
+    }
+
+    /// Claims the name, taking it over from the instance holding it if need
+    /// be, and returns whether this instance holds it now: the holder is
+    /// asked to exit with `SingleInstance::request_takeover`, waiting up to
+    /// `timeout` for the name to be released, and if it does not comply or
+    /// does not listen, stopped with `force`, if given, through
+    /// `SingleInstance::kill_other`, waiting up to `timeout` again.
+    ///
+    /// Returns `false` if the name was not released in time, also without
+    /// `force`. Fails if this instance was not created with a `Backend`, or
+    /// with the errors of `kill_other` if the holder may not be stopped,
+    /// e.g. as it runs another executable, in which case it is left alone.
+    pub fn acquire_or_steal(&mut self, timeout: Duration, force: Option<KillSignal>) -> Result<bool> {
+        if self.is_single() || self.try_reacquire()? || self.request_takeover(Some(timeout))? {
+            return Ok(true);
+        }
+        let signal = match force {
+            Some(signal) => signal,
+            None => return Ok(false),
+        };
+        match self.kill_other(signal) {
+            Ok(()) => {}
+            // The holder exited meanwhile.
+            Err(SingleInstanceError::Io(ref err)) if err.kind() == io::ErrorKind::NotFound && self.try_reacquire()? => {
+                return Ok(true)
+            }
+            Err(err) => return Err(err),
+        }
+        self.wait_until_single(Some(timeout))
+    }
+}
+
+/// Returns whether the process `pid` runs the executable this one does, as
+/// `/proc` tells. An executable replaced since it was started, e.g. by an
+/// update, still counts as the same.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "cygwin"))]
+fn same_executable(pid: u32) -> io::Result<bool> {
+    use std::fs;
+    use std::path::PathBuf;
+
+    let exe = |pid: &str| -> io::Result<PathBuf> {
+        let exe = fs::read_link(format!("/proc/{}/exe", pid))?;
+        Ok(match exe.to_str().and_then(|exe| exe.strip_suffix(" (deleted)")) {
+            Some(exe) => PathBuf::from(exe),
+            None => exe,
+        })
+    };
+    Ok(exe(&pid.to_string())? == exe("self")?)
+}
+
+/// Returns whether the process `pid` runs the executable this one does, as
+/// `proc_pidpath` tells.
+#[cfg(target_os = "macos")]
+fn same_executable(pid: u32) -> io::Result<bool> {
+    use std::env;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut path = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len = unsafe { libc::proc_pidpath(pid as libc::c_int, path.as_mut_ptr() as *mut _, path.len() as u32) };
+    if len <= 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let theirs = fs::canonicalize(OsStr::from_bytes(&path[..len as usize]))?;
+    Ok(theirs == fs::canonicalize(env::current_exe()?)?)
+}
+
+/// Returns an error, as the executable of another process cannot be told.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "cygwin",
+        target_os = "macos"
+    ))
+))]
+fn same_executable(_pid: u32) -> io::Result<bool> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the executable of another process cannot be told here",
+    ))
+}
+
+/// Returns whether the process `pid` runs the executable this one does, as
+/// `QueryFullProcessImageNameW` tells, ignoring case like the file system.
+#[cfg(windows)]
+fn same_executable(pid: u32) -> io::Result<bool> {
+    let process = Process::open(pid, PROCESS_QUERY_LIMITED_INFORMATION)?;
+    let mut path = vec![0u16; 32768];
+    let mut len = path.len() as DWORD;
+    if unsafe { QueryFullProcessImageNameW(process.0, 0, path.as_mut_ptr(), &mut len) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let theirs = PathBuf::from(String::from_utf16_lossy(&path[..len as usize]));
+    let ours = env::current_exe()?;
+    Ok(theirs.to_string_lossy().to_lowercase() == ours.to_string_lossy().to_lowercase())
+}
+
+/// Sends `signal` to the process `pid`.
+#[cfg(unix)]
+fn kill(pid: u32, signal: KillSignal) -> io::Result<()> {
+    let signal = match signal {
+        KillSignal::Terminate => libc::SIGTERM,
+        KillSignal::Kill => libc::SIGKILL,
+    };
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Posts `WM_CLOSE` to the visible top-level windows of the process `pid`,
+/// or terminates it.
+#[cfg(windows)]
+fn kill(pid: u32, signal: KillSignal) -> io::Result<()> {
+    match signal {
+        KillSignal::Terminate => {
+            unsafe extern "system" fn close(window: HWND, param: LPARAM) -> BOOL {
+                let (pid, closed) = &mut *(param as *mut (u32, bool));
+                let mut owner = 0;
+                GetWindowThreadProcessId(window, &mut owner);
+                if owner == *pid && IsWindowVisible(window) != 0 && PostMessageW(window, WM_CLOSE, 0, 0) != 0 {
+                    *closed = true;
+                }
+                TRUE
+            }
+
+            let mut state = (pid, false);
+            unsafe { EnumWindows(Some(close), &mut state as *mut (u32, bool) as LPARAM) };
+            if !state.1 {
+                return Err(io::Error::new(io::ErrorKind::NotFound, "the holder of the name has no window to close"));
+            }
+            Ok(())
+        }
+        KillSignal::Kill => {
+            let process = Process::open(pid, PROCESS_TERMINATE)?;
+            if unsafe { TerminateProcess(process.0, 1) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// A process handle, closed when dropped.
+#[cfg(windows)]
+struct Process(HANDLE);
+
+#[cfg(windows)]
+impl Process {
+    fn open(pid: u32, access: DWORD) -> io::Result<Self> {
+        let process = unsafe { OpenProcess(access, 0, pid) };
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Process(process))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Process {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
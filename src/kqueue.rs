@@ -0,0 +1,51 @@
+//! Waiting for another process to exit on macos, ios and the BSDs, which
+//! kqueue reports with `NOTE_EXIT` without polling.
+
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr;
+use std::time::{Duration, Instant};
+
+/// Waits up to `timeout` for the process `pid` to exit, returning whether it
+/// did, also if it had exited before.
+pub fn wait_for_exit(pid: u32, timeout: Option<Duration>) -> io::Result<bool> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let queue = unsafe { libc::kqueue() };
+    if queue < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let queue = unsafe { OwnedFd::from_raw_fd(queue) };
+    let mut change: libc::kevent = unsafe { mem::zeroed() };
+    change.ident = pid as _;
+    change.filter = libc::EVFILT_PROC as _;
+    change.flags = (libc::EV_ADD | libc::EV_ONESHOT) as _;
+    change.fflags = libc::NOTE_EXIT as _;
+    if unsafe { libc::kevent(queue.as_raw_fd(), &change, 1, ptr::null_mut(), 0, ptr::null()) } < 0 {
+        let err = io::Error::last_os_error();
+        return match err.raw_os_error() {
+            Some(libc::ESRCH) => Ok(true),
+            _ => Err(err),
+        };
+    }
+    loop {
+        let remaining = deadline.map(|deadline| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            libc::timespec {
+                tv_sec: remaining.as_secs() as libc::time_t,
+                tv_nsec: remaining.subsec_nanos() as _,
+            }
+        });
+        let remaining = match remaining {
+            Some(ref remaining) => remaining as *const libc::timespec,
+            None => ptr::null(),
+        };
+        let mut event: libc::kevent = unsafe { mem::zeroed() };
+        match unsafe { libc::kevent(queue.as_raw_fd(), ptr::null(), 0, &mut event, 1, remaining) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            -1 => return Err(io::Error::last_os_error()),
+            0 => return Ok(false),
+            _ => return Ok(true),
+        }
+    }
+}
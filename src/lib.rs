@@ -4,8 +4,11 @@
 //!
 //! ## Detail
 //! On windows, init `SingleInstance` will create a mutex named by given `&str` then check error code by calling `GetLastError`.
-//! On linux init will bind abstract unix domain socket with given name . On macos, init will create or open a file which path is given `&str`,
-//! then call `flock` to apply an advisory lock on the open file.
+//! On linux init will bind abstract unix domain socket with given name . On macos and most other unix platforms, init will create or open a file which path is given `&str`,
+//! then call `flock` to apply an advisory lock on the open file. `Backend::Default` tells the details for every platform.
+//! `SingleInstance::with_backend` picks another mechanism, see `Backend`, and `SingleInstance::with_scope` claims the name per user, session or machine, see `Scope`.
+//! The instance holding a name can be told about the ones failing to claim it and exchange messages with them, see `SingleInstance::on_second_instance` and `SingleInstance::send`.
+//! Every backend but the platform's default and `Backend::FileLock` is behind a cargo feature; other optional ones, such as `async`, `serde` or `notify`, are off by default.
 //!
 //! ### Examples
 //! ```rust
@@ -20,34 +23,438 @@
 //! }
 //! ```
 
+#[cfg(abstract_socket)]
+mod abstract_socket;
+#[cfg(target_os = "android")]
+pub mod android;
+#[cfg(all(feature = "async", any(unix, windows)))]
+mod background;
+pub mod backend;
+mod builder;
+#[cfg(any(unix, windows))]
+mod cancel;
+#[cfg(target_os = "cygwin")]
+mod cygwin;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+mod dbus;
+#[cfg(all(feature = "notify", any(target_os = "linux", target_os = "macos", windows)))]
+mod desktop_notification;
 pub mod error;
+mod guard;
+#[cfg(any(unix, windows))]
+mod fnv;
+#[cfg(all(unix, not(target_os = "cygwin")))]
+mod holder;
+#[cfg(all(feature = "sysinfo", any(unix, windows)))]
+mod holder_info;
+#[cfg(all(feature = "serde", any(unix, windows)))]
+pub mod json_lines;
+#[cfg(any(unix, windows))]
+pub mod names;
+#[cfg(target_os = "ios")]
+pub mod ios;
+#[cfg(any(unix, windows))]
+mod kill;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod kqueue;
+#[cfg(any(target_os = "android", target_os = "ios"))]
+mod lock_dir;
+#[cfg(any(all(unix, not(target_os = "cygwin")), target_os = "windows"))]
+pub mod lock_info;
+#[cfg(all(feature = "appkit", target_os = "macos"))]
+pub mod macos;
+#[cfg(any(unix, windows))]
+mod notify;
+#[cfg(any(all(unix, not(target_os = "cygwin")), windows))]
+mod passing;
+#[cfg(any(unix, windows))]
+mod peer;
+#[cfg(all(feature = "pid-file", unix, not(target_os = "cygwin")))]
+mod pid_file;
+#[cfg(all(feature = "redis", any(unix, windows)))]
+pub mod redis;
+#[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+mod robust_mutex;
+#[cfg(any(unix, windows))]
+mod scope;
+#[cfg(posix_semaphore)]
+mod semaphore;
+#[cfg(sysv_semaphore)]
+mod sysv_semaphore;
+#[cfg(all(feature = "systemd", target_os = "linux"))]
+pub mod systemd;
+#[cfg(all(feature = "tcp-port", any(unix, windows)))]
+pub mod tcp;
+#[cfg(all(feature = "serde", any(unix, windows)))]
+pub mod typed;
+#[cfg(all(feature = "unix-socket", unix, not(target_os = "cygwin")))]
+pub mod unix_socket;
+#[cfg(target_os = "wasi")]
+mod wasi_file;
+#[cfg(target_os = "windows")]
+pub mod windows;
+#[cfg(target_os = "windows")]
+mod windows_file_lock;
+#[cfg(target_os = "windows")]
+mod windows_holder;
+#[cfg(all(feature = "named-pipe", target_os = "windows"))]
+mod windows_pipe;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+mod web_locks;
+#[cfg(all(feature = "x11", target_os = "linux"))]
+mod x11;
+#[cfg(all(unix, not(target_os = "cygwin")))]
+mod file_lock;
 
-#[cfg(target_os = "macos")]
+#[cfg(all(unix, not(target_os = "cygwin")))]
 extern crate libc;
-#[cfg(any(target_os = "linux", target_os ="android"))]
+#[cfg(abstract_socket)]
 extern crate nix;
 extern crate thiserror;
-#[cfg(target_os = "windows")]
+#[cfg(any(target_os = "windows", target_os = "cygwin"))]
 extern crate widestring;
 #[cfg(target_os = "windows")]
 extern crate winapi;
+#[cfg(all(feature = "notify", any(target_os = "linux", target_os = "macos", windows)))]
+extern crate notify_rust;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "sysinfo")]
+extern crate sysinfo;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+extern crate js_sys;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+extern crate wasm_bindgen;
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+extern crate web_sys;
+#[cfg(all(feature = "x11", target_os = "linux"))]
+extern crate x11rb;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+extern crate zbus;
 
+pub use self::backend::{Backend, CustomBackend};
+pub use self::builder::SingleInstanceBuilder;
+pub use self::guard::InstanceGuard;
+#[cfg(all(feature = "sysinfo", any(unix, windows)))]
+pub use self::holder_info::HolderInfo;
 pub use self::inner::*;
+#[cfg(any(unix, windows))]
+pub use self::kill::KillSignal;
+#[cfg(any(unix, windows))]
+pub use self::scope::Scope;
+#[cfg(any(unix, windows))]
+pub use self::notify::{ForwardResult, Message, SecondInstance};
 
-#[cfg(target_os = "windows")]
+#[cfg(all(feature = "async", any(unix, windows)))]
+impl SingleInstance {
+    /// Claims `name` like `SingleInstance::acquire` on a thread of its own,
+    /// so that async applications do not block a worker of their runtime,
+    /// whichever it is.
+    pub fn acquire_async(name: &str) -> impl std::future::Future<Output = error::Result<AcquireOutcome>> {
+        let name = name.to_owned();
+        background::spawn(move |_| Self::acquire(&name))
+    }
+
+    /// Waits like `SingleInstance::wait_until_single` on a thread of its own,
+    /// so that async applications do not block a worker of their runtime.
+    /// This instance is moved to that thread and handed back along with the
+    /// result.
+    ///
+    /// Dropping the future cancels the wait, e.g. on shutdown or when it
+    /// loses a `select!`: the thread wakes from its wait and drops this
+    /// instance, which releases the name if it was claimed meanwhile, and so
+    /// does a wait for a lock file left behind once it takes the lock.
+    pub fn wait_until_single_async(
+        mut self,
+        timeout: Option<std::time::Duration>,
+    ) -> impl std::future::Future<Output = (Self, error::Result<bool>)> {
+        background::spawn(move |cancel| {
+            let result = self.wait_until_single_unless(timeout, cancel);
+            (self, result)
+        })
+    }
+}
+
+/// Runs `f` if `name` can be claimed, holding it while `f` runs and
+/// releasing it afterwards, also if `f` panics, in which case the panic is
+/// resumed once the name is released. Returns what `f` returned, or `None`
+/// without running it if another instance holds the name.
+pub fn run_if_single<T, F>(name: &str, f: F) -> error::Result<Option<T>>
+where
+    F: FnOnce() -> T,
+{
+    let guard = match SingleInstance::acquire(name)? {
+        AcquireOutcome::Acquired(guard) => guard,
+        AcquireOutcome::AlreadyRunning { .. } => return Ok(None),
+    };
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    drop(guard);
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+}
+
+/// The outcome of `SingleInstance::acquire`.
+pub enum AcquireOutcome {
+    /// This instance holds the name until the guard is dropped.
+    Acquired(InstanceGuard),
+    /// Another instance holds the name.
+    AlreadyRunning {
+        /// The PID of the instance holding the name, if known, see
+        /// `SingleInstance::holder_pid`.
+        pid: Option<u32>,
+        /// This instance, which is not single but can still talk to the one
+        /// holding the name, e.g. with `SingleInstance::activate`.
+        instance: SingleInstance,
+    },
+}
+
+#[cfg(any(unix, windows))]
+impl SingleInstance {
+    /// Sends the environment variables `names`, those of them that are set,
+    /// along with every message instances in this process send to the one
+    /// holding a name from now on, including the notification of failing to
+    /// claim it. The holder finds them in `SecondInstance::env`, next to the
+    /// working directory in `SecondInstance::cwd`, e.g. to open windows on
+    /// the display given by `DISPLAY` or `WAYLAND_DISPLAY`.
+    pub fn forward_env<I, S>(names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        notify::forward_env(names.into_iter().map(|name| name.as_ref().to_owned()).collect());
+    }
+
+    /// Has instances in this process failing to claim a name from now on show
+    /// a desktop notification with `text`, such as "MyApp is already
+    /// running", or none if `None`.
+    #[cfg(all(feature = "notify", any(target_os = "linux", target_os = "macos", windows)))]
+    pub fn notify_when_running(text: Option<&str>) {
+        desktop_notification::set(text.map(str::to_owned));
+    }
+
+    /// Returns a new SingleInstance object for a name derived from the path
+    /// of the running executable, so that simple tools need not make one up:
+    /// its file stem followed by a hash of its canonical path, e.g.
+    /// `mytool-1a2b3c4d`, as a lock file in the temporary directory where
+    /// names are paths. Copies of the binary at other paths claim other
+    /// names; `SingleInstance::new(env!("CARGO_PKG_NAME"))` claims one that
+    /// survives moving it instead.
+    pub fn for_current_exe() -> error::Result<Self> {
+        Self::new(&current_exe_name()?)
+    }
+
+    /// Returns a new SingleInstance object for the reverse DNS application
+    /// identifier `app_id`, such as `com.example.MyApp`, claimed the way each
+    /// platform suits best: the session's mutex `app_id` on windows, the lock
+    /// file `$XDG_RUNTIME_DIR/com.example.MyApp.lock` on linux, falling back
+    /// to the temporary directory, and the lock file of that name in the
+    /// temporary directory on macos, which lies in the app's container when
+    /// sandboxed, and on ios. Android claims `app_id` by default. Fails if
+    /// `app_id` is empty or contains a path separator.
+    pub fn for_app_id(app_id: &str) -> error::Result<Self> {
+        if app_id.is_empty() || app_id.contains(['/', '\\']) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid application identifier").into());
+        }
+        #[cfg(any(windows, target_os = "cygwin", target_os = "android"))]
+        let (name, backend) = (app_id.to_owned(), Backend::Default);
+        #[cfg(target_os = "ios")]
+        let (name, backend) = (format!("{}.lock", app_id), Backend::FileLock);
+        #[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+        let (name, backend) = {
+            #[cfg(target_os = "macos")]
+            let dir = std::env::temp_dir();
+            #[cfg(not(target_os = "macos"))]
+            let dir = std::env::var_os("XDG_RUNTIME_DIR")
+                .map(std::path::PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .unwrap_or_else(std::env::temp_dir);
+            let path = dir.join(format!("{}.lock", app_id));
+            (path.to_string_lossy().into_owned(), Backend::FileLock)
+        };
+        Self::with_backend(&name, backend)
+    }
+}
+
+/// Returns the name `SingleInstance::for_current_exe` claims.
+#[cfg(any(unix, windows))]
+fn current_exe_name() -> std::io::Result<String> {
+    let exe = std::env::current_exe()?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let stem = exe.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = format!("{}-{:08x}", stem, fnv::hash(&exe.to_string_lossy()));
+    #[cfg(all(unix, not(any(target_os = "cygwin", target_os = "ios", abstract_socket))))]
+    let name = std::env::temp_dir().join(format!("{}.lock", name)).to_string_lossy().into_owned();
+    Ok(name)
+}
+
+impl SingleInstance {
+    /// Claims `name`, telling whether this instance got it or which one
+    /// holds it, so that the outcome cannot go unchecked like `is_single` can.
+    pub fn acquire(name: &str) -> error::Result<AcquireOutcome> {
+        let instance = match Self::new(name)?.into_guard() {
+            Ok(guard) => return Ok(AcquireOutcome::Acquired(guard)),
+            Err(instance) => instance,
+        };
+        #[cfg(any(unix, windows))]
+        let pid = instance.holder_pid();
+        #[cfg(not(any(unix, windows)))]
+        let pid = None;
+        Ok(AcquireOutcome::AlreadyRunning { pid, instance })
+    }
+
+    /// Returns a guard for a new SingleInstance object holding `name`, or
+    /// fails with `SingleInstanceError::AlreadyRunning` if another instance
+    /// holds it, see `SingleInstance::acquire`.
+    pub fn try_acquire(name: &str) -> error::Result<InstanceGuard> {
+        match Self::acquire(name)? {
+            AcquireOutcome::Acquired(guard) => Ok(guard),
+            AcquireOutcome::AlreadyRunning { pid, .. } => Err(error::SingleInstanceError::AlreadyRunning { pid }),
+        }
+    }
+
+    /// Returns the options for claiming `name`, to combine before claiming
+    /// it with `SingleInstanceBuilder::build`.
+    pub fn builder(name: &str) -> SingleInstanceBuilder {
+        SingleInstanceBuilder::new(name)
+    }
+
+    /// Returns a new SingleInstance object claiming `name` with the first of
+    /// `backends` that works, e.g. `[Backend::AbstractSocket,
+    /// Backend::FileLock, Backend::TcpPort]`, or with `Backend::Default` if
+    /// there are none.
+    ///
+    /// A backend failing, e.g. because a sandbox forbids it or the file system
+    /// is read only, falls through to the next one, while one finding `name`
+    /// held by another instance does not. If all of them fail the last error
+    /// is returned. The same `name` is given to each, so it has to suit all
+    /// of them.
+    pub fn with_backends(name: &str, backends: &[Backend]) -> error::Result<Self> {
+        let mut last_error = None;
+        for &backend in backends {
+            match Self::with_backend(name, backend) {
+                Ok(instance) => return Ok(instance),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(e),
+            None => Self::with_backend(name, Backend::Default),
+        }
+    }
+}
+
+#[cfg(any(target_os = "windows", target_os = "cygwin"))]
 mod inner {
+    use backend::{Backend, Custom, CustomBackend};
+    use cancel::Cancel;
+    #[cfg(all(feature = "event", target_os = "cygwin"))]
+    use cygwin::CreateEventW;
+    #[cfg(target_os = "cygwin")]
+    use cygwin::{
+        CloseHandle, CreateMutexW, CreateSemaphoreW, GetLastError, ReleaseSemaphore, WaitForSingleObject,
+        ERROR_ACCESS_DENIED, ERROR_ALREADY_EXISTS, ERROR_INVALID_HANDLE, HANDLE, WAIT_OBJECT_0, WAIT_TIMEOUT,
+    };
     use error::{Result, SingleInstanceError};
+    use names;
+    use std::borrow::Cow;
+    #[cfg(feature = "serde")]
+    use json_lines::JsonMessage;
+    use notify::{self, ForwardResult, Message, Notifier, Passed, SecondInstance, Settings};
+    #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+    use std::fs::File;
+    use std::io;
+    #[cfg(feature = "tcp-port")]
+    use std::net::TcpListener;
+    #[cfg(target_os = "windows")]
+    use std::os::windows::io::{AsRawHandle, BorrowedHandle, RawHandle};
+    #[cfg(target_os = "windows")]
+    use std::path::{Path, PathBuf};
     use std::ptr;
-    use widestring::WideCString;
-    use winapi::shared::winerror::{ERROR_ALREADY_EXISTS, ERROR_INVALID_HANDLE};
+    #[cfg(target_os = "windows")]
+    use std::sync::mpsc::{self, Sender};
+    use std::sync::mpsc::Receiver;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    #[cfg(feature = "tcp-port")]
+    use tcp;
+    use widestring::U16CString;
+    #[cfg(target_os = "windows")]
+    use winapi::shared::minwindef::{DWORD, FALSE, TRUE};
+    #[cfg(target_os = "windows")]
+    use winapi::shared::windef::HWND;
+    #[cfg(all(feature = "event", target_os = "windows"))]
+    use winapi::shared::winerror::ERROR_INVALID_HANDLE;
+    #[cfg(target_os = "windows")]
+    use winapi::shared::winerror::{ERROR_ACCESS_DENIED, ERROR_ALREADY_EXISTS, WAIT_TIMEOUT};
+    #[cfg(target_os = "windows")]
     use winapi::um::errhandlingapi::GetLastError;
+    #[cfg(target_os = "windows")]
     use winapi::um::handleapi::CloseHandle;
-    use winapi::um::synchapi::CreateMutexW;
+    #[cfg(all(feature = "event", target_os = "windows"))]
+    use winapi::um::synchapi::CreateEventW;
+    #[cfg(target_os = "windows")]
+    use winapi::um::synchapi::{
+        CreateMutexW, CreateSemaphoreW, ReleaseMutex, ReleaseSemaphore, WaitForMultipleObjects, WaitForSingleObject,
+    };
+    #[cfg(target_os = "windows")]
+    use winapi::um::winbase::{INFINITE, WAIT_ABANDONED_0, WAIT_OBJECT_0};
+    #[cfg(target_os = "windows")]
     use winapi::um::winnt::HANDLE;
+    #[cfg(target_os = "windows")]
+    use winapi::um::winuser::{AllowSetForegroundWindow, ASFW_ANY};
+    #[cfg(target_os = "windows")]
+    use windows_file_lock::{self, FileLock};
+    #[cfg(target_os = "windows")]
+    use windows_holder::{self, Published};
+    #[cfg(target_os = "windows")]
+    use lock_info::LockInfo;
+    #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+    use windows_pipe;
+
+    /// A claimed instance name.
+    #[allow(dead_code)] // Only the handles are closed, the rest is dropped.
+    enum Lock {
+        Handle(HANDLE),
+        /// A created mutex, with this process published as its holder.
+        #[cfg(target_os = "windows")]
+        Mutex(OwnedMutex, Option<Published>),
+        /// A taken named semaphore, see `SingleInstance::with_limit`.
+        Semaphore(HANDLE),
+        #[cfg(target_os = "windows")]
+        File(FileLock),
+        #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+        Pipe(File),
+        #[cfg(feature = "tcp-port")]
+        TcpPort(TcpListener),
+        Custom(Custom),
+    }
 
     /// A struct representing one running instance.
     pub struct SingleInstance {
-        handle: Option<HANDLE>,
+        /// Listening for other instances, stopped before the name is released.
+        notifier: Option<Notifier>,
+        /// The handlers and options of the notifier stopped by `release`,
+        /// restored by `try_reacquire`.
+        released: Option<Settings>,
+        lock: Option<Lock>,
+        backend: Option<Backend>,
+        name: String,
     }
 
     unsafe impl Send for SingleInstance {}
@@ -56,145 +463,3832 @@ mod inner {
     impl SingleInstance {
         /// Returns a new SingleInstance object.
         pub fn new(name: &str) -> Result<Self> {
-            let name = WideCString::from_str(name)?;
-            unsafe {
-                let handle = CreateMutexW(ptr::null_mut(), 0, name.as_ptr());
-                let last_error = GetLastError();
+            Self::with_backend(name, Backend::Default)
+        }
 
-                // https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-createmutexexw
-                if handle.is_null() || handle == ERROR_INVALID_HANDLE as _ {
-                    Err(SingleInstanceError::MutexError(last_error))
-                } else if last_error == ERROR_ALREADY_EXISTS {
-                    CloseHandle(handle);
-                    Ok(SingleInstance { handle: None })
-                } else {
-                    Ok(SingleInstance {
-                        handle: Some(handle),
-                    })
-                }
+        /// Returns a new SingleInstance object claiming `name` with `backend`.
+        pub fn with_backend(name: &str, backend: Backend) -> Result<Self> {
+            let name = &*Self::sanitized(name, backend)?;
+            let lock = Self::claim(name, backend)?;
+            if lock.is_none() {
+                notify::send(name);
             }
+            Ok(Self {
+                notifier: None,
+                released: None,
+                lock,
+                backend: Some(backend),
+                name: name.to_owned(),
+            })
+        }
+
+        /// Returns `name` suiting `backend`, see `names`.
+        fn sanitized(name: &str, backend: Backend) -> Result<Cow<'_, str>> {
+            match backend {
+                Backend::Default => names::object(name),
+                #[cfg(feature = "event")]
+                Backend::Event => names::object(name),
+                // Without optional backends, no other ones are left.
+                #[allow(unreachable_patterns)]
+                _ => Ok(Cow::Borrowed(name)),
+            }
+        }
+
+        /// Claims `name` with `backend`, returning `None` if another instance
+        /// holds it.
+        fn claim(name: &str, backend: Backend) -> Result<Option<Lock>> {
+            Ok(match backend {
+                #[cfg(target_os = "windows")]
+                Backend::Default => own_mutex(name, Some(Duration::ZERO), ptr::null_mut())?
+                    .map(|mutex| Lock::Mutex(mutex, windows_holder::publish(name))),
+                #[cfg(target_os = "cygwin")]
+                Backend::Default => create_mutex(name)?.map(Lock::Handle),
+                #[cfg(feature = "event")]
+                Backend::Event => create_event(name)?.map(Lock::Handle),
+                #[cfg(target_os = "windows")]
+                Backend::FileLock => windows_file_lock::try_lock(Path::new(name))?.map(Lock::File),
+                #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+                Backend::NamedPipe => windows_pipe::try_create(name)?.map(Lock::Pipe),
+                #[cfg(feature = "tcp-port")]
+                Backend::TcpPort => tcp::try_bind(name)?.map(Lock::TcpPort),
+            })
+        }
+
+        /// Returns a new SingleInstance object claiming `name` with a backend
+        /// supplied by the application.
+        pub fn with_custom_backend(name: &str, backend: Box<dyn CustomBackend>) -> Result<Self> {
+            let lock = Custom::acquire(backend, name)?.map(Lock::Custom);
+            if lock.is_none() {
+                notify::send(name);
+            }
+            Ok(Self {
+                notifier: None,
+                released: None,
+                lock,
+                backend: None,
+                name: name.to_owned(),
+            })
+        }
+
+        /// Returns a new SingleInstance object taking one of `limit` slots
+        /// for `name`, so that at most `limit` instances are single at once.
+        ///
+        /// The slots are counted by the named semaphore `name`, created by the
+        /// first instance with its `limit`. The slot of an instance that
+        /// crashed is only given back once no instance uses the semaphore.
+        pub fn with_limit(name: &str, limit: usize) -> Result<Self> {
+            let name = &*names::object(name)?;
+            Ok(Self {
+                notifier: None,
+                released: None,
+                lock: take_semaphore(name, limit)?.map(Lock::Semaphore),
+                backend: None,
+                name: name.to_owned(),
+            })
         }
 
         /// Returns whether this instance is single.
         pub fn is_single(&self) -> bool {
-            self.handle.is_some()
+            match self.lock {
+                Some(Lock::Custom(ref custom)) => custom.is_held(),
+                ref lock => lock.is_some(),
+            }
+        }
+
+        /// Releases the name as dropping this instance would, so that
+        /// `is_single` returns `false` from now on and another instance can
+        /// claim it, e.g. when this one is kept in a static. Handlers stop
+        /// being called. Does nothing if the name is not held.
+        pub fn release(&mut self) {
+            if let Some(notifier) = self.notifier.take() {
+                self.released = Some(notifier.settings());
+            }
+            match self.lock.take() {
+                Some(Lock::Handle(handle)) => unsafe {
+                    CloseHandle(handle);
+                },
+                Some(Lock::Semaphore(handle)) => unsafe {
+                    ReleaseSemaphore(handle, 1, ptr::null_mut());
+                    CloseHandle(handle);
+                },
+                _ => {}
+            }
+        }
+
+        /// Tries once to claim the name with the backend this instance was
+        /// created with, if it does not hold it because another instance did
+        /// when it was created or it called `SingleInstance::release`, and
+        /// returns whether it holds it now. The handlers and options set
+        /// before releasing the name are kept and listened with again. Fails
+        /// if this instance was not created with a `Backend`.
+        pub fn try_reacquire(&mut self) -> Result<bool> {
+            self.wait_until_single(Some(Duration::ZERO))
+        }
+
+        /// Waits up to `timeout`, or as long as it takes without one, for the
+        /// instance holding the name to release it, and claims it like
+        /// `SingleInstance::try_reacquire`, e.g. for a command line tool to
+        /// queue up behind a running job. Returns whether this instance holds
+        /// the name now. Fails if it was not created with a `Backend`.
+        pub fn wait_until_single(&mut self, timeout: Option<Duration>) -> Result<bool> {
+            self.wait_until_single_unless(timeout, &Arc::new(Cancel::new()))
+        }
+
+        /// Waits like `SingleInstance::wait_until_single` unless `cancel` is
+        /// set meanwhile, which gives up without claiming the name.
+        pub(crate) fn wait_until_single_unless(&mut self, timeout: Option<Duration>, cancel: &Arc<Cancel>) -> Result<bool> {
+            if self.is_single() {
+                return Ok(true);
+            }
+            let backend = match self.backend {
+                Some(backend) => backend,
+                None => return Err(io::Error::other("only names claimed with a backend can be claimed again").into()),
+            };
+            if !self.claim_within(backend, timeout, cancel)? {
+                return Ok(false);
+            }
+            if let Some(settings) = self.released.take() {
+                self.notifier = Some(notify::listen_with(&self.name, settings)?);
+            }
+            Ok(true)
+        }
+
+        /// Returns the backend this instance was created with, which for
+        /// `SingleInstance::with_backends` is the first one that worked, or
+        /// `None` for a custom backend or a limit.
+        pub fn backend(&self) -> Option<Backend> {
+            self.backend
+        }
+
+        /// Returns the name this instance was created with.
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Returns the path of the file locked with `Backend::FileLock`, or
+        /// `None` for the other backends, which claim named objects instead,
+        /// see `object_name`.
+        #[cfg(target_os = "windows")]
+        pub fn lock_path(&self) -> Option<PathBuf> {
+            match self.backend? {
+                Backend::FileLock => Some(PathBuf::from(&self.name)),
+                _ => None,
+            }
+        }
+
+        /// Keeps the holder recorded in the file locked with
+        /// `Backend::FileLock` once the lock is released, if `persist` is
+        /// set, for deployments expecting a PID file to be left behind.
+        #[cfg(target_os = "windows")]
+        pub fn persist_lock_file(&mut self, persist: bool) {
+            if let Some(Lock::File(ref mut lock)) = self.lock {
+                lock.set_persist(persist);
+            }
+        }
+
+        /// Returns the record of the instance holding `name` claimed with
+        /// `backend`, without trying to claim it, e.g. for tools telling who
+        /// holds it and since when. The holder of a `Backend::Default` mutex
+        /// publishes it next to the mutex and the holder of a
+        /// `Backend::FileLock` lock writes it into the file. Returns `None`
+        /// if no running instance recorded itself, or for other backends.
+        #[cfg(target_os = "windows")]
+        pub fn read_lock_info(name: &str, backend: Backend) -> Option<LockInfo> {
+            let name = &*Self::sanitized(name, backend).ok()?;
+            match backend {
+                Backend::Default => windows_holder::published_info(name),
+                Backend::FileLock => windows_holder::read(Path::new(name)),
+                // Without optional backends, no other ones are left.
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+
+        /// Returns the full name of the mutex, event or pipe the name is
+        /// claimed through with the backend this instance was created with,
+        /// including the namespace, which is the session's, `Local\`, unless
+        /// the name gives another one. In packaged app mode within an
+        /// AppContainer, it is the name within the package's namespace. Returns
+        /// `None` for other backends, custom backends and limits.
+        pub fn object_name(&self) -> Option<String> {
+            let name = match self.backend? {
+                Backend::Default => &self.name,
+                #[cfg(feature = "event")]
+                Backend::Event => &self.name,
+                #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+                Backend::NamedPipe => return Some(::windows::pipe_name(&self.name)),
+                // Without optional backends, no other ones are left.
+                #[allow(unreachable_patterns)]
+                _ => return None,
+            };
+            #[cfg(target_os = "windows")]
+            {
+                let object = ::windows::object_name(name);
+                if object.len() != name.len() {
+                    return Some(object.to_owned());
+                }
+            }
+            if ["Global\\", "Local\\", "Session\\"].iter().any(|prefix| name.starts_with(prefix)) {
+                Some(name.to_owned())
+            } else {
+                Some(format!("Local\\{}", name))
+            }
+        }
+
+        /// Calls `handler` for every instance failing to claim the name from
+        /// now on, with its PID and working directory, until this instance is
+        /// dropped. The handler runs on a thread of its own, e.g. to bring the
+        /// application's window to the front. Fails if this instance is not
+        /// single.
+        pub fn on_second_instance<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) + Send + 'static,
+        {
+            self.notifier()?.on_second_instance(handler);
+            Ok(())
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::activate`, with its PID and working directory,
+        /// until this instance is dropped. The handler runs on a thread of its
+        /// own and is left to bring the application's window to the front.
+        /// Fails if this instance is not single.
+        pub fn on_activate<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) + Send + 'static,
+        {
+            self.notifier()?.on_activate(handler);
+            Ok(())
+        }
+
+        /// Asks the instance holding the name to activate itself, returning
+        /// `false` if it does not listen, see `SingleInstance::on_activate`.
+        /// The activation token this instance was started with is sent along,
+        /// see `SecondInstance::activation_token`. Fails if this instance is
+        /// single.
+        pub fn activate(&self) -> Result<bool> {
+            self.activate_with_token(&notify::activation_token())
+        }
+
+        /// Asks the instance holding the name to activate itself like
+        /// `SingleInstance::activate`, sending `token` as the activation
+        /// token instead of the one this instance was started with, e.g. one
+        /// requested from the compositor, or none if it is empty.
+        pub fn activate_with_token(&self, token: &str) -> Result<bool> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            #[cfg(target_os = "windows")]
+            unsafe {
+                // Let the holder take the foreground from this instance.
+                AllowSetForegroundWindow(ASFW_ANY);
+            }
+            Ok(notify::activate(&self.name, token)?)
+        }
+
+        /// Sends `data` to the instance holding the name and returns its
+        /// reply, or `None` if it does not listen, see
+        /// `SingleInstance::messages`. Blocks until the holder replies to or
+        /// drops the message. Fails if this instance is single.
+        pub fn send(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.send_passing(data, &[])
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::send_and_wait_reply`, with its PID and working
+        /// directory and the data it sent, and replies with what the handler
+        /// returns, until this instance is dropped. The handler runs on a
+        /// thread of its own. Fails if this instance is not single.
+        pub fn on_request<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, Vec<u8>) -> Vec<u8> + Send + 'static,
+        {
+            self.notifier()?.on_request(handler);
+            Ok(())
+        }
+
+        /// Sends `data` to the instance holding the name and returns what its
+        /// `SingleInstance::on_request` handler replied, so that the caller
+        /// knows the request was handled. Returns `None` if the holder does
+        /// not listen or has no request handler, and fails with
+        /// `io::ErrorKind::TimedOut` if no reply arrived within `timeout`.
+        /// Fails if this instance is single.
+        pub fn send_and_wait_reply(&self, data: &[u8], timeout: Duration) -> Result<Option<Vec<u8>>> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::request_reply(&self.name, data, timeout)?)
+        }
+
+        /// Sends `data` to every instance that subscribed with
+        /// `SingleInstance::subscribe` and is still connected, returning how
+        /// many it was sent to. Fails if this instance is not single.
+        pub fn broadcast(&mut self, data: &[u8]) -> Result<usize> {
+            Ok(self.notifier()?.broadcast(data))
+        }
+
+        /// Stays connected to the instance holding the name and returns a
+        /// receiver of what it sends with `SingleInstance::broadcast` from now
+        /// on, or `None` if it does not listen. The receiver is disconnected
+        /// once the holder drops its `SingleInstance`. Fails if this instance
+        /// is single.
+        pub fn subscribe(&self) -> Result<Option<Receiver<Vec<u8>>>> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::subscribe(&self.name)?)
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::forward_urls`, with its PID and working directory
+        /// and the URLs it forwarded, until this instance is dropped. The
+        /// handler runs on a thread of its own. Fails if this instance is not
+        /// single.
+        pub fn on_urls<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, Vec<String>) + Send + 'static,
+        {
+            self.notifier()?.on_urls(handler);
+            Ok(())
+        }
+
+        /// Forwards `urls`, such as the deep links an application registered
+        /// as a protocol handler is started with, to the instance holding the
+        /// name, and blocks until its `SingleInstance::on_urls` handler
+        /// returned. Returns `ForwardResult::Single` without forwarding
+        /// anything if this instance holds the name itself.
+        pub fn forward_urls<I, S>(&self, urls: I) -> Result<ForwardResult>
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            if self.is_single() {
+                return Ok(ForwardResult::Single);
+            }
+            Ok(notify::urls(&self.name, urls)?)
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::forward_open`, with its PID and working directory
+        /// and the paths it forwarded, until this instance is dropped. Paths
+        /// are made absolute against the working directory of the other
+        /// instance and canonicalized if they exist. The handler runs on a
+        /// thread of its own. Fails if this instance is not single.
+        pub fn on_open<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, Vec<PathBuf>) + Send + 'static,
+        {
+            self.notifier()?.on_open(handler);
+            Ok(())
+        }
+
+        /// Forwards `paths`, such as the documents a user opened with the
+        /// application while it was running, to the instance holding the
+        /// name, and blocks until its `SingleInstance::on_open` handler
+        /// returned. Returns `ForwardResult::Single` without forwarding
+        /// anything if this instance holds the name itself.
+        pub fn forward_open<I, P>(&self, paths: I) -> Result<ForwardResult>
+        where
+            I: IntoIterator<Item = P>,
+            P: AsRef<Path>,
+        {
+            if self.is_single() {
+                return Ok(ForwardResult::Single);
+            }
+            Ok(notify::open(&self.name, paths)?)
+        }
+
+        /// Sends `data` along with the open files or sockets `handles` to the
+        /// instance holding the name, like `SingleInstance::send`. The holder
+        /// duplicates them out of this process into `Message::handles` before
+        /// this returns, so that it can use them without opening any paths
+        /// itself.
+        #[cfg(target_os = "windows")]
+        pub fn send_handles(&self, data: &[u8], handles: &[BorrowedHandle<'_>]) -> Result<Option<Vec<u8>>> {
+            self.send_passing(data, handles)
+        }
+
+        fn send_passing(&self, data: &[u8], passed: &[Passed<'_>]) -> Result<Option<Vec<u8>>> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::message(&self.name, data, passed)?)
+        }
+
+        /// Returns a receiver of the messages other instances send with
+        /// `SingleInstance::send` from now on, until this instance is dropped.
+        /// It is a plain `std::sync::mpsc::Receiver` fed by the thread
+        /// listening for other instances, so existing event loops can check
+        /// it with `try_recv` or `recv_timeout` between their own events.
+        /// Only the receiver returned last gets messages. Fails if this
+        /// instance is not single.
+        pub fn messages(&mut self) -> Result<Receiver<Message>> {
+            Ok(self.notifier()?.messages())
+        }
+
+        /// Returns a message another instance sent with `SingleInstance::send`
+        /// if one is waiting, without blocking, e.g. to be called once per
+        /// frame from the loop of a game or GUI. Messages are queued from the
+        /// first call on, replacing the receiver returned by
+        /// `SingleInstance::messages` like calling it again would. Fails if
+        /// this instance is not single.
+        pub fn poll_message(&mut self) -> Result<Option<Message>> {
+            Ok(self.notifier()?.poll_message())
+        }
+
+        /// Returns a receiver of the messages other instances send with
+        /// `SingleInstance::send` from now on for async applications, fed by a
+        /// thread of its own until this instance is dropped. Replaces the
+        /// receiver returned by `SingleInstance::messages` like calling it
+        /// again would. Fails if this instance is not single.
+        #[cfg(feature = "tokio")]
+        pub fn async_messages(&mut self) -> Result<tokio::sync::mpsc::Receiver<Message>> {
+            Ok(self.notifier()?.async_messages())
+        }
+
+        /// Has the window `window` of this instance restored and brought to
+        /// the foreground with `windows::activate_window` whenever another
+        /// instance calls `SingleInstance::activate`, before the handler set
+        /// with `SingleInstance::on_activate` is called. Fails if this
+        /// instance is not single.
+        #[cfg(target_os = "windows")]
+        pub fn set_window(&mut self, window: HWND) -> Result<()> {
+            self.notifier()?.set_window(window);
+            Ok(())
+        }
+
+        /// Returns how many times another instance failed to claim the name
+        /// since this one started listening for them, with the first call to
+        /// this or any other method doing so, such as
+        /// `SingleInstance::on_second_instance`, which is called on every
+        /// attempt. This allows telling users how often they tried to start an
+        /// application that is already running. Fails if this instance is not
+        /// single.
+        pub fn attempt_count(&mut self) -> Result<u64> {
+            Ok(self.notifier()?.attempt_count())
+        }
+
+        /// Sets the size in bytes of the largest message other instances may
+        /// send this one from now on, 16 MiB by default, counting a header
+        /// holding the working directory and forwarded environment variables.
+        /// Larger ones fail to send with `io::ErrorKind::InvalidInput`, without
+        /// reaching the handlers. Fails if this instance is not single.
+        pub fn set_max_message_size(&mut self, size: usize) -> Result<()> {
+            self.notifier()?.set_max_message_size(size);
+            Ok(())
+        }
+
+        /// Sets whether instances run by other users may notify this one and
+        /// send it messages, which only those run by the same user can by
+        /// default, e.g. for a service intentionally shared on a machine.
+        /// Fails if this instance is not single.
+        pub fn allow_other_users(&mut self, allow: bool) -> Result<()> {
+            Ok(self.notifier()?.allow_other_users(allow)?)
+        }
+
+        /// Calls `handler` with every message other instances send with
+        /// `SingleInstance::send` from now on, on a thread of its own that
+        /// stops once this instance is dropped. Replaces the receiver returned
+        /// by `SingleInstance::messages`, like calling it again would. Fails
+        /// if this instance is not single.
+        pub fn listen<F>(&mut self, mut handler: F) -> Result<()>
+        where
+            F: FnMut(Message) + Send + 'static,
+        {
+            let messages = self.messages()?;
+            thread::spawn(move || {
+                for message in messages {
+                    handler(message);
+                }
+            });
+            Ok(())
+        }
+
+        /// Returns a receiver of the JSON lines processes send from now on,
+        /// as described in `json_lines`, until this instance is dropped. Only
+        /// the receiver returned last gets lines. Fails if this instance is
+        /// not single.
+        #[cfg(feature = "serde")]
+        pub fn json_lines(&mut self) -> Result<Receiver<JsonMessage>> {
+            Ok(self.notifier()?.json_lines())
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::request_takeover`, with its PID and working
+        /// directory, until this instance is dropped. The handler runs on a
+        /// thread of its own and should have the application exit, or at
+        /// least drop this instance, for the other one to take over the name.
+        /// Fails if this instance is not single.
+        pub fn on_takeover<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) + Send + 'static,
+        {
+            self.notifier()?.on_takeover(handler);
+            Ok(())
+        }
+
+        /// Asks the instance holding the name to exit, see
+        /// `SingleInstance::on_takeover`, and waits up to `timeout`, or as
+        /// long as it takes without one, to claim the name with the same
+        /// backend, returning whether it did. Returns `false` right away if
+        /// the holder does not listen. Fails if this instance is single or was
+        /// not created with a `Backend`.
+        pub fn request_takeover(&mut self, timeout: Option<Duration>) -> Result<bool> {
+            let backend = self.backend_to_take_over()?;
+            if !notify::takeover(&self.name)? {
+                return Ok(false);
+            }
+            self.claim_within(backend, timeout, &Arc::new(Cancel::new()))
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::request_handover`, with its PID and working
+        /// directory, for the state to hand over to it. Once the state is
+        /// sent, the handler set with `SingleInstance::on_takeover` is called
+        /// for the application to exit. Fails if this instance is not single.
+        pub fn on_handover<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) -> Vec<u8> + Send + 'static,
+        {
+            self.notifier()?.on_handover(handler);
+            Ok(())
+        }
+
+        /// Asks the instance holding the name for its state and to exit, see
+        /// `SingleInstance::on_handover`, and waits up to `timeout`, or as
+        /// long as it takes without one, to claim the name with the same
+        /// backend. Returns the state if it did, empty if the holder has no
+        /// handover handler, or `None` if the name was not claimed or the
+        /// holder does not listen. Fails if this instance is single or was
+        /// not created with a `Backend`.
+        pub fn request_handover(&mut self, timeout: Option<Duration>) -> Result<Option<Vec<u8>>> {
+            let backend = self.backend_to_take_over()?;
+            let state = match notify::handover(&self.name)? {
+                Some(state) => state,
+                None => return Ok(None),
+            };
+            Ok(if self.claim_within(backend, timeout, &Arc::new(Cancel::new()))? {
+                Some(state)
+            } else {
+                None
+            })
+        }
+
+        /// Returns the backend to claim the name with once the holder exits.
+        fn backend_to_take_over(&self) -> Result<Backend> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            match self.backend {
+                Some(backend) => Ok(backend),
+                None => Err(io::Error::other("only names claimed with a backend can be taken over").into()),
+            }
+        }
+
+        /// Tries to claim the name with `backend` until `timeout` passes or
+        /// `cancel` is set, returning whether it did, and releasing the name
+        /// if it was claimed once `cancel` was set. The mutex of
+        /// `Backend::Default` on windows is waited for until its owner
+        /// releases it, other backends polled for.
+        fn claim_within(&mut self, backend: Backend, timeout: Option<Duration>, cancel: &Arc<Cancel>) -> Result<bool> {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            loop {
+                let lock = match backend {
+                    #[cfg(target_os = "windows")]
+                    Backend::Default => {
+                        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                        own_mutex(&self.name, timeout, cancel.event())?
+                            .map(|mutex| Lock::Mutex(mutex, windows_holder::publish(&self.name)))
+                    }
+                    _ => Self::claim(&self.name, backend)?,
+                };
+                if let Some(lock) = lock {
+                    // Nobody waits for a name claimed once cancelled.
+                    if cancel.is_cancelled() {
+                        return Ok(false);
+                    }
+                    self.lock = Some(lock);
+                    return Ok(true);
+                }
+                if cancel.is_cancelled() || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Ok(false);
+                }
+                thread::sleep(notify::TAKEOVER_POLL_INTERVAL);
+            }
+        }
+
+        /// Returns the notifier listening for other instances, started on
+        /// first use.
+        fn notifier(&mut self) -> Result<&Notifier> {
+            if !self.is_single() {
+                return Err(io::Error::other("this instance does not hold the name").into());
+            }
+            let notifier = match self.notifier.take() {
+                Some(notifier) => notifier,
+                None => notify::listen(&self.name)?,
+            };
+            Ok(self.notifier.insert(notifier))
+        }
+
+        /// Returns the listener of the port claimed with `Backend::TcpPort`,
+        /// for other instances to connect to.
+        #[cfg(feature = "tcp-port")]
+        pub fn tcp_listener(&self) -> Option<&TcpListener> {
+            match self.lock {
+                Some(Lock::TcpPort(ref listener)) => Some(listener),
+                _ => None,
+            }
+        }
+
+        /// Returns the first instance of the pipe claimed with
+        /// `Backend::NamedPipe`, for other instances to connect to.
+        #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+        pub fn pipe(&self) -> Option<&File> {
+            match self.lock {
+                Some(Lock::Pipe(ref pipe)) => Some(pipe),
+                _ => None,
+            }
+        }
+
+        /// Returns the PID of the instance holding the name if this one does
+        /// not, which that instance publishes for `Backend::Default` and
+        /// records in the file for `Backend::FileLock`.
+        pub fn holder_pid(&self) -> Option<u32> {
+            if self.is_single() {
+                return None;
+            }
+            match self.backend? {
+                #[cfg(target_os = "windows")]
+                Backend::Default => windows_holder::published(&self.name),
+                #[cfg(target_os = "windows")]
+                Backend::FileLock => windows_holder::find(Path::new(&self.name)),
+                // Without optional backends, no other ones are left.
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+
+        /// Waits up to `timeout`, or as long as it takes without one, for the
+        /// instance holding the name to exit, returning whether it did, by
+        /// waiting on a handle to its process. Fails if that instance is not
+        /// known, see `holder_pid`.
+        #[cfg(target_os = "windows")]
+        pub fn wait_for_exit(&self, timeout: Option<Duration>) -> Result<bool> {
+            let pid = self
+                .holder_pid()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the holder of the name is not known"))?;
+            let info = match self.backend {
+                Some(Backend::Default) => windows_holder::published_info(&self.name),
+                Some(Backend::FileLock) => windows_holder::read(Path::new(&self.name)),
+                _ => None,
+            };
+            let start_time = info.filter(|info| info.pid == pid).map(|info| info.start_time);
+            Ok(windows_holder::wait(pid, start_time, timeout)?)
         }
     }
 
-    impl Drop for SingleInstance {
+    /// A named mutex owned by a thread of its own, which releases it when
+    /// dropped. Mutexes are owned by threads rather than processes, and would
+    /// be abandoned by the thread that created one exiting, e.g. that of
+    /// `SingleInstance::wait_until_single_async`.
+    #[cfg(target_os = "windows")]
+    struct OwnedMutex {
+        handle: HANDLE,
+        release: Sender<()>,
+        thread: Option<thread::JoinHandle<()>>,
+    }
+
+    /// Returns the mutex `name` owned by a thread of its own if this call
+    /// created it, or once its owner released it or exited within `timeout`,
+    /// or as long as it takes without one, unless the event `cancel` is set
+    /// first. Returns `None` otherwise, also right away if the mutex cannot
+    /// be opened, such as one of another user in the `Global\` namespace.
+    #[cfg(target_os = "windows")]
+    fn own_mutex(name: &str, timeout: Option<Duration>, cancel: HANDLE) -> Result<Option<OwnedMutex>> {
+        let name = ::windows::object_name(name);
+        let denied_means_held = denied_means_held(name);
+        let name = U16CString::from_str(name)?;
+        let millis = match timeout {
+            Some(timeout) => timeout.as_millis().min(INFINITE as u128 - 1) as DWORD,
+            None => INFINITE,
+        };
+        let cancel = cancel as usize;
+        let (owned, owning) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || unsafe {
+            let handle = CreateMutexW(ptr::null_mut(), TRUE, name.as_ptr());
+            let last_error = GetLastError();
+            if handle.is_null() {
+                let denied = last_error == ERROR_ACCESS_DENIED && denied_means_held;
+                let _ = owned.send(if denied { Ok(None) } else { Err(last_error) });
+                return;
+            }
+            // The mutex is only owned right away if this call created it.
+            if last_error == ERROR_ALREADY_EXISTS {
+                let handles = [handle, cancel as HANDLE];
+                let count = if cancel == 0 { 1 } else { 2 };
+                match WaitForMultipleObjects(count, handles.as_ptr(), FALSE, millis) {
+                    // An abandoned mutex was held by a thread or process that
+                    // exited, which owned it.
+                    WAIT_OBJECT_0 | WAIT_ABANDONED_0 => {}
+                    _ => {
+                        CloseHandle(handle);
+                        let _ = owned.send(Ok(None));
+                        return;
+                    }
+                }
+            }
+            let _ = owned.send(Ok(Some(handle as usize)));
+            let _ = released.recv();
+            ReleaseMutex(handle);
+            CloseHandle(handle);
+        });
+        match owning.recv() {
+            Ok(Ok(Some(handle))) => Ok(Some(OwnedMutex {
+                handle: handle as HANDLE,
+                release,
+                thread: Some(thread),
+            })),
+            Ok(Ok(None)) => Ok(None),
+            Ok(Err(last_error)) => Err(SingleInstanceError::MutexError(last_error)),
+            Err(_) => Err(io::Error::other("the thread owning the mutex panicked").into()),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    impl Drop for OwnedMutex {
         fn drop(&mut self) {
-            if let Some(handle) = self.handle.take() {
-                unsafe {
+            // Released before returning, for the name to be free right away.
+            let _ = self.release.send(());
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    /// Returns a handle to the mutex `name` if this call created it, or
+    /// `None` if it already existed.
+    #[cfg(target_os = "cygwin")]
+    fn create_mutex(name: &str) -> Result<Option<HANDLE>> {
+        create_named(
+            name,
+            |name| unsafe { CreateMutexW(ptr::null_mut(), 0, name) },
+            SingleInstanceError::MutexError,
+        )
+    }
+
+    /// Returns a handle to the event `name` if this call created it, or
+    /// `None` if it already existed.
+    #[cfg(feature = "event")]
+    fn create_event(name: &str) -> Result<Option<HANDLE>> {
+        create_named(
+            name,
+            |name| unsafe { CreateEventW(ptr::null_mut(), 1, 0, name) },
+            SingleInstanceError::EventError,
+        )
+    }
+
+    /// Returns a handle to the semaphore `name` counting `limit` slots if one
+    /// of them was taken, or `None` if all of them are.
+    fn take_semaphore(name: &str, limit: usize) -> Result<Option<HANDLE>> {
+        if limit == 0 {
+            return Ok(None);
+        }
+        let limit = limit.min(i32::MAX as usize) as i32;
+        #[cfg(target_os = "windows")]
+        let name = ::windows::object_name(name);
+        let denied_means_held = denied_means_held(name);
+        let name = U16CString::from_str(name)?;
+        unsafe {
+            let handle = CreateSemaphoreW(ptr::null_mut(), limit, limit, name.as_ptr());
+            if handle.is_null() {
+                let last_error = GetLastError();
+                if last_error == ERROR_ACCESS_DENIED && denied_means_held {
+                    return Ok(None);
+                }
+                return Err(SingleInstanceError::SemaphoreError(last_error));
+            }
+            match WaitForSingleObject(handle, 0) {
+                WAIT_OBJECT_0 => Ok(Some(handle)),
+                WAIT_TIMEOUT => {
                     CloseHandle(handle);
+                    Ok(None)
+                }
+                _ => {
+                    let last_error = GetLastError();
+                    CloseHandle(handle);
+                    Err(SingleInstanceError::SemaphoreError(last_error))
                 }
             }
         }
     }
-}
 
-#[cfg(any(target_os = "linux", target_os="android"))]
-mod inner {
-    use error::Result;
-    use nix::sys::socket::{self, UnixAddr};
-    use nix::unistd;
-    use std::os::unix::prelude::RawFd;
+    /// Returns a handle to the object `name` if `create` created it, or
+    /// `None` if it already existed. `error` wraps the error code of a failed
+    /// creation.
+    #[cfg(any(feature = "event", target_os = "cygwin"))]
+    fn create_named(
+        name: &str,
+        create: fn(*const u16) -> HANDLE,
+        error: fn(u32) -> SingleInstanceError,
+    ) -> Result<Option<HANDLE>> {
+        #[cfg(target_os = "windows")]
+        let name = ::windows::object_name(name);
+        let denied_means_held = denied_means_held(name);
+        let name = U16CString::from_str(name)?;
+        unsafe {
+            let handle = create(name.as_ptr());
+            let last_error = GetLastError();
 
-    /// A struct representing one running instance.
-    pub struct SingleInstance {
-        maybe_sock: Option<RawFd>,
-    }
+            // https://docs.microsoft.com/en-us/windows/win32/api/synchapi/nf-synchapi-createmutexexw
+            if handle.is_null() && last_error == ERROR_ACCESS_DENIED && denied_means_held {
+                return Ok(None);
+            }
 
-    impl SingleInstance {
-        /// Returns a new SingleInstance object.
-        pub fn new(name: &str) -> Result<Self> {
-            let addr = UnixAddr::new_abstract(name.as_bytes())?;
-            let sock = socket::socket(
-                socket::AddressFamily::Unix,
-                socket::SockType::Stream,
-                // If we fork and exec, then make sure the child process doesn't
-                // hang on to this file descriptor.
-                socket::SockFlag::SOCK_CLOEXEC,
-                None,
-            )?;
-
-            let maybe_sock = match socket::bind(sock, &socket::SockAddr::Unix(addr)) {
-                Ok(()) => Some(sock),
-                Err(nix::errno::Errno::EADDRINUSE) => None,
-                Err(e) => return Err(e.into()),
-            };
+            if handle.is_null() || handle == ERROR_INVALID_HANDLE as _ {
+                Err(error(last_error))
+            } else if last_error == ERROR_ALREADY_EXISTS {
+                CloseHandle(handle);
+                Ok(None)
+            } else {
+                Ok(Some(handle))
+            }
+        }
+    }
 
-            Ok(Self { maybe_sock })
+    /// Returns whether failing to open the object `name` with
+    /// `ERROR_ACCESS_DENIED` means that it exists but was created by another
+    /// instance with a security descriptor this process does not pass: within
+    /// an AppContainer in packaged app mode, or in the `Global\` namespace,
+    /// where it may have been created by a service or another user.
+    fn denied_means_held(name: &str) -> bool {
+        #[cfg(target_os = "windows")]
+        {
+            if ::windows::packaged_app_mode() {
+                return true;
+            }
         }
+        name.starts_with("Global\\")
+    }
 
-        /// Returns whether this instance is single.
-        pub fn is_single(&self) -> bool {
-            self.maybe_sock.is_some()
+    /// The handle of the lock held: the mutex, event or semaphore, the
+    /// locked file or the pipe, e.g. to wait on it with
+    /// `WaitForMultipleObjects`. Backends holding no handle, such as
+    /// `Backend::TcpPort`, and instances not holding the name return a null
+    /// handle.
+    #[cfg(target_os = "windows")]
+    impl AsRawHandle for SingleInstance {
+        fn as_raw_handle(&self) -> RawHandle {
+            match self.lock {
+                Some(Lock::Handle(handle)) | Some(Lock::Semaphore(handle)) => handle as RawHandle,
+                Some(Lock::Mutex(ref mutex, _)) => mutex.handle as RawHandle,
+                Some(Lock::File(ref lock)) => lock.as_raw_handle(),
+                #[cfg(feature = "named-pipe")]
+                Some(Lock::Pipe(ref pipe)) => pipe.as_raw_handle(),
+                _ => ptr::null_mut(),
+            }
         }
     }
 
     impl Drop for SingleInstance {
         fn drop(&mut self) {
-            if let Some(sock) = self.maybe_sock {
-                // Intentionally discard any close errors.
-                let _ = unistd::close(sock);
-            }
+            self.release();
         }
     }
 }
 
-#[cfg(target_os = "macos")]
+#[cfg(all(unix, not(target_os = "cygwin")))]
 mod inner {
+    #[cfg(abstract_socket)]
+    use abstract_socket::{self, AbstractSocket};
+    use backend::{Backend, Custom, CustomBackend};
+    use cancel::{self, Cancel};
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    use dbus;
     use error::Result;
-    use libc::{__error, flock, EWOULDBLOCK, LOCK_EX, LOCK_NB};
-    use std::fs::File;
-    use std::os::unix::io::AsRawFd;
-    use std::path::Path;
+    use file_lock::{self, FileLock};
+    use holder;
+    #[cfg(target_os = "linux")]
+    use holder::Holder;
+    use lock_info::LockInfo;
+    #[cfg(feature = "pid-file")]
+    use pid_file::{self, PidFile};
+    #[cfg(all(feature = "bundle-id", target_os = "macos"))]
+    use macos;
+    #[cfg(feature = "serde")]
+    use json_lines::JsonMessage;
+    use names;
+    use notify::{self, ForwardResult, Message, Notifier, SecondInstance, Settings};
+    #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+    use robust_mutex::{self, RobustMutex};
+    #[cfg(posix_semaphore)]
+    use semaphore::{self, Semaphore};
+    use std::borrow::Cow;
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    use std::env;
+    use std::io;
+    #[cfg(feature = "tcp-port")]
+    use std::net::TcpListener;
+    use std::os::unix::io::{AsRawFd, BorrowedFd, RawFd};
+    #[cfg(feature = "unix-socket")]
+    use std::os::unix::net::UnixListener;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::sync::mpsc::Receiver;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    #[cfg(sysv_semaphore)]
+    use sysv_semaphore::{self, SysvSemaphore};
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    use systemd;
+    #[cfg(feature = "tcp-port")]
+    use tcp;
+    #[cfg(feature = "unix-socket")]
+    use unix_socket::{self, UnixSocket};
+
+    /// A claimed instance name, released when dropped.
+    #[allow(dead_code)] // The locks are only ever dropped, never read.
+    enum Lock {
+        File(FileLock),
+        #[cfg(abstract_socket)]
+        AbstractSocket(AbstractSocket),
+        #[cfg(posix_semaphore)]
+        Semaphore(Semaphore),
+        #[cfg(sysv_semaphore)]
+        SysvSemaphore(SysvSemaphore),
+        #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+        RobustMutex(RobustMutex),
+        #[cfg(all(feature = "dbus", target_os = "linux"))]
+        DBus(zbus::blocking::Connection),
+        #[cfg(all(feature = "bundle-id", target_os = "macos"))]
+        BundleId,
+        #[cfg(feature = "tcp-port")]
+        TcpPort(TcpListener),
+        #[cfg(feature = "unix-socket")]
+        UnixSocket(UnixSocket),
+        #[cfg(feature = "pid-file")]
+        PidFile(PidFile),
+        Custom(Custom),
+    }
+
+    /// The outcome of blocking until a name is claimed, see
+    /// `SingleInstance::wait_for`.
+    enum Waited {
+        Claimed(Lock),
+        GaveUp,
+        /// The backend cannot block and is polled for instead.
+        CannotBlock,
+    }
 
     /// A struct representing one running instance.
     pub struct SingleInstance {
-        _file: File,
-        is_single: bool,
+        /// Listening for other instances, stopped before the name is released.
+        notifier: Option<Notifier>,
+        /// The handlers and options of the notifier stopped by `release`,
+        /// restored by `try_reacquire`.
+        released: Option<Settings>,
+        lock: Option<Lock>,
+        backend: Option<Backend>,
+        name: String,
+        /// The instance holding the name, if known.
+        #[cfg(target_os = "linux")]
+        holder: Option<Holder>,
     }
 
     impl SingleInstance {
         /// Returns a new SingleInstance object.
         pub fn new(name: &str) -> Result<Self> {
-            let path = Path::new(name);
-            let file = if path.exists() {
-                File::open(path)?
-            } else {
-                File::create(path)?
+            Self::with_backend(name, Backend::Default)
+        }
+
+        /// Returns a new SingleInstance object claiming `name` with `backend`.
+        pub fn with_backend(name: &str, backend: Backend) -> Result<Self> {
+            let name = &*Self::sanitized(name, backend)?;
+            let lock = Self::claim(name, backend)?;
+            #[cfg(target_os = "linux")]
+            let holder = match backend {
+                Backend::FileLock if lock.is_none() => holder::find(&lock_path(name)),
+                #[cfg(feature = "systemd")]
+                Backend::Systemd if lock.is_none() => holder::find(&systemd::lock_path(name)),
+                _ => None,
             };
-            unsafe {
-                let rc = flock(file.as_raw_fd(), LOCK_EX | LOCK_NB);
-                let is_single = rc == 0 || EWOULDBLOCK != *__error();
-                Ok(Self {
-                    _file: file,
-                    is_single,
-                })
+            if lock.is_none() {
+                notify::send(name);
             }
+            Ok(Self {
+                notifier: None,
+                released: None,
+                lock,
+                backend: Some(backend),
+                name: name.to_owned(),
+                #[cfg(target_os = "linux")]
+                holder,
+            })
         }
 
-        /// Returns whether this instance is single.
-        pub fn is_single(&self) -> bool {
-            self.is_single
-        }
+        /// Returns `name` suiting `backend`, see `names`.
+        fn sanitized(name: &str, backend: Backend) -> Result<Cow<'_, str>> {
+            match backend {
+                #[cfg(abstract_socket)]
+                Backend::Default => {
+                    #[cfg(target_os = "android")]
+                    {
+                        if ::android::lock_path(name).is_some() {
+                            return names::file(name);
+                        }
+                    }
+                    names::abstract_socket(name)
+                }
+                #[cfg(not(abstract_socket))]
+                Backend::Default => names::file(name),
+                Backend::FileLock => names::file(name),
+                #[cfg(flock_backend)]
+                Backend::Flock => names::file(name),
+                #[cfg(posix_semaphore)]
+                Backend::Semaphore => names::posix_object(name),
+                #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+                Backend::RobustMutex => names::posix_object(name),
+                #[cfg(all(feature = "systemd", target_os = "linux"))]
+                Backend::Systemd => names::file(name),
+                #[cfg(feature = "pid-file")]
+                Backend::PidFile { .. } => names::file(name),
+                // The other backends hash the name or take it as is.
+                #[allow(unreachable_patterns)]
+                _ => Ok(Cow::Borrowed(name)),
+            }
+        }
+
+        /// Claims `name` with `backend`, returning `None` if another instance
+        /// holds it.
+        fn claim(name: &str, backend: Backend) -> Result<Option<Lock>> {
+            Ok(match backend {
+                Backend::Default => Self::default_lock(name)?,
+                Backend::FileLock => file_lock::try_lock(&lock_path(name))?.map(Lock::File),
+                #[cfg(flock_backend)]
+                Backend::Flock => file_lock::try_flock(&lock_path(name))?.map(Lock::File),
+                #[cfg(abstract_socket)]
+                Backend::AbstractSocket => abstract_socket::try_bind(name)?.map(Lock::AbstractSocket),
+                #[cfg(posix_semaphore)]
+                Backend::Semaphore => semaphore::try_acquire(name)?.map(Lock::Semaphore),
+                #[cfg(sysv_semaphore)]
+                Backend::SysvSemaphore => sysv_semaphore::try_acquire(name)?.map(Lock::SysvSemaphore),
+                #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+                Backend::RobustMutex => robust_mutex::try_lock(name)?.map(Lock::RobustMutex),
+                #[cfg(all(feature = "dbus", target_os = "linux"))]
+                Backend::DBus => dbus::try_own(name)?.map(Lock::DBus),
+                #[cfg(all(feature = "systemd", target_os = "linux"))]
+                Backend::Systemd => file_lock::try_lock(&systemd::lock_path(name))?.map(Lock::File),
+                #[cfg(all(feature = "bundle-id", target_os = "macos"))]
+                Backend::BundleId => {
+                    if macos::others_running(name)? {
+                        None
+                    } else {
+                        Some(Lock::BundleId)
+                    }
+                }
+                #[cfg(feature = "tcp-port")]
+                Backend::TcpPort => tcp::try_bind(name)?.map(Lock::TcpPort),
+                #[cfg(feature = "unix-socket")]
+                Backend::UnixSocket => unix_socket::try_bind(name)?.map(Lock::UnixSocket),
+                #[cfg(feature = "pid-file")]
+                Backend::PidFile { same_executable } => {
+                    pid_file::try_create(&lock_path(name), same_executable)?.map(Lock::PidFile)
+                }
+            })
+        }
+
+        /// Returns a new SingleInstance object claiming `name` with a backend
+        /// supplied by the application.
+        pub fn with_custom_backend(name: &str, backend: Box<dyn CustomBackend>) -> Result<Self> {
+            let lock = Custom::acquire(backend, name)?.map(Lock::Custom);
+            if lock.is_none() {
+                notify::send(name);
+            }
+            Ok(Self {
+                notifier: None,
+                released: None,
+                lock,
+                backend: None,
+                name: name.to_owned(),
+                #[cfg(target_os = "linux")]
+                holder: None,
+            })
+        }
+
+        /// Returns a new SingleInstance object taking one of `limit` slots
+        /// for `name`, so that at most `limit` instances are single at once.
+        ///
+        /// Each slot is a lock file next to the one for `name`, with the slot
+        /// number appended, and the first free slot is locked.
+        pub fn with_limit(name: &str, limit: usize) -> Result<Self> {
+            let name = &*names::file(name)?;
+            let mut lock = None;
+            for slot in 0..limit {
+                let mut path = lock_path(name).into_os_string();
+                path.push(format!(".{}", slot));
+                if let Some(file) = file_lock::try_lock(Path::new(&path))? {
+                    lock = Some(Lock::File(file));
+                    break;
+                }
+            }
+            Ok(Self {
+                notifier: None,
+                released: None,
+                lock,
+                backend: None,
+                name: name.to_owned(),
+                #[cfg(target_os = "linux")]
+                holder: None,
+            })
+        }
+
+        /// Linux binds an abstract socket, and so does android unless a lock
+        /// directory is set. Everywhere else, or without the `abstract-socket`
+        /// feature, the file at `name` is locked.
+        #[cfg(abstract_socket)]
+        fn default_lock(name: &str) -> Result<Option<Lock>> {
+            #[cfg(target_os = "android")]
+            {
+                if let Some(path) = ::android::lock_path(name) {
+                    return Ok(file_lock::try_lock(&path)?.map(Lock::File));
+                }
+            }
+            Ok(abstract_socket::try_bind(name)?.map(Lock::AbstractSocket))
+        }
+
+        #[cfg(not(abstract_socket))]
+        fn default_lock(name: &str) -> Result<Option<Lock>> {
+            Ok(file_lock::try_lock(&lock_path(name))?.map(Lock::File))
+        }
+
+        /// Returns whether this instance is single.
+        pub fn is_single(&self) -> bool {
+            match self.lock {
+                Some(Lock::Custom(ref custom)) => custom.is_held(),
+                ref lock => lock.is_some(),
+            }
+        }
+
+        /// Releases the name as dropping this instance would, so that
+        /// `is_single` returns `false` from now on and another instance can
+        /// claim it, e.g. when this one is kept in a static. Handlers stop
+        /// being called. Does nothing if the name is not held.
+        pub fn release(&mut self) {
+            // Stopped first, like on drop, so that no handler runs once
+            // another instance may hold the name.
+            if let Some(notifier) = self.notifier.take() {
+                self.released = Some(notifier.settings());
+            }
+            self.lock = None;
+        }
+
+        /// Tries once to claim the name with the backend this instance was
+        /// created with, if it does not hold it because another instance did
+        /// when it was created or it called `SingleInstance::release`, and
+        /// returns whether it holds it now. The handlers and options set
+        /// before releasing the name are kept and listened with again. Fails
+        /// if this instance was not created with a `Backend`.
+        pub fn try_reacquire(&mut self) -> Result<bool> {
+            self.wait_until_single(Some(Duration::ZERO))
+        }
+
+        /// Waits up to `timeout`, or as long as it takes without one, for the
+        /// instance holding the name to release it, and claims it like
+        /// `SingleInstance::try_reacquire`, e.g. for a command line tool to
+        /// queue up behind a running job. Returns whether this instance holds
+        /// the name now. Fails if it was not created with a `Backend`.
+        pub fn wait_until_single(&mut self, timeout: Option<Duration>) -> Result<bool> {
+            self.wait_until_single_unless(timeout, &Arc::new(Cancel::new()))
+        }
+
+        /// Waits like `SingleInstance::wait_until_single` unless `cancel` is
+        /// set meanwhile, which gives up without claiming the name.
+        pub(crate) fn wait_until_single_unless(&mut self, timeout: Option<Duration>, cancel: &Arc<Cancel>) -> Result<bool> {
+            if self.is_single() {
+                return Ok(true);
+            }
+            let backend = match self.backend {
+                Some(backend) => backend,
+                None => return Err(io::Error::other("only names claimed with a backend can be claimed again").into()),
+            };
+            if !self.claim_within(backend, timeout, cancel)? {
+                return Ok(false);
+            }
+            if let Some(settings) = self.released.take() {
+                self.notifier = Some(notify::listen_with(&self.name, settings)?);
+            }
+            Ok(true)
+        }
+
+        /// Returns the backend this instance was created with, which for
+        /// `SingleInstance::with_backends` is the first one that worked, or
+        /// `None` for a custom backend or a limit.
+        pub fn backend(&self) -> Option<Backend> {
+            self.backend
+        }
+
+        /// Returns the name this instance was created with.
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Calls `handler` for every instance failing to claim the name from
+        /// now on, with its PID and working directory, until this instance is
+        /// dropped. The handler runs on a thread of its own, e.g. to bring the
+        /// application's window to the front. Fails if this instance is not
+        /// single.
+        pub fn on_second_instance<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) + Send + 'static,
+        {
+            self.notifier()?.on_second_instance(handler);
+            Ok(())
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::activate`, with its PID and working directory,
+        /// until this instance is dropped. The handler runs on a thread of its
+        /// own and is left to bring the application's window to the front.
+        /// Fails if this instance is not single.
+        pub fn on_activate<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) + Send + 'static,
+        {
+            self.notifier()?.on_activate(handler);
+            Ok(())
+        }
+
+        /// Asks the instance holding the name to activate itself, returning
+        /// `false` if it does not listen, see `SingleInstance::on_activate`.
+        /// The activation token this instance was started with is sent along,
+        /// see `SecondInstance::activation_token`. Fails if this instance is
+        /// single.
+        pub fn activate(&self) -> Result<bool> {
+            self.activate_with_token(&notify::activation_token())
+        }
+
+        /// Asks the instance holding the name to activate itself like
+        /// `SingleInstance::activate`, sending `token` as the activation
+        /// token instead of the one this instance was started with, e.g. one
+        /// requested from the compositor, or none if it is empty.
+        pub fn activate_with_token(&self, token: &str) -> Result<bool> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::activate(&self.name, token)?)
+        }
+
+        /// Sends `data` to the instance holding the name and returns its
+        /// reply, or `None` if it does not listen, see
+        /// `SingleInstance::messages`. Blocks until the holder replies to or
+        /// drops the message. Fails if this instance is single.
+        pub fn send(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.send_fds(data, &[])
+        }
+
+        /// Sends `data` along with the open files or sockets `fds` to the
+        /// instance holding the name, like `SingleInstance::send`. The holder
+        /// receives descriptors of its own for them in `Message::fds`, so that
+        /// it can use them without opening any paths itself. At most 64 can
+        /// be sent at once.
+        pub fn send_fds(&self, data: &[u8], fds: &[BorrowedFd<'_>]) -> Result<Option<Vec<u8>>> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::message(&self.name, data, fds)?)
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::send_and_wait_reply`, with its PID and working
+        /// directory and the data it sent, and replies with what the handler
+        /// returns, until this instance is dropped. The handler runs on a
+        /// thread of its own. Fails if this instance is not single.
+        pub fn on_request<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, Vec<u8>) -> Vec<u8> + Send + 'static,
+        {
+            self.notifier()?.on_request(handler);
+            Ok(())
+        }
+
+        /// Sends `data` to the instance holding the name and returns what its
+        /// `SingleInstance::on_request` handler replied, so that the caller
+        /// knows the request was handled. Returns `None` if the holder does
+        /// not listen or has no request handler, and fails with
+        /// `io::ErrorKind::TimedOut` if no reply arrived within `timeout`.
+        /// Fails if this instance is single.
+        pub fn send_and_wait_reply(&self, data: &[u8], timeout: Duration) -> Result<Option<Vec<u8>>> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::request_reply(&self.name, data, timeout)?)
+        }
+
+        /// Sends `data` to every instance that subscribed with
+        /// `SingleInstance::subscribe` and is still connected, returning how
+        /// many it was sent to. Fails if this instance is not single.
+        pub fn broadcast(&mut self, data: &[u8]) -> Result<usize> {
+            Ok(self.notifier()?.broadcast(data))
+        }
+
+        /// Stays connected to the instance holding the name and returns a
+        /// receiver of what it sends with `SingleInstance::broadcast` from now
+        /// on, or `None` if it does not listen. The receiver is disconnected
+        /// once the holder drops its `SingleInstance`. Fails if this instance
+        /// is single.
+        pub fn subscribe(&self) -> Result<Option<Receiver<Vec<u8>>>> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            Ok(notify::subscribe(&self.name)?)
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::forward_urls`, with its PID and working directory
+        /// and the URLs it forwarded, until this instance is dropped. The
+        /// handler runs on a thread of its own. Fails if this instance is not
+        /// single.
+        pub fn on_urls<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, Vec<String>) + Send + 'static,
+        {
+            self.notifier()?.on_urls(handler);
+            Ok(())
+        }
+
+        /// Forwards `urls`, such as the deep links an application registered
+        /// as a protocol handler is started with, to the instance holding the
+        /// name, and blocks until its `SingleInstance::on_urls` handler
+        /// returned. Returns `ForwardResult::Single` without forwarding
+        /// anything if this instance holds the name itself.
+        pub fn forward_urls<I, S>(&self, urls: I) -> Result<ForwardResult>
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            if self.is_single() {
+                return Ok(ForwardResult::Single);
+            }
+            Ok(notify::urls(&self.name, urls)?)
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::forward_open`, with its PID and working directory
+        /// and the paths it forwarded, until this instance is dropped. Paths
+        /// are made absolute against the working directory of the other
+        /// instance and canonicalized if they exist. The handler runs on a
+        /// thread of its own. Fails if this instance is not single.
+        pub fn on_open<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, Vec<PathBuf>) + Send + 'static,
+        {
+            self.notifier()?.on_open(handler);
+            Ok(())
+        }
+
+        /// Forwards `paths`, such as the documents a user opened with the
+        /// application while it was running, to the instance holding the
+        /// name, and blocks until its `SingleInstance::on_open` handler
+        /// returned. Returns `ForwardResult::Single` without forwarding
+        /// anything if this instance holds the name itself.
+        pub fn forward_open<I, P>(&self, paths: I) -> Result<ForwardResult>
+        where
+            I: IntoIterator<Item = P>,
+            P: AsRef<Path>,
+        {
+            if self.is_single() {
+                return Ok(ForwardResult::Single);
+            }
+            Ok(notify::open(&self.name, paths)?)
+        }
+
+        /// Serves `org.freedesktop.Application` on the connection owning the
+        /// name with `Backend::DBus`, so that the desktop environment itself
+        /// can activate the application, which the handler set with
+        /// `SingleInstance::on_activate` then receives, ask it to open
+        /// files, received by `SingleInstance::on_open`, or other URIs,
+        /// received by `SingleInstance::on_urls`, and activate its actions,
+        /// received by `SingleInstance::on_action`. The activation token the
+        /// desktop passed is in `SecondInstance::activation_token`, the PID
+        /// and working directory are left unknown. For the desktop to use it,
+        /// the name has to be the ID of the application's desktop file, which
+        /// sets `DBusActivatable=true`. Fails if the name is not held with
+        /// `Backend::DBus`.
+        #[cfg(all(feature = "dbus", target_os = "linux"))]
+        pub fn serve_application(&mut self) -> Result<()> {
+            let dispatcher = self.notifier()?.dispatcher();
+            match self.lock {
+                Some(Lock::DBus(ref conn)) => Ok(dbus::serve_application(conn, &self.name, dispatcher)?),
+                _ => Err(io::Error::other("the name is not held with Backend::DBus").into()),
+            }
+        }
+
+        /// Calls `handler` whenever the desktop environment activates one of
+        /// the application's actions through `SingleInstance::serve_application`,
+        /// with the name of the action and its parameter, if it takes one,
+        /// until this instance is dropped. The handler runs on a thread of its
+        /// own. Fails if this instance is not single.
+        #[cfg(all(feature = "dbus", target_os = "linux"))]
+        pub fn on_action<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance, String, Option<zbus::zvariant::OwnedValue>) + Send + 'static,
+        {
+            self.notifier()?.on_action(handler);
+            Ok(())
+        }
+
+        /// Returns a receiver of the messages other instances send with
+        /// `SingleInstance::send` from now on, until this instance is dropped.
+        /// It is a plain `std::sync::mpsc::Receiver` fed by the thread
+        /// listening for other instances, so existing event loops can check
+        /// it with `try_recv` or `recv_timeout` between their own events.
+        /// Only the receiver returned last gets messages. Fails if this
+        /// instance is not single.
+        pub fn messages(&mut self) -> Result<Receiver<Message>> {
+            Ok(self.notifier()?.messages())
+        }
+
+        /// Returns a message another instance sent with `SingleInstance::send`
+        /// if one is waiting, without blocking, e.g. to be called once per
+        /// frame from the loop of a game or GUI. Messages are queued from the
+        /// first call on, replacing the receiver returned by
+        /// `SingleInstance::messages` like calling it again would. Fails if
+        /// this instance is not single.
+        pub fn poll_message(&mut self) -> Result<Option<Message>> {
+            Ok(self.notifier()?.poll_message())
+        }
+
+        /// Returns a receiver of the messages other instances send with
+        /// `SingleInstance::send` from now on for async applications, fed by a
+        /// thread of its own until this instance is dropped. Replaces the
+        /// receiver returned by `SingleInstance::messages` like calling it
+        /// again would. Fails if this instance is not single.
+        #[cfg(feature = "tokio")]
+        pub fn async_messages(&mut self) -> Result<tokio::sync::mpsc::Receiver<Message>> {
+            Ok(self.notifier()?.async_messages())
+        }
+
+        /// Has the X11 window `window` of this instance mapped and activated,
+        /// which raises and focuses it, whenever another instance calls
+        /// `SingleInstance::activate`, before the handler set with
+        /// `SingleInstance::on_activate` is called. Fails if this instance is
+        /// not single.
+        #[cfg(all(feature = "x11", target_os = "linux"))]
+        pub fn set_x11_window(&mut self, window: u32) -> Result<()> {
+            self.notifier()?.set_x11_window(window);
+            Ok(())
+        }
+
+        /// Has this app unhidden, its minimized windows restored and brought
+        /// to the front with `macos::activate_current` whenever another
+        /// instance calls `SingleInstance::activate`, before the handler set
+        /// with `SingleInstance::on_activate` is called, like clicking its
+        /// icon in the Dock would. Fails if this instance is not single.
+        #[cfg(all(feature = "appkit", target_os = "macos"))]
+        pub fn activate_app_on_request(&mut self) -> Result<()> {
+            self.notifier()?.activate_app_on_request();
+            Ok(())
+        }
+
+        /// Returns how many times another instance failed to claim the name
+        /// since this one started listening for them, with the first call to
+        /// this or any other method doing so, such as
+        /// `SingleInstance::on_second_instance`, which is called on every
+        /// attempt. This allows telling users how often they tried to start an
+        /// application that is already running. Fails if this instance is not
+        /// single.
+        pub fn attempt_count(&mut self) -> Result<u64> {
+            Ok(self.notifier()?.attempt_count())
+        }
+
+        /// Sets the size in bytes of the largest message other instances may
+        /// send this one from now on, 16 MiB by default, counting a header
+        /// holding the working directory and forwarded environment variables.
+        /// Larger ones fail to send with `io::ErrorKind::InvalidInput`, without
+        /// reaching the handlers. Fails if this instance is not single.
+        pub fn set_max_message_size(&mut self, size: usize) -> Result<()> {
+            self.notifier()?.set_max_message_size(size);
+            Ok(())
+        }
+
+        /// Sets whether instances run by other users may notify this one and
+        /// send it messages, which only those run by the same user can by
+        /// default, e.g. for a service intentionally shared on a machine.
+        /// Fails if this instance is not single.
+        pub fn allow_other_users(&mut self, allow: bool) -> Result<()> {
+            Ok(self.notifier()?.allow_other_users(allow)?)
+        }
+
+        /// Calls `handler` with every message other instances send with
+        /// `SingleInstance::send` from now on, on a thread of its own that
+        /// stops once this instance is dropped. Replaces the receiver returned
+        /// by `SingleInstance::messages`, like calling it again would. Fails
+        /// if this instance is not single.
+        pub fn listen<F>(&mut self, mut handler: F) -> Result<()>
+        where
+            F: FnMut(Message) + Send + 'static,
+        {
+            let messages = self.messages()?;
+            thread::spawn(move || {
+                for message in messages {
+                    handler(message);
+                }
+            });
+            Ok(())
+        }
+
+        /// Returns a receiver of the JSON lines processes send from now on,
+        /// as described in `json_lines`, until this instance is dropped. Only
+        /// the receiver returned last gets lines. Fails if this instance is
+        /// not single.
+        #[cfg(feature = "serde")]
+        pub fn json_lines(&mut self) -> Result<Receiver<JsonMessage>> {
+            Ok(self.notifier()?.json_lines())
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::request_takeover`, with its PID and working
+        /// directory, until this instance is dropped. The handler runs on a
+        /// thread of its own and should have the application exit, or at
+        /// least drop this instance, for the other one to take over the name.
+        /// Fails if this instance is not single.
+        pub fn on_takeover<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) + Send + 'static,
+        {
+            self.notifier()?.on_takeover(handler);
+            Ok(())
+        }
+
+        /// Asks the instance holding the name to exit, see
+        /// `SingleInstance::on_takeover`, and waits up to `timeout`, or as
+        /// long as it takes without one, to claim the name with the same
+        /// backend, returning whether it did. Returns `false` right away if
+        /// the holder does not listen. Fails if this instance is single or was
+        /// not created with a `Backend`.
+        pub fn request_takeover(&mut self, timeout: Option<Duration>) -> Result<bool> {
+            let backend = self.backend_to_take_over()?;
+            if !notify::takeover(&self.name)? {
+                return Ok(false);
+            }
+            self.claim_within(backend, timeout, &Arc::new(Cancel::new()))
+        }
+
+        /// Calls `handler` whenever another instance calls
+        /// `SingleInstance::request_handover`, with its PID and working
+        /// directory, for the state to hand over to it. Once the state is
+        /// sent, the handler set with `SingleInstance::on_takeover` is called
+        /// for the application to exit. Fails if this instance is not single.
+        pub fn on_handover<F>(&mut self, handler: F) -> Result<()>
+        where
+            F: FnMut(SecondInstance) -> Vec<u8> + Send + 'static,
+        {
+            self.notifier()?.on_handover(handler);
+            Ok(())
+        }
+
+        /// Asks the instance holding the name for its state and to exit, see
+        /// `SingleInstance::on_handover`, and waits up to `timeout`, or as
+        /// long as it takes without one, to claim the name with the same
+        /// backend. Returns the state if it did, empty if the holder has no
+        /// handover handler, or `None` if the name was not claimed or the
+        /// holder does not listen. Fails if this instance is single or was
+        /// not created with a `Backend`.
+        pub fn request_handover(&mut self, timeout: Option<Duration>) -> Result<Option<Vec<u8>>> {
+            let backend = self.backend_to_take_over()?;
+            let state = match notify::handover(&self.name)? {
+                Some(state) => state,
+                None => return Ok(None),
+            };
+            Ok(if self.claim_within(backend, timeout, &Arc::new(Cancel::new()))? {
+                Some(state)
+            } else {
+                None
+            })
+        }
+
+        /// Returns the backend to claim the name with once the holder exits.
+        fn backend_to_take_over(&self) -> Result<Backend> {
+            if self.is_single() {
+                return Err(io::Error::other("this instance holds the name").into());
+            }
+            match self.backend {
+                Some(backend) => Ok(backend),
+                None => Err(io::Error::other("only names claimed with a backend can be taken over").into()),
+            }
+        }
+
+        /// Tries to claim the name with `backend` until `timeout` passes or
+        /// `cancel` is set, returning whether it did, and releasing the name
+        /// if it was claimed once `cancel` was set. Lock files are waited
+        /// for until their holder releases them, other backends polled for.
+        fn claim_within(&mut self, backend: Backend, timeout: Option<Duration>, cancel: &Arc<Cancel>) -> Result<bool> {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let mut blocking = true;
+            loop {
+                let lock = match Self::claim(&self.name, backend)? {
+                    Some(lock) => lock,
+                    None if cancel.is_cancelled() || deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                        return Ok(false)
+                    }
+                    None if blocking => match Self::wait_for(&self.name, backend, deadline, cancel)? {
+                        Waited::Claimed(lock) => lock,
+                        Waited::GaveUp => return Ok(false),
+                        Waited::CannotBlock => {
+                            blocking = false;
+                            continue;
+                        }
+                    },
+                    None => {
+                        thread::sleep(notify::TAKEOVER_POLL_INTERVAL);
+                        continue;
+                    }
+                };
+                // Nobody waits for a name claimed once cancelled.
+                if cancel.is_cancelled() {
+                    return Ok(false);
+                }
+                self.lock = Some(lock);
+                #[cfg(target_os = "linux")]
+                {
+                    self.holder = None;
+                }
+                return Ok(true);
+            }
+        }
+
+        /// Blocks until the name is claimed with `backend`, `deadline`
+        /// passes or `cancel` is set, for the backends that can.
+        fn wait_for(name: &str, backend: Backend, deadline: Option<Instant>, cancel: &Arc<Cancel>) -> Result<Waited> {
+            let path = lock_path(name);
+            let waited = match backend {
+                Backend::FileLock => cancel::wait_on_thread(move || file_lock::wait_lock(&path), deadline, cancel),
+                #[cfg(flock_backend)]
+                Backend::Flock => cancel::wait_on_thread(move || file_lock::wait_flock(&path), deadline, cancel),
+                _ => return Ok(Waited::CannotBlock),
+            };
+            Ok(match waited {
+                Some(lock) => match lock? {
+                    Some(lock) => Waited::Claimed(Lock::File(lock)),
+                    None => Waited::CannotBlock,
+                },
+                None => Waited::GaveUp,
+            })
+        }
+
+        /// Returns the notifier listening for other instances, started on
+        /// first use.
+        fn notifier(&mut self) -> Result<&Notifier> {
+            if !self.is_single() {
+                return Err(io::Error::other("this instance does not hold the name").into());
+            }
+            let notifier = match self.notifier.take() {
+                Some(notifier) => notifier,
+                None => notify::listen(&self.name)?,
+            };
+            Ok(self.notifier.insert(notifier))
+        }
+
+        /// Returns the listener of the port claimed with `Backend::TcpPort`,
+        /// for other instances to connect to.
+        #[cfg(feature = "tcp-port")]
+        pub fn tcp_listener(&self) -> Option<&TcpListener> {
+            match self.lock {
+                Some(Lock::TcpPort(ref listener)) => Some(listener),
+                _ => None,
+            }
+        }
+
+        /// Returns the listener of the socket claimed with
+        /// `Backend::UnixSocket`, for other instances to connect to.
+        #[cfg(feature = "unix-socket")]
+        pub fn unix_listener(&self) -> Option<&UnixListener> {
+            match self.lock {
+                Some(Lock::UnixSocket(ref socket)) => Some(&socket.listener),
+                _ => None,
+            }
+        }
+
+        /// Returns the path of the file or socket the name is claimed through
+        /// with the backend this instance was created with, as resolved for
+        /// the platform, e.g. into the lock directory on android, or `None`
+        /// for backends claiming no path, custom backends and limits.
+        pub fn lock_path(&self) -> Option<PathBuf> {
+            Some(match self.backend? {
+                #[cfg(not(abstract_socket))]
+                Backend::Default => lock_path(&self.name),
+                #[cfg(all(abstract_socket, target_os = "android"))]
+                Backend::Default => ::android::lock_path(&self.name)?,
+                Backend::FileLock => lock_path(&self.name),
+                #[cfg(flock_backend)]
+                Backend::Flock => lock_path(&self.name),
+                #[cfg(all(feature = "systemd", target_os = "linux"))]
+                Backend::Systemd => systemd::lock_path(&self.name),
+                #[cfg(feature = "unix-socket")]
+                Backend::UnixSocket => unix_socket::path(&self.name),
+                #[cfg(feature = "pid-file")]
+                Backend::PidFile { .. } => lock_path(&self.name),
+                // Without optional backends, no other ones are left.
+                #[allow(unreachable_patterns)]
+                _ => return None,
+            })
+        }
+
+        /// Keeps the lock file as it is once the name is released, if
+        /// `persist` is set, for deployments expecting a PID file to be left
+        /// behind: the file of `Backend::PidFile` is not removed, and the
+        /// holder recorded in a `Backend::FileLock` file is not cleared.
+        /// Applies to the lock held, if any.
+        pub fn persist_lock_file(&mut self, persist: bool) {
+            match self.lock {
+                Some(Lock::File(ref mut lock)) => lock.set_persist(persist),
+                #[cfg(feature = "pid-file")]
+                Some(Lock::PidFile(ref mut pid_file)) => pid_file.set_persist(persist),
+                _ => {}
+            }
+        }
+
+        /// Returns the record of the instance holding `name` claimed with
+        /// `backend`, without trying to claim it, e.g. for tools telling who
+        /// holds it and since when. The holder of a `Backend::FileLock` or
+        /// `Backend::Systemd` lock writes it into the file. Returns `None` if
+        /// no running instance recorded itself, or for other backends.
+        pub fn read_lock_info(name: &str, backend: Backend) -> Option<LockInfo> {
+            let name = &*Self::sanitized(name, backend).ok()?;
+            match backend {
+                Backend::FileLock => holder::read(&lock_path(name)),
+                #[cfg(all(feature = "systemd", target_os = "linux"))]
+                Backend::Systemd => holder::read(&systemd::lock_path(name)),
+                _ => None,
+            }
+        }
+
+        /// Returns the PID of the instance holding the name if this one does
+        /// not, where it is known: the holder of a `Backend::FileLock` or
+        /// `Backend::Systemd` lock records itself in the file, see
+        /// `read_lock_info`, unless the file has other contents or it may not
+        /// write to it, in which case `F_GETLK` reports the holder of a
+        /// classic `fcntl` lock, as taken by default on solaris and aix.
+        /// `flock` locks belong to no process and tell nothing. The holder of
+        /// an abstract socket is looked up in `/proc`, which only tells
+        /// processes of the same user.
+        pub fn holder_pid(&self) -> Option<u32> {
+            #[cfg(target_os = "linux")]
+            {
+                if let Some(ref holder) = self.holder {
+                    return Some(holder.pid());
+                }
+            }
+            if self.is_single() {
+                return None;
+            }
+            #[cfg(abstract_socket)]
+            {
+                match self.backend? {
+                    Backend::AbstractSocket => return abstract_socket::holder_pid(&self.name),
+                    Backend::Default if self.lock_path().is_none() => return abstract_socket::holder_pid(&self.name),
+                    _ => {}
+                }
+            }
+            let path = self.lock_path()?;
+            holder::read(&path)
+                .map(|info| info.pid)
+                .or_else(|| file_lock::holder_pid(&path))
+        }
+
+        /// Waits up to `timeout`, or as long as it takes without one, for the
+        /// instance holding the name to exit, returning whether it did. Fails
+        /// if that instance is not known, see `holder_pid`.
+        ///
+        /// Where pidfds are supported, since linux 5.3, the holder is waited
+        /// for through one, otherwise `/proc` is polled. On macos, ios and the
+        /// BSDs kqueue reports its exit with `NOTE_EXIT`.
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        pub fn wait_for_exit(&self, timeout: Option<Duration>) -> Result<bool> {
+            let unknown = || io::Error::new(io::ErrorKind::NotFound, "the holder of the name is not known");
+            #[cfg(target_os = "linux")]
+            let exited = match self.holder {
+                Some(ref holder) => holder.wait(timeout)?,
+                None => match Holder::open(self.holder_pid().ok_or_else(unknown)?) {
+                    Some(holder) => holder.wait(timeout)?,
+                    // The holder exited meanwhile.
+                    None => true,
+                },
+            };
+            #[cfg(not(target_os = "linux"))]
+            let exited = ::kqueue::wait_for_exit(self.holder_pid().ok_or_else(unknown)?, timeout)?;
+            Ok(exited)
+        }
+
+        /// Returns whether the previous holder of the name exited without
+        /// releasing it, which only `Backend::RobustMutex` can tell.
+        #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+        pub fn owner_died(&self) -> bool {
+            match self.lock {
+                Some(Lock::RobustMutex(ref mutex)) => mutex.owner_died(),
+                _ => false,
+            }
+        }
+    }
+
+    /// The descriptor of the lock held: the locked file, the bound socket or
+    /// the shared memory of a robust mutex, e.g. to poll it or to write more
+    /// into the lock file. Backends holding no descriptor, such as
+    /// semaphores, and instances not holding the name return `-1`, which is
+    /// never a valid descriptor.
+    impl AsRawFd for SingleInstance {
+        fn as_raw_fd(&self) -> RawFd {
+            match self.lock {
+                Some(Lock::File(ref lock)) => lock.as_raw_fd(),
+                #[cfg(abstract_socket)]
+                Some(Lock::AbstractSocket(ref socket)) => socket.as_raw_fd(),
+                #[cfg(all(feature = "robust-mutex", target_os = "linux"))]
+                Some(Lock::RobustMutex(ref mutex)) => mutex.as_raw_fd(),
+                #[cfg(feature = "tcp-port")]
+                Some(Lock::TcpPort(ref listener)) => listener.as_raw_fd(),
+                #[cfg(feature = "unix-socket")]
+                Some(Lock::UnixSocket(ref socket)) => socket.listener.as_raw_fd(),
+                _ => -1,
+            }
+        }
+    }
+
+    /// Returns the path of the lock file for `name`.
+    #[cfg(target_os = "android")]
+    fn lock_path(name: &str) -> PathBuf {
+        ::android::lock_path(name).unwrap_or_else(|| PathBuf::from(name))
+    }
+
+    /// Returns the path of the lock file for `name`.
+    #[cfg(target_os = "ios")]
+    fn lock_path(name: &str) -> PathBuf {
+        ::ios::lock_path(name)
+    }
+
+    /// Returns the path of the lock file for `name`: `name` itself if it
+    /// contains a path separator, otherwise the file of that name in
+    /// `XDG_RUNTIME_DIR`, or else in the temporary directory, which other
+    /// users may share, suffixed with the user's ID.
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    fn lock_path(name: &str) -> PathBuf {
+        if name.contains('/') {
+            return PathBuf::from(name);
+        }
+        match env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).filter(|dir| dir.is_absolute()) {
+            Some(dir) => dir.join(name),
+            None => {
+                let file = format!("{}-{}", name, unsafe { libc::getuid() });
+                let file = names::file(&file).map(Cow::into_owned).unwrap_or(file);
+                env::temp_dir().join(file)
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "wasi")]
+mod inner {
+    use backend::{Backend, Custom, CustomBackend};
+    use error::Result;
+    use std::path::PathBuf;
+    use wasi_file::{self, LockFile};
+
+    /// A struct representing one running instance.
+    pub struct SingleInstance {
+        /// The lock file, while held.
+        file: Option<LockFile>,
+        /// The path of the lock file of `Backend::Default`.
+        path: Option<PathBuf>,
+        custom: Option<Custom>,
+        backend: Option<Backend>,
+        name: String,
+    }
+
+    impl SingleInstance {
+        /// Returns a new SingleInstance object.
+        pub fn new(name: &str) -> Result<Self> {
+            Self::with_backend(name, Backend::Default)
+        }
+
+        /// Returns a new SingleInstance object claiming `name` with `backend`.
+        pub fn with_backend(name: &str, backend: Backend) -> Result<Self> {
+            match backend {
+                Backend::Default => Self::create_file(name),
+            }
+        }
+
+        fn create_file(name: &str) -> Result<Self> {
+            // WASI has no advisory locks, the lock file existing is the lock.
+            let path = wasi_file::lock_path(name)?;
+            Ok(Self {
+                file: wasi_file::try_create(&path)?,
+                path: Some(path),
+                custom: None,
+                backend: Some(Backend::Default),
+                name: name.to_owned(),
+            })
+        }
+
+        /// Returns a new SingleInstance object claiming `name` with a backend
+        /// supplied by the application.
+        pub fn with_custom_backend(name: &str, backend: Box<dyn CustomBackend>) -> Result<Self> {
+            Ok(Self {
+                file: None,
+                path: None,
+                custom: Custom::acquire(backend, name)?,
+                backend: None,
+                name: name.to_owned(),
+            })
+        }
+
+        /// Returns a new SingleInstance object taking one of `limit` slots
+        /// for `name`, so that at most `limit` instances are single at once.
+        ///
+        /// Each slot is a file next to the one for `name`, with the slot
+        /// number appended, and the first one missing or stale is created.
+        pub fn with_limit(name: &str, limit: usize) -> Result<Self> {
+            let path = wasi_file::lock_path(name)?;
+            for slot in 0..limit {
+                let mut slot_path = path.clone().into_os_string();
+                slot_path.push(format!(".{}", slot));
+                if let Some(file) = wasi_file::try_create(slot_path.as_ref())? {
+                    return Ok(Self {
+                        file: Some(file),
+                        path: None,
+                        custom: None,
+                        backend: None,
+                        name: name.to_owned(),
+                    });
+                }
+            }
+            Ok(Self {
+                file: None,
+                path: None,
+                custom: None,
+                backend: None,
+                name: name.to_owned(),
+            })
+        }
+
+        /// Returns whether this instance is single.
+        pub fn is_single(&self) -> bool {
+            self.file.is_some() || self.custom.as_ref().is_some_and(Custom::is_held)
+        }
+
+        /// Releases the name as dropping this instance would, removing the
+        /// lock file, so that `is_single` returns `false` from now on and
+        /// another instance can claim it. Does nothing if the name is not
+        /// held.
+        pub fn release(&mut self) {
+            self.file = None;
+            self.custom = None;
+        }
+
+        /// Returns the backend this instance was created with, which for
+        /// `SingleInstance::with_backends` is the first one that worked, or
+        /// `None` for a custom backend or a limit.
+        pub fn backend(&self) -> Option<Backend> {
+            self.backend
+        }
+
+        /// Returns the name this instance was created with.
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Returns the path of the lock file, which for a limit is the one of
+        /// the slot taken, or `None` for a custom backend or while no slot is
+        /// held.
+        pub fn lock_path(&self) -> Option<PathBuf> {
+            match self.file {
+                Some(ref file) if self.path.is_none() => Some(file.path().to_owned()),
+                _ => self.path.clone(),
+            }
+        }
+    }
+
+    impl Drop for SingleInstance {
+        fn drop(&mut self) {
+            self.release();
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod inner {
+    use backend::{Backend, Custom, CustomBackend};
+    use error::{Result, SingleInstanceError};
+    #[cfg(feature = "wasm")]
+    use std::future::Future;
+    #[cfg(feature = "wasm")]
+    use std::pin::Pin;
+    #[cfg(feature = "wasm")]
+    use std::task::{Context, Poll};
+    #[cfg(feature = "wasm")]
+    use web_locks::{self, WebLock};
+
+    /// A struct representing one running instance.
+    pub struct SingleInstance {
+        #[cfg(feature = "wasm")]
+        lock: Option<WebLock>,
+        custom: Option<Custom>,
+        backend: Option<Backend>,
+        name: String,
+    }
+
+    /// A future resolving to an instance claiming a name, see
+    /// `SingleInstance::new_async`.
+    #[cfg(feature = "wasm")]
+    struct Claim {
+        request: web_locks::Request,
+        name: String,
+    }
+
+    impl SingleInstance {
+        /// Fails with `SingleInstanceError::Asynchronous`, as names can only
+        /// be claimed asynchronously in the browser, see
+        /// `SingleInstance::new_async`.
+        pub fn new(name: &str) -> Result<Self> {
+            Self::with_backend(name, Backend::Default)
+        }
+
+        /// Fails with `SingleInstanceError::Asynchronous` like
+        /// `SingleInstance::new`.
+        pub fn with_backend(_name: &str, backend: Backend) -> Result<Self> {
+            match backend {
+                Backend::Default => Err(SingleInstanceError::Asynchronous),
+            }
+        }
+
+        /// Returns a future resolving to a new SingleInstance object claiming
+        /// `name` with a lock of the Web Locks API, which is shared by the
+        /// tabs and workers of an origin. Fails with
+        /// `SingleInstanceError::WebLocks` where the API is missing, e.g.
+        /// outside a secure context.
+        #[cfg(feature = "wasm")]
+        pub fn new_async(name: &str) -> impl Future<Output = Result<Self>> {
+            Claim {
+                request: web_locks::request(name),
+                name: name.to_owned(),
+            }
+        }
+
+        /// Returns a new SingleInstance object claiming `name` with a backend
+        /// supplied by the application.
+        pub fn with_custom_backend(name: &str, backend: Box<dyn CustomBackend>) -> Result<Self> {
+            Ok(Self {
+                #[cfg(feature = "wasm")]
+                lock: None,
+                custom: Custom::acquire(backend, name)?,
+                backend: None,
+                name: name.to_owned(),
+            })
+        }
+
+        /// Fails with `SingleInstanceError::Asynchronous` like
+        /// `SingleInstance::new`.
+        pub fn with_limit(_name: &str, _limit: usize) -> Result<Self> {
+            Err(SingleInstanceError::Asynchronous)
+        }
+
+        /// Returns whether this instance is single.
+        pub fn is_single(&self) -> bool {
+            #[cfg(feature = "wasm")]
+            {
+                if self.lock.is_some() {
+                    return true;
+                }
+            }
+            self.custom.as_ref().is_some_and(Custom::is_held)
+        }
+
+        /// Releases the name as dropping this instance would, so that
+        /// `is_single` returns `false` from now on and another instance can
+        /// claim it. Does nothing if the name is not held.
+        pub fn release(&mut self) {
+            #[cfg(feature = "wasm")]
+            {
+                self.lock = None;
+            }
+            self.custom = None;
+        }
+
+        /// Returns the backend this instance was created with, which for
+        /// `SingleInstance::with_backends` is the first one that worked, or
+        /// `None` for a custom backend or a limit.
+        pub fn backend(&self) -> Option<Backend> {
+            self.backend
+        }
+
+        /// Returns the name this instance was created with.
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+    }
+
+    #[cfg(feature = "wasm")]
+    impl Future for Claim {
+        type Output = Result<SingleInstance>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let lock = match Pin::new(&mut self.request).poll(cx) {
+                Poll::Ready(lock) => lock?,
+                Poll::Pending => return Poll::Pending,
+            };
+            Poll::Ready(Ok(SingleInstance {
+                lock,
+                custom: None,
+                backend: Some(Backend::Default),
+                name: std::mem::take(&mut self.name),
+            }))
+        }
+    }
+
+    impl Drop for SingleInstance {
+        fn drop(&mut self) {
+            self.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    static UNIQ_ID: &str = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b";
+
+    /// Removes the file `Backend::FileLock` locked for `name`.
+    #[cfg(unix)]
+    fn remove_lock_file(name: &str) {
+        let path = SingleInstance::with_backend(name, Backend::FileLock).unwrap().lock_path().unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_single_instance() {
+        {
+            let instance_a = SingleInstance::new(UNIQ_ID).unwrap();
+            assert!(instance_a.is_single());
+            assert_eq!(instance_a.backend(), Some(Backend::Default));
+            assert_eq!(instance_a.name(), UNIQ_ID);
+            #[cfg(target_os = "windows")]
+            assert_eq!(instance_a.object_name(), Some(format!("Local\\{}", UNIQ_ID)));
+            let instance_b = SingleInstance::new(UNIQ_ID).unwrap();
+            assert!(!instance_b.is_single());
+            #[cfg(target_os = "windows")]
+            assert_eq!(instance_b.holder_pid(), Some(std::process::id()));
+        }
+        let instance_c = SingleInstance::new(UNIQ_ID).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(abstract_socket, target_os = "linux"))]
+    #[test]
+    fn test_single_instance_with_backends() {
+        // Locking a file in a missing directory fails, binding the name as
+        // an abstract socket does not.
+        let name = "/aa2d0258-ffe9-11e7-ba89-0ed5f89f718b/missing";
+        let backends = [Backend::FileLock, Backend::AbstractSocket];
+        {
+            let instance_a = SingleInstance::with_backends(name, &backends).unwrap();
+            assert!(instance_a.is_single());
+            assert_eq!(instance_a.backend(), Some(Backend::AbstractSocket));
+            let instance_b = SingleInstance::with_backends(name, &backends).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backends(name, &backends).unwrap();
+        assert!(instance_c.is_single());
+        assert!(SingleInstance::with_backends(name, &[Backend::FileLock]).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_single_instance_with_limit() {
+        let path = std::env::temp_dir().join("aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-limit");
+        let name = path.to_str().unwrap();
+        let instance_a = SingleInstance::with_limit(name, 2).unwrap();
+        assert!(instance_a.is_single());
+        assert_eq!(instance_a.backend(), None);
+        let instance_b = SingleInstance::with_limit(name, 2).unwrap();
+        assert!(instance_b.is_single());
+        let instance_c = SingleInstance::with_limit(name, 2).unwrap();
+        assert!(!instance_c.is_single());
+        drop(instance_a);
+        let instance_d = SingleInstance::with_limit(name, 2).unwrap();
+        assert!(instance_d.is_single());
+        assert!(!SingleInstance::with_limit(name, 0).unwrap().is_single());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_on_second_instance() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-second";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        instance_a.on_second_instance(move |second| sender.send(second).unwrap()).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!instance_b.is_single());
+        assert!(instance_b.on_second_instance(|_| {}).is_err());
+        let second = receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(second.pid, std::process::id());
+        assert_eq!(second.cwd, std::env::current_dir().unwrap());
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stalled_sender() {
+        use std::io::Write;
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::{SocketAddr, UnixStream};
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-stalled";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        instance_a.on_second_instance(move |second| sender.send(second).unwrap()).unwrap();
+        // A sender that never finishes its message holds up no other one.
+        let addr = SocketAddr::from_abstract_name(&notify::address(instance_a.name())[1..]).unwrap();
+        let mut stalled = UnixStream::connect_addr(&addr).unwrap();
+        stalled.write_all(&[notify::PROTOCOL_VERSION]).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!instance_b.is_single());
+        receiver.recv_timeout(std::time::Duration::from_secs(2)).unwrap();
+        drop(stalled);
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[test]
+    fn test_single_instance_release() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-release";
+        let mut instance_a = SingleInstance::new(name).unwrap();
+        assert!(instance_a.is_single());
+        instance_a.release();
+        assert!(!instance_a.is_single());
+        instance_a.release();
+        let instance_b = SingleInstance::new(name).unwrap();
+        assert!(instance_b.is_single());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_try_reacquire() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-reacquire";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        instance_a.on_second_instance(move |second| sender.send(second).unwrap()).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert!(!instance_b.try_reacquire().unwrap());
+        instance_a.release();
+        assert!(instance_b.try_reacquire().unwrap());
+        assert!(!instance_a.try_reacquire().unwrap());
+        drop(instance_b);
+        assert!(instance_a.try_reacquire().unwrap());
+        let instance_c = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!instance_c.is_single());
+        receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        drop(instance_c);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_wait_until_single() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-wait-until-single";
+        let instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!instance_b.wait_until_single(Some(std::time::Duration::from_millis(50))).unwrap());
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            drop(instance_a);
+        });
+        assert!(instance_b.wait_until_single(None).unwrap());
+        assert!(instance_b.is_single());
+        release.join().unwrap();
+        drop(instance_b);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_wait_until_single_cancelled() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-wait-cancelled";
+        let instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let cancel = std::sync::Arc::new(cancel::Cancel::new());
+        let cancelling = cancel.clone();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            cancelling.cancel();
+        });
+        assert!(!instance_b.wait_until_single_unless(None, &cancel).unwrap());
+        assert!(!instance_b.is_single());
+        canceller.join().unwrap();
+        // A name claimed once cancelled is released again.
+        drop(instance_a);
+        assert!(!instance_b.wait_until_single_unless(None, &cancel).unwrap());
+        assert!(!instance_b.is_single());
+        drop(instance_b);
+        remove_lock_file(name);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_wait_on_thread_given_up() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-wait-on-thread";
+        let instance = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let path = instance.lock_path().unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(50);
+        let cancel = std::sync::Arc::new(cancel::Cancel::new());
+        let waiting = path.clone();
+        let lock = cancel::wait_on_thread(move || file_lock::wait_lock(&waiting), Some(deadline), &cancel);
+        assert!(lock.is_none());
+        // The wait left behind releases the lock it takes once the name is.
+        drop(instance);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while file_lock::try_lock(&path).unwrap().is_none() {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        remove_lock_file(name);
+    }
+
+    #[test]
+    fn test_single_instance_into_guard() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-guard";
+        let guard = SingleInstance::new(name).unwrap().into_guard().ok().unwrap();
+        let instance_b = SingleInstance::new(name).unwrap().into_guard().err().unwrap();
+        assert!(!instance_b.is_single());
+        let guard = std::thread::spawn(move || guard.into_inner()).join().unwrap();
+        assert!(guard.is_single());
+    }
+
+    #[test]
+    fn test_run_if_single() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-run-if-single";
+        let nested = run_if_single(name, || run_if_single(name, || ()).unwrap()).unwrap();
+        assert_eq!(nested, Some(None));
+        let panicked = std::panic::catch_unwind(|| run_if_single(name, || panic!("app failed")));
+        assert!(panicked.is_err());
+        assert_eq!(run_if_single(name, || 1).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_single_instance_acquire() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-acquire";
+        let instance_a = match SingleInstance::acquire(name).unwrap() {
+            AcquireOutcome::Acquired(instance) => instance,
+            AcquireOutcome::AlreadyRunning { .. } => panic!("expected Acquired"),
+        };
+        assert!(instance_a.is_single());
+        match SingleInstance::acquire(name).unwrap() {
+            AcquireOutcome::AlreadyRunning { instance, .. } => assert!(!instance.is_single()),
+            AcquireOutcome::Acquired(_) => panic!("expected AlreadyRunning"),
+        }
+    }
+
+    #[test]
+    fn test_single_instance_try_acquire() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-try-acquire";
+        let instance_a = SingleInstance::try_acquire(name).unwrap();
+        assert!(instance_a.is_single());
+        match SingleInstance::try_acquire(name) {
+            Err(error::SingleInstanceError::AlreadyRunning { .. }) => {}
+            Err(e) => panic!("expected AlreadyRunning, got {:?}", e),
+            Ok(_) => panic!("expected AlreadyRunning"),
+        }
+        drop(instance_a);
+        assert!(SingleInstance::try_acquire(name).unwrap().is_single());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_builder() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-builder";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let instance_a = SingleInstance::builder(name)
+            .backend(Backend::FileLock)
+            .on_second_instance(move |second| sender.send(second).unwrap())
+            .build()
+            .unwrap();
+        assert!(instance_a.is_single());
+        assert_eq!(instance_a.backend(), Some(Backend::FileLock));
+        let instance_b = SingleInstance::builder(name)
+            .backend(Backend::FileLock)
+            .on_second_instance(|_| panic!("not single"))
+            .build()
+            .unwrap();
+        assert!(!instance_b.is_single());
+        let second = receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(second.pid, std::process::id());
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_activate() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-activate";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_a.activate().is_err());
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!instance_b.activate().unwrap());
+        instance_a.on_activate(move |second| sender.send(second).unwrap()).unwrap();
+        assert!(instance_b.activate().unwrap());
+        let second = receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(second.pid, std::process::id());
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(all(feature = "x11", target_os = "linux"))]
+    #[test]
+    fn test_single_instance_set_x11_window() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-x11";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_b.set_x11_window(1).is_err());
+        // The handler is called whether or not there is an X server to raise
+        // the window on.
+        instance_a.set_x11_window(1).unwrap();
+        instance_a.on_activate(move |second| sender.send(second).unwrap()).unwrap();
+        assert!(instance_b.activate().unwrap());
+        receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_activation_token() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-token";
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        instance_a.on_activate(move |second| sender.send(second).unwrap()).unwrap();
+        assert!(instance_b.activate_with_token("token-1").unwrap());
+        let second = receiver.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert_eq!(second.activation_token.as_deref(), Some("token-1"));
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_send() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-send";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_a.send(b"ping").is_err());
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_b.send(b"ping").unwrap(), None);
+        let messages = instance_a.messages().unwrap();
+        let holder = std::thread::spawn(move || {
+            let message = messages.recv().unwrap();
+            assert_eq!(message.sender.pid, std::process::id());
+            assert_eq!(message.data, b"ping");
+            message.reply(b"pong").unwrap();
+            drop(messages.recv().unwrap());
+        });
+        assert_eq!(instance_b.send(b"ping").unwrap(), Some(b"pong".to_vec()));
+        assert_eq!(instance_b.send(b"").unwrap(), Some(Vec::new()));
+        holder.join().unwrap();
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_send_and_wait_reply() {
+        use std::time::Duration;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-request";
+        let timeout = Duration::from_secs(5);
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_a.send_and_wait_reply(b"open", timeout).is_err());
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_b.send_and_wait_reply(b"open", timeout).unwrap(), None);
+        // Listening without a request handler drops requests unanswered.
+        let _messages = instance_a.messages().unwrap();
+        assert_eq!(instance_b.send_and_wait_reply(b"open", timeout).unwrap(), None);
+        instance_a
+            .on_request(|sender, data| {
+                assert_eq!(sender.pid, std::process::id());
+                if data == b"slow" {
+                    std::thread::sleep(Duration::from_millis(500));
+                }
+                [&b"opened "[..], &data].concat()
+            })
+            .unwrap();
+        assert_eq!(
+            instance_b.send_and_wait_reply(b"a.txt", timeout).unwrap(),
+            Some(b"opened a.txt".to_vec())
+        );
+        assert_eq!(instance_b.send_and_wait_reply(b"", timeout).unwrap(), Some(b"opened ".to_vec()));
+        match instance_b.send_and_wait_reply(b"slow", Duration::from_millis(50)) {
+            Err(error::SingleInstanceError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_broadcast() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-broadcast";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_a.subscribe().is_err());
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_b.broadcast(b"event").is_err());
+        assert!(instance_b.subscribe().unwrap().is_none());
+        assert_eq!(instance_a.broadcast(b"event").unwrap(), 0);
+        let first = instance_b.subscribe().unwrap().unwrap();
+        let second = instance_b.subscribe().unwrap().unwrap();
+        assert_eq!(instance_a.broadcast(b"event").unwrap(), 2);
+        assert_eq!(instance_a.broadcast(b"").unwrap(), 2);
+        assert_eq!(first.recv().unwrap(), b"event");
+        assert_eq!(first.recv().unwrap(), b"");
+        assert_eq!(second.recv().unwrap(), b"event");
+        drop(instance_a);
+        assert_eq!(second.recv().unwrap(), b"");
+        assert!(second.recv().is_err());
+        drop(instance_b);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_forward_urls() {
+        use std::sync::mpsc;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-urls";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_a.forward_urls(["app://open"]).unwrap(), ForwardResult::Single);
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_b.forward_urls(["app://open"]).unwrap(), ForwardResult::NotDelivered);
+        let (sender, receiver) = mpsc::channel();
+        instance_a.on_urls(move |_, urls| sender.send(urls).unwrap()).unwrap();
+        let urls = vec![String::from("app://open?file=a.txt"), String::new()];
+        assert_eq!(instance_b.forward_urls(&urls).unwrap(), ForwardResult::Delivered);
+        assert_eq!(receiver.try_recv().unwrap(), urls);
+        assert_eq!(instance_b.forward_urls(Vec::<String>::new()).unwrap(), ForwardResult::Delivered);
+        assert_eq!(receiver.try_recv().unwrap(), Vec::<String>::new());
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_forward_open() {
+        use std::sync::mpsc;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-open";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_a.forward_open(["Cargo.toml"]).unwrap(), ForwardResult::Single);
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_b.forward_open(["Cargo.toml"]).unwrap(), ForwardResult::NotDelivered);
+        let (sender, receiver) = mpsc::channel();
+        instance_a.on_open(move |_, paths| sender.send(paths).unwrap()).unwrap();
+        assert_eq!(instance_b.forward_open(["Cargo.toml", "missing.txt"]).unwrap(), ForwardResult::Delivered);
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            vec![std::fs::canonicalize("Cargo.toml").unwrap(), cwd.join("missing.txt")]
+        );
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_forward_env() {
+        use std::ffi::OsString;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-env";
+        // Set by cargo for the tests, which must not change the environment
+        // other tests run in.
+        let value = std::env::var_os("CARGO_PKG_NAME").unwrap();
+        SingleInstance::forward_env(["CARGO_PKG_NAME", "SINGLE_INSTANCE_TEST_UNSET"]);
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let messages = instance_a.messages().unwrap();
+        let holder = std::thread::spawn(move || {
+            let message = messages.recv().unwrap();
+            assert_eq!(message.sender.cwd, std::env::current_dir().unwrap());
+            assert_eq!(message.sender.env, vec![(OsString::from("CARGO_PKG_NAME"), value)]);
+        });
+        instance_b.send(b"open").unwrap();
+        holder.join().unwrap();
+        SingleInstance::forward_env(Vec::<String>::new());
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_listen() {
+        use std::sync::mpsc;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-listen";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(SingleInstance::with_backend(name, Backend::FileLock).unwrap().listen(|_| {}).is_err());
+        let (sender, receiver) = mpsc::channel();
+        instance_a
+            .listen(move |message| {
+                sender.send(message.data.clone()).unwrap();
+                message.reply(b"pong").unwrap();
+            })
+            .unwrap();
+        assert_eq!(instance_b.send(b"ping").unwrap(), Some(b"pong".to_vec()));
+        assert_eq!(receiver.recv().unwrap(), b"ping");
+        // The thread stops, dropping the handler, once the holder is dropped.
+        drop(instance_a);
+        assert!(receiver.recv().is_err());
+        drop(instance_b);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_poll_message() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-poll";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_a.poll_message().unwrap().is_none());
+        let sender = std::thread::spawn(move || instance_b.send(b"frame").unwrap());
+        let message = loop {
+            if let Some(message) = instance_a.poll_message().unwrap() {
+                break message;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+        assert_eq!(message.data, b"frame");
+        message.reply(b"done").unwrap();
+        assert_eq!(sender.join().unwrap(), Some(b"done".to_vec()));
+        assert!(instance_a.poll_message().unwrap().is_none());
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    /// Polls `future` to completion on this thread, for tests without a
+    /// runtime.
+    #[cfg(all(feature = "async", unix))]
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Unpark(std::thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[cfg(all(feature = "async", unix))]
+    #[test]
+    fn test_single_instance_acquire_async() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-acquire-async";
+        let instance_a = match block_on(SingleInstance::acquire_async(name)).unwrap() {
+            AcquireOutcome::Acquired(instance) => instance,
+            AcquireOutcome::AlreadyRunning { .. } => panic!("expected Acquired"),
+        };
+        let instance_b = match block_on(SingleInstance::acquire_async(name)).unwrap() {
+            AcquireOutcome::AlreadyRunning { instance, .. } => instance,
+            AcquireOutcome::Acquired(_) => panic!("expected AlreadyRunning"),
+        };
+        drop(instance_b.wait_until_single_async(None));
+        let instance_b = SingleInstance::new(name).unwrap();
+        let waiting = instance_b.wait_until_single_async(None);
+        drop(instance_a);
+        let (instance_b, result) = block_on(waiting);
+        assert!(result.unwrap());
+        assert!(instance_b.is_single());
+    }
+
+    #[cfg(all(feature = "tokio", unix))]
+    #[test]
+    fn test_single_instance_async_messages() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-tokio";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let mut messages = instance_a.async_messages().unwrap();
+        let sender = std::thread::spawn(move || instance_b.send(b"ping").unwrap());
+        let message = messages.blocking_recv().unwrap();
+        assert_eq!(message.data, b"ping");
+        message.reply(b"pong").unwrap();
+        assert_eq!(sender.join().unwrap(), Some(b"pong".to_vec()));
+        drop(instance_a);
+        assert!(messages.blocking_recv().is_none());
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_allow_other_users() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-users";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_b.allow_other_users(true).is_err());
+        let messages = instance_a.messages().unwrap();
+        for allow in [true, false] {
+            instance_a.allow_other_users(allow).unwrap();
+            let sender = std::thread::spawn(move || instance_b.send(b"ping").unwrap());
+            // The same user is listened to either way.
+            assert_eq!(messages.recv().unwrap().data, b"ping");
+            assert_eq!(sender.join().unwrap(), Some(Vec::new()));
+            // Other users only find the holder while they are allowed.
+            #[cfg(target_os = "linux")]
+            {
+                use std::os::linux::net::SocketAddrExt;
+                let shared = format!("single-instance-{:08x}", fnv::hash(name));
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(&shared).unwrap();
+                assert_eq!(std::os::unix::net::UnixStream::connect_addr(&addr).is_ok(), allow);
+                assert!(notify::address(name).contains(&format!("-{}-", unsafe { libc::geteuid() })));
+            }
+            instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        }
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_max_message_size() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-size";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        instance_a.set_max_message_size(1024).unwrap();
+        let messages = instance_a.messages().unwrap();
+        match instance_b.send(&[0; 8192]) {
+            Err(error::SingleInstanceError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput),
+            other => panic!("expected the message to be rejected, got {:?}", other),
+        }
+        assert!(messages.try_recv().is_err());
+        let sender = std::thread::spawn(move || instance_b.send(b"ping").unwrap());
+        assert_eq!(messages.recv().unwrap().data, b"ping");
+        assert_eq!(sender.join().unwrap(), Some(Vec::new()));
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_single_instance_protocol_version() {
+        use std::io::{Read, Write};
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::{SocketAddr, UnixStream};
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-version";
+        let mut instance = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let messages = instance.messages().unwrap();
+        let address = notify::address(name);
+        let addr = SocketAddr::from_abstract_name(address.trim_start_matches('@')).unwrap();
+        let mut stream = UnixStream::connect_addr(&addr).unwrap();
+        // A message of a later version, which this one cannot read.
+        stream.write_all(&[2, 2, 0, 0, 0, 0]).unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut answer = Vec::new();
+        stream.read_to_end(&mut answer).unwrap();
+        assert_eq!(answer, [1, 1]);
+        assert!(messages.try_recv().is_err());
+        drop(instance);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_attempt_count() {
+        use std::sync::mpsc;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-attempts";
+        let mut instance = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        instance.on_second_instance(move |_| sender.send(()).unwrap()).unwrap();
+        assert_eq!(instance.attempt_count().unwrap(), 0);
+        for _ in 0..3 {
+            let mut other = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+            assert!(other.attempt_count().is_err());
+            receiver.recv().unwrap();
+        }
+        assert_eq!(instance.attempt_count().unwrap(), 3);
+        drop(instance);
+        remove_lock_file(name);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    static UNIQ_ID : &'static str   = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b";
+    #[cfg(unix)]
     #[test]
-    fn test_single_instance() {
+    fn test_single_instance_send_fds() {
+        use std::io::{Read, Write};
+        use std::os::unix::io::AsFd;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-fds";
+        let path = std::env::temp_dir().join("aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-passed");
+        std::fs::File::create(&path).unwrap().write_all(b"passed").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let messages = instance_a.messages().unwrap();
+        let holder = std::thread::spawn(move || {
+            let mut message = messages.recv().unwrap();
+            assert_eq!(message.data, b"open");
+            assert_eq!(message.fds.len(), 1);
+            let mut contents = String::new();
+            std::fs::File::from(message.fds.remove(0)).read_to_string(&mut contents).unwrap();
+            message.reply(contents.as_bytes()).unwrap();
+        });
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let reply = instance_b.send_fds(b"open", &[file.as_fd()]).unwrap();
+        assert_eq!(reply, Some(b"passed".to_vec()));
+        holder.join().unwrap();
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(all(feature = "serde", unix))]
+    #[test]
+    fn test_single_instance_typed_channel() {
+        use typed::TypedChannel;
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-typed";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!TypedChannel::send(&instance_b, &(String::from("open"), 1)).unwrap());
+        let channel = TypedChannel::<(String, u32)>::new(&mut instance_a).unwrap();
+        // Sending blocks until the holder received the value.
+        let sender = std::thread::spawn(move || {
+            assert!(TypedChannel::send(&instance_b, &(String::from("open"), 1)).unwrap());
+            instance_b.send(b"not json").unwrap();
+        });
+        let (second, value) = channel.recv().unwrap().unwrap();
+        assert_eq!(second.pid, std::process::id());
+        assert_eq!(value, (String::from("open"), 1));
+        assert!(channel.recv().unwrap().is_err());
+        sender.join().unwrap();
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(all(feature = "msgpack", unix))]
+    #[test]
+    fn test_single_instance_typed_channel_msgpack() {
+        use typed::{Codec, TypedChannel};
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-msgpack";
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let channel = TypedChannel::<(String, u32)>::with_codec(&mut instance_a, Codec::MessagePack).unwrap();
+        let sender = std::thread::spawn(move || {
+            let value = (String::from("open"), 1);
+            assert!(TypedChannel::send_with_codec(&instance_b, &value, Codec::MessagePack).unwrap());
+            assert!(TypedChannel::send(&instance_b, &value).unwrap());
+        });
+        let (_, value) = channel.recv().unwrap().unwrap();
+        assert_eq!(value, (String::from("open"), 1));
+        // JSON is not valid MessagePack for this type.
+        assert!(channel.recv().unwrap().is_err());
+        sender.join().unwrap();
+        drop(instance_a);
+        remove_lock_file(name);
+    }
+
+    #[cfg(all(feature = "serde", target_os = "linux"))]
+    #[test]
+    fn test_single_instance_json_lines() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::{SocketAddr, UnixStream};
+
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-json";
+        let mut instance = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let lines = instance.json_lines().unwrap();
+        let address = json_lines::address(name);
+        let client = std::thread::spawn(move || {
+            let addr = SocketAddr::from_abstract_name(address.trim_start_matches('@')).unwrap();
+            let mut stream = UnixStream::connect_addr(&addr).unwrap();
+            // Another process's ID is refused.
+            stream.write_all(b"{\"pid\":42}\n").unwrap();
+            let line = format!("{{\"pid\":{},\"data\":[\"open\",\"a.txt\"]}}\n[]\n", std::process::id());
+            stream.write_all(line.as_bytes()).unwrap();
+            let mut answers = BufReader::new(stream).lines();
+            let answer = answers.next().unwrap().unwrap();
+            assert!(answer.starts_with(r#"{"error":"#));
+            let answer = answers.next().unwrap().unwrap();
+            assert_eq!(answer, r#"{"reply":"opened"}"#);
+            let answer = answers.next().unwrap().unwrap();
+            assert!(answer.starts_with(r#"{"error":"#));
+        });
+        let line = lines.recv().unwrap();
+        assert_eq!(line.sender.pid, std::process::id());
+        assert_eq!(line.data, serde_json::json!(["open", "a.txt"]));
+        line.reply(serde_json::json!("opened"));
+        client.join().unwrap();
+        drop(instance);
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_request_takeover() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-takeover";
+        let timeout = Some(std::time::Duration::from_secs(5));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(instance_a.request_takeover(timeout).is_err());
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert!(!instance_b.request_takeover(timeout).unwrap());
+        instance_a.on_takeover(move |second| sender.send(second).unwrap()).unwrap();
+        let taker = std::thread::spawn(move || {
+            assert!(instance_b.request_takeover(timeout).unwrap());
+            assert!(instance_b.is_single());
+        });
+        assert_eq!(receiver.recv().unwrap().pid, std::process::id());
+        drop(instance_a);
+        taker.join().unwrap();
+        remove_lock_file(name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_single_instance_request_handover() {
+        let name = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-handover";
+        let timeout = Some(std::time::Duration::from_secs(5));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut instance_a = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        let mut instance_b = SingleInstance::with_backend(name, Backend::FileLock).unwrap();
+        assert_eq!(instance_b.request_handover(timeout).unwrap(), None);
+        instance_a.on_handover(|_| b"state".to_vec()).unwrap();
+        instance_a.on_takeover(move |_| sender.send(()).unwrap()).unwrap();
+        let taker = std::thread::spawn(move || {
+            assert_eq!(instance_b.request_handover(timeout).unwrap(), Some(b"state".to_vec()));
+            assert!(instance_b.is_single());
+        });
+        receiver.recv().unwrap();
+        drop(instance_a);
+        taker.join().unwrap();
+        remove_lock_file(name);
+    }
+
+    /// Claims names in a shared flag, standing in for a lock service.
+    struct FlagBackend {
+        flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
+        held: bool,
+    }
+
+    impl CustomBackend for FlagBackend {
+        fn acquire(&mut self, _name: &str) -> std::result::Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+            self.held = !self.flag.swap(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(self.held)
+        }
+
+        fn is_held(&self) -> bool {
+            self.held
+        }
+
+        fn release(&mut self) {
+            self.flag.store(false, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_single_instance_with_custom_backend() {
+        let flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let backend = || {
+            Box::new(FlagBackend {
+                flag: flag.clone(),
+                held: false,
+            })
+        };
         {
-            let instance_a = SingleInstance::new(UNIQ_ID).unwrap();
+            let instance_a = SingleInstance::with_custom_backend(UNIQ_ID, backend()).unwrap();
             assert!(instance_a.is_single());
-            let instance_b = SingleInstance::new(UNIQ_ID).unwrap();
+            assert_eq!(instance_a.backend(), None);
+            let instance_b = SingleInstance::with_custom_backend(UNIQ_ID, backend()).unwrap();
             assert!(!instance_b.is_single());
         }
-        let instance_c = SingleInstance::new(UNIQ_ID).unwrap();
+        let instance_c = SingleInstance::with_custom_backend(UNIQ_ID, backend()).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_for_current_exe() {
+        let instance_a = SingleInstance::for_current_exe().unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::for_current_exe().unwrap();
+        assert!(!instance_b.is_single());
+        assert_eq!(instance_a.name(), instance_b.name());
+    }
+
+    #[cfg(all(abstract_socket, target_os = "linux"))]
+    #[test]
+    fn test_sanitized_names() {
+        let long = format!("{}-{}", UNIQ_ID, "x".repeat(300));
+        {
+            let instance_a = SingleInstance::new(&long).unwrap();
+            assert!(instance_a.is_single());
+            assert!(instance_a.name().len() <= 107);
+            let instance_b = SingleInstance::new(&long).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let path = std::env::temp_dir().join(&long);
+        let instance_a = SingleInstance::with_backend(path.to_str().unwrap(), Backend::FileLock).unwrap();
+        assert!(instance_a.is_single());
+        assert!(instance_a.lock_path().unwrap().file_name().unwrap().len() <= 255);
+        let instance_b = SingleInstance::with_backend(path.to_str().unwrap(), Backend::FileLock).unwrap();
+        assert!(!instance_b.is_single());
+        match SingleInstance::new("") {
+            Err(error::SingleInstanceError::InvalidName(_)) => {}
+            _ => panic!("an empty name must be rejected"),
+        }
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_for_app_id() {
+        let app_id = "com.example.aa2d0258-ffe9-11e7-ba89-0ed5f89f718b";
+        let instance_a = SingleInstance::for_app_id(app_id).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::for_app_id(app_id).unwrap();
+        assert!(!instance_b.is_single());
+        #[cfg(target_os = "linux")]
+        assert!(instance_a.lock_path().unwrap().ends_with(format!("{}.lock", app_id)));
+        assert!(SingleInstance::for_app_id("com/example").is_err());
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_with_scope_user() {
+        let name = format!("{}-scope-user", UNIQ_ID);
+        let instance_a = SingleInstance::with_scope(&name, Scope::User).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::builder(&name).scope(Scope::User).build().unwrap();
+        assert!(!instance_b.is_single());
+        assert_eq!(instance_a.name(), Scope::User.apply(&name).unwrap());
+        #[cfg(unix)]
+        assert!(instance_a.name().ends_with(&format!("-{}", unsafe { libc::getuid() })));
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    #[test]
+    fn test_with_scope_machine() {
+        let name = format!("{}-scope-machine", UNIQ_ID);
+        let instance_a = match SingleInstance::with_scope(&name, Scope::Machine) {
+            // The system-wide lock directory may not be writable here.
+            Err(error::SingleInstanceError::PermissionDenied(_)) => return,
+            instance => instance.unwrap(),
+        };
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::with_scope(&name, Scope::Machine).unwrap();
+        assert!(!instance_b.is_single());
+        #[cfg(target_os = "windows")]
+        assert_eq!(instance_a.name(), format!("Global\\{}", name));
+        #[cfg(target_os = "linux")]
+        {
+            assert!(instance_a.name().ends_with(&format!("/{}.lock", name)));
+            let path = instance_a.lock_path().unwrap();
+            assert_eq!(path, std::path::Path::new(instance_a.name()));
+            assert!(path.exists());
+            assert!(SingleInstance::with_scope("a/b", Scope::Machine).is_err());
+            let builder = || SingleInstance::builder(&name).scope(Scope::Machine).build();
+            assert_eq!(builder().unwrap().backend(), Some(Backend::FileLock));
+            assert!(!builder().unwrap().is_single());
+            drop((instance_a, instance_b));
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_with_scope_session() {
+        let name = format!("{}-scope-session", UNIQ_ID);
+        let instance_a = SingleInstance::with_scope(&name, Scope::Session).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::with_scope(&name, Scope::Session).unwrap();
+        assert!(!instance_b.is_single());
+        assert!(instance_a.name().starts_with(&format!("{}-session-", name)));
+        assert_ne!(Scope::Session.apply(&name).unwrap(), Scope::User.apply(&name).unwrap());
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_with_scope_display() {
+        let name = format!("{}-scope-display", UNIQ_ID);
+        let instance_a = SingleInstance::with_scope(&name, Scope::Display).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::with_scope(&name, Scope::Display).unwrap();
+        assert!(!instance_b.is_single());
+        assert!(instance_a.name().starts_with(&name));
+        assert!(!instance_a.name()[name.len()..].contains(['/', ':']));
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_with_scope_path() {
+        let name = format!("{}-scope-path", UNIQ_ID);
+        let dir = std::env::temp_dir();
+        let instance_a = SingleInstance::with_scope(&name, Scope::Path(dir.clone())).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::with_scope(&name, Scope::Path(dir.join(".").join(""))).unwrap();
+        assert!(!instance_b.is_single());
+        let instance_c = SingleInstance::with_scope(&name, Scope::Path(std::env::current_dir().unwrap())).unwrap();
+        assert!(instance_c.is_single());
+        assert!(SingleInstance::with_scope(&name, Scope::Path(dir.join(UNIQ_ID).join("missing"))).is_err());
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_with_scope_container() {
+        let name = format!("{}-scope-container", UNIQ_ID);
+        let instance_a = SingleInstance::with_scope(&name, Scope::Container).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::with_scope(&name, Scope::Container).unwrap();
+        assert!(!instance_b.is_single());
+        #[cfg(target_os = "linux")]
+        assert!(instance_a.name().starts_with(&format!("{}-mnt-", name)));
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn test_with_scope_host() {
+        let name = format!("{}-scope-host", UNIQ_ID);
+        let instance_a = SingleInstance::with_scope(&name, Scope::Host).unwrap();
+        assert!(instance_a.is_single());
+        let instance_b = SingleInstance::with_scope(&name, Scope::Host).unwrap();
+        assert!(!instance_b.is_single());
+        assert!(instance_a.name().len() > format!("{}-host-", name).len());
+    }
+
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    #[test]
+    fn test_builder_file_mode() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let path = std::env::temp_dir().join(format!("{}-mode", UNIQ_ID));
+        let gid = unsafe { libc::getegid() };
+        let instance = SingleInstance::builder(path.to_str().unwrap())
+            .backend(Backend::FileLock)
+            .file_mode(0o640)
+            .file_group(gid)
+            .build()
+            .unwrap();
+        assert!(instance.is_single());
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+        assert_eq!(meta.gid(), gid);
+        drop(instance);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(all(target_os = "linux", not(target_env = "ohos")))]
+    #[test]
+    fn test_bare_name_lock_path() {
+        let name = format!("{}-bare", UNIQ_ID);
+        let instance_a = SingleInstance::with_backend(&name, Backend::FileLock).unwrap();
+        assert!(instance_a.is_single());
+        let path = instance_a.lock_path().unwrap();
+        assert!(path.is_absolute());
+        assert!(path.file_name().unwrap().to_str().unwrap().starts_with(&name));
+        assert!(path.exists());
+        assert!(!std::path::Path::new(&name).exists());
+        let instance_b = SingleInstance::with_backend(&name, Backend::FileLock).unwrap();
+        assert!(!instance_b.is_single());
+        drop(instance_b);
+        drop(instance_a);
+        remove_lock_file(&name);
+    }
+
+    #[cfg(any(target_os = "windows", all(target_os = "linux", not(target_env = "ohos"))))]
+    #[test]
+    fn test_single_instance_with_file_lock_backend() {
+        let path = std::env::temp_dir().join(UNIQ_ID);
+        let path = path.to_str().unwrap();
+        {
+            let instance_a = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+            assert!(instance_a.is_single());
+            assert_eq!(instance_a.lock_path(), Some(std::path::PathBuf::from(path)));
+            // Closing another descriptor of the file keeps the lock.
+            #[cfg(target_os = "linux")]
+            drop(std::fs::File::open(path).unwrap());
+            let instance_b = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+            assert!(!instance_b.is_single());
+            assert_eq!(instance_a.holder_pid(), None);
+            assert_eq!(instance_b.holder_pid(), Some(std::process::id()));
+            #[cfg(target_os = "linux")]
+            {
+                use std::os::unix::io::AsRawFd;
+                assert!(instance_a.as_raw_fd() >= 0);
+                assert_eq!(instance_b.as_raw_fd(), -1);
+            }
+            #[cfg(target_os = "windows")]
+            {
+                use std::os::windows::io::AsRawHandle;
+                assert!(!instance_a.as_raw_handle().is_null());
+                assert!(instance_b.as_raw_handle().is_null());
+            }
+            #[cfg(target_os = "linux")]
+            {
+                let timeout = std::time::Duration::from_millis(10);
+                assert!(!instance_b.wait_for_exit(Some(timeout)).unwrap());
+            }
+        }
+        // The holder's record is removed with its lock.
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "");
+        let instance_c = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "sysinfo", target_os = "linux"))]
+    #[test]
+    fn test_holder_info() {
+        let path = std::env::temp_dir().join(format!("{}-holder-info", UNIQ_ID));
+        let path = path.to_str().unwrap();
+        let instance_a = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(instance_a.holder_info().is_none());
+        let instance_b = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        let info = instance_b.holder_info().unwrap();
+        assert_eq!(info.pid, std::process::id());
+        assert_eq!(info.exe, Some(std::env::current_exe().unwrap()));
+        assert!(info.start_time.unwrap() <= std::time::SystemTime::now());
+    }
+
+    #[cfg(all(feature = "flock", target_os = "linux", not(target_env = "ohos")))]
+    #[test]
+    fn test_single_instance_with_flock_backend() {
+        let path = std::env::temp_dir().join(format!("{}-flock", UNIQ_ID));
+        let path = path.to_str().unwrap();
+        {
+            let instance_a = SingleInstance::with_backend(path, Backend::Flock).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(path, Backend::Flock).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backend(path, Backend::Flock).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "semaphore", target_os = "linux", not(target_env = "ohos")))]
+    #[test]
+    fn test_single_instance_with_semaphore_backend() {
+        {
+            let instance_a = SingleInstance::with_backend(UNIQ_ID, Backend::Semaphore).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(UNIQ_ID, Backend::Semaphore).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backend(UNIQ_ID, Backend::Semaphore).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "sysv-semaphore", target_os = "linux", not(target_env = "ohos")))]
+    #[test]
+    fn test_single_instance_with_sysv_semaphore_backend() {
+        {
+            let instance_a = SingleInstance::with_backend(UNIQ_ID, Backend::SysvSemaphore).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(UNIQ_ID, Backend::SysvSemaphore).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backend(UNIQ_ID, Backend::SysvSemaphore).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "robust-mutex", target_os = "linux", not(target_env = "ohos")))]
+    #[test]
+    fn test_single_instance_with_robust_mutex_backend() {
+        static ROBUST_MUTEX_ID: &str = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-robust";
+        {
+            let instance_a = SingleInstance::with_backend(ROBUST_MUTEX_ID, Backend::RobustMutex).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(ROBUST_MUTEX_ID, Backend::RobustMutex).unwrap();
+            assert!(!instance_b.is_single());
+        }
+
+        // A thread exiting while holding the mutex looks like a crashed owner.
+        std::thread::spawn(|| {
+            let instance = SingleInstance::with_backend(ROBUST_MUTEX_ID, Backend::RobustMutex).unwrap();
+            assert!(instance.is_single());
+            std::mem::forget(instance);
+        })
+        .join()
+        .unwrap();
+
+        let instance_c = SingleInstance::with_backend(ROBUST_MUTEX_ID, Backend::RobustMutex).unwrap();
+        assert!(instance_c.is_single());
+        assert!(instance_c.owner_died());
+    }
+
+    #[cfg(all(feature = "tcp-port", any(unix, windows)))]
+    #[test]
+    fn test_single_instance_with_tcp_port_backend() {
+        {
+            let instance_a = SingleInstance::with_backend(UNIQ_ID, Backend::TcpPort).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(UNIQ_ID, Backend::TcpPort).unwrap();
+            assert!(!instance_b.is_single());
+            assert!(instance_b.tcp_listener().is_none());
+
+            let addr = instance_a.tcp_listener().unwrap().local_addr().unwrap();
+            assert_eq!(addr.port(), tcp::port(UNIQ_ID));
+            std::net::TcpStream::connect(addr).unwrap();
+        }
+        let instance_c = SingleInstance::with_backend(UNIQ_ID, Backend::TcpPort).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "unix-socket", unix, not(target_os = "cygwin")))]
+    #[test]
+    fn test_single_instance_with_unix_socket_backend() {
+        static UNIX_SOCKET_ID: &str = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b.sock";
+        {
+            let instance_a = SingleInstance::with_backend(UNIX_SOCKET_ID, Backend::UnixSocket).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(UNIX_SOCKET_ID, Backend::UnixSocket).unwrap();
+            assert!(!instance_b.is_single());
+            std::os::unix::net::UnixStream::connect(unix_socket::path(UNIX_SOCKET_ID)).unwrap();
+        }
+
+        // A socket file left behind by a crashed instance is taken over.
+        drop(std::os::unix::net::UnixListener::bind(unix_socket::path(UNIX_SOCKET_ID)).unwrap());
+        let instance_c = SingleInstance::with_backend(UNIX_SOCKET_ID, Backend::UnixSocket).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "pid-file", target_os = "linux"))]
+    #[test]
+    fn test_single_instance_with_pid_file_backend() {
+        let path = std::env::temp_dir().join("aa2d0258-ffe9-11e7-ba89-0ed5f89f718b.pid");
+        let backend = Backend::PidFile { same_executable: true };
+        {
+            let instance_a = SingleInstance::with_backend(path.to_str().unwrap(), backend).unwrap();
+            assert!(instance_a.is_single());
+            let pid = std::fs::read_to_string(&path).unwrap();
+            assert_eq!(pid.trim(), std::process::id().to_string());
+            let instance_b = SingleInstance::with_backend(path.to_str().unwrap(), backend).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        assert!(!path.exists());
+
+        // A file naming a process that no longer runs is replaced.
+        std::fs::write(&path, format!("{}\n", i32::MAX)).unwrap();
+        let instance_c = SingleInstance::with_backend(path.to_str().unwrap(), backend).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "pid-file", unix, not(target_os = "cygwin")))]
+    #[test]
+    fn test_persist_lock_file() {
+        let path = std::env::temp_dir().join("aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-persist.pid");
+        let instance = SingleInstance::builder(path.to_str().unwrap())
+            .backend(Backend::PidFile { same_executable: false })
+            .persist_lock_file(true)
+            .build()
+            .unwrap();
+        assert!(instance.is_single());
+        drop(instance);
+        let pid = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(pid.trim(), std::process::id().to_string());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = std::env::temp_dir().join(format!("{}-persist", UNIQ_ID));
+        let mut instance = SingleInstance::with_backend(path.to_str().unwrap(), Backend::FileLock).unwrap();
+        instance.persist_lock_file(true);
+        drop(instance);
+        let record = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(lock_info::LockInfo::parse(&record).unwrap().pid, std::process::id());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(any(all(unix, not(target_os = "cygwin")), target_os = "windows"))]
+    #[test]
+    fn test_lock_info() {
+        use lock_info::LockInfo;
+
+        let info = LockInfo {
+            pid: 1234,
+            start_time: 5678,
+            exe: Some(std::path::PathBuf::from("/usr/bin/my app")),
+            version: 1,
+        };
+        assert_eq!(LockInfo::parse(&info.encode()), Some(info));
+        let legacy = LockInfo::parse("1234 5678\n").unwrap();
+        assert_eq!((legacy.pid, legacy.start_time, legacy.exe, legacy.version), (1234, 5678, None, 0));
+        assert_eq!(LockInfo::parse("pid=1234\nstart_time=5678\nfuture=1\n").unwrap().pid, 1234);
+        assert_eq!(LockInfo::parse("pid=1234\n"), None);
+        assert_eq!(LockInfo::parse("hello world\n"), None);
+
+        let path = std::env::temp_dir().join(format!("{}-lock-info", UNIQ_ID));
+        let instance = SingleInstance::with_backend(path.to_str().unwrap(), Backend::FileLock).unwrap();
+        assert!(instance.is_single());
+        let info = LockInfo::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(info.pid, std::process::id());
+        assert_eq!(info.exe, std::env::current_exe().ok());
+        assert_ne!(info.version, 0);
+        drop(instance);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(any(all(unix, not(target_os = "cygwin")), target_os = "windows"))]
+    #[test]
+    fn test_read_lock_info() {
+        let path = std::env::temp_dir().join(format!("{}-read-lock-info", UNIQ_ID));
+        let path = path.to_str().unwrap();
+        assert_eq!(SingleInstance::read_lock_info(path, Backend::FileLock), None);
+        let instance = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(instance.is_single());
+        let info = SingleInstance::read_lock_info(path, Backend::FileLock).unwrap();
+        assert_eq!(info.pid, std::process::id());
+        assert_eq!(info.exe, std::env::current_exe().ok());
+        drop(instance);
+        assert_eq!(SingleInstance::read_lock_info(path, Backend::FileLock), None);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn test_wait_for_exit_without_holder() {
+        let path = std::env::temp_dir().join(format!("{}-wait-for-exit", UNIQ_ID));
+        let path = path.to_str().unwrap();
+        let instance = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(instance.is_single());
+        match instance.wait_for_exit(Some(std::time::Duration::from_millis(10))) {
+            Err(error::SingleInstanceError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("unexpected {:?}", other),
+        }
+        drop(instance);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_kill_other() {
+        let path = std::env::temp_dir().join(format!("{}-kill-other", UNIQ_ID));
+        let path = path.to_str().unwrap();
+        let instance_a = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(instance_a.is_single());
+        match instance_a.kill_other(KillSignal::Terminate) {
+            Err(error::SingleInstanceError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotFound),
+            other => panic!("unexpected {:?}", other),
+        }
+        // The holder is this process, which must not be hit.
+        let instance_b = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(!instance_b.is_single());
+        match instance_b.kill_other(KillSignal::Kill) {
+            Err(error::SingleInstanceError::HolderIsSelf) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        drop(instance_a);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(all(abstract_socket, target_os = "linux"))]
+    #[test]
+    fn test_holder_pid_of_abstract_socket() {
+        let name = format!("{}-holder-pid", UNIQ_ID);
+        let instance_a = SingleInstance::with_backend(&name, Backend::AbstractSocket).unwrap();
+        assert!(instance_a.is_single());
+        assert_eq!(instance_a.holder_pid(), None);
+        let instance_b = SingleInstance::with_backend(&name, Backend::AbstractSocket).unwrap();
+        assert!(!instance_b.is_single());
+        assert_eq!(instance_b.holder_pid(), Some(std::process::id()));
+        drop(instance_a);
+        assert_eq!(instance_b.holder_pid(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_acquire_or_steal() {
+        let path = std::env::temp_dir().join(format!("{}-acquire-or-steal", UNIQ_ID));
+        let path = path.to_str().unwrap();
+        let mut instance_a = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(instance_a.acquire_or_steal(std::time::Duration::ZERO, None).unwrap());
+        let timeout = std::time::Duration::from_millis(100);
+        let mut instance_b = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+        assert!(!instance_b.acquire_or_steal(timeout, None).unwrap());
+        // The holder is this process, which is left alone.
+        match instance_b.acquire_or_steal(timeout, Some(KillSignal::Kill)) {
+            Err(error::SingleInstanceError::HolderIsSelf) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        drop(instance_a);
+        assert!(instance_b.acquire_or_steal(timeout, Some(KillSignal::Kill)).unwrap());
+        drop(instance_b);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(all(feature = "bundle-id", target_os = "macos"))]
+    #[test]
+    fn test_single_instance_with_bundle_id_backend() {
+        // Test binaries are not bundled, so no other app has this identifier.
+        let instance = SingleInstance::with_backend("com.example.aa2d0258", Backend::BundleId).unwrap();
+        assert!(instance.is_single());
+        assert!(!macos::activate("com.example.aa2d0258"));
+    }
+
+    #[cfg(all(feature = "systemd", target_os = "linux"))]
+    #[test]
+    fn test_single_instance_with_systemd_backend() {
+        let bare = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b.systemd";
+        assert_eq!(systemd::lock_path(bare), systemd::runtime_dir().join(bare));
+        // A path keeps the test away from the runtime directory, which may
+        // not be writable.
+        let path = std::env::temp_dir().join(bare);
+        let name = path.to_str().unwrap();
+        assert_eq!(systemd::lock_path(name), path);
+        {
+            let instance_a = SingleInstance::with_backend(name, Backend::Systemd).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(name, Backend::Systemd).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backend(name, Backend::Systemd).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    /// Serves the commands `RedisLock` sends like a Redis server without
+    /// expiry, returning its address.
+    #[cfg(feature = "redis")]
+    fn fake_redis() -> String {
+        use std::collections::HashMap;
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::{Arc, Mutex};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let keys = Arc::new(Mutex::new(HashMap::<String, String>::new()));
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let keys = keys.clone();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                        let count: usize = line.trim_end()[1..].parse().unwrap();
+                        let mut args = Vec::new();
+                        for _ in 0..count {
+                            line.clear();
+                            reader.read_line(&mut line).unwrap();
+                            line.clear();
+                            reader.read_line(&mut line).unwrap();
+                            args.push(line.trim_end().to_owned());
+                        }
+                        line.clear();
+
+                        let mut keys = keys.lock().unwrap();
+                        let reply = match args[0].as_str() {
+                            "SET" if keys.contains_key(&args[1]) => "$-1\r\n".to_owned(),
+                            "SET" => {
+                                keys.insert(args[1].clone(), args[2].clone());
+                                "+OK\r\n".to_owned()
+                            }
+                            "EVAL" => {
+                                let held = keys.get(&args[3]) == Some(&args[4]);
+                                if held && args[1].contains("'del'") {
+                                    keys.remove(&args[3]);
+                                }
+                                format!(":{}\r\n", held as i32)
+                            }
+                            _ => "-ERR unknown command\r\n".to_owned(),
+                        };
+                        stream.write_all(reply.as_bytes()).unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_single_instance_with_redis_backend() {
+        let addr = fake_redis();
+        let lock = || Box::new(redis::RedisLock::new(&addr, std::time::Duration::from_secs(30)));
+        {
+            let instance_a = SingleInstance::with_custom_backend(UNIQ_ID, lock()).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_custom_backend(UNIQ_ID, lock()).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_custom_backend(UNIQ_ID, lock()).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(all(feature = "event", any(target_os = "windows", target_os = "cygwin")))]
+    #[test]
+    fn test_single_instance_with_event_backend() {
+        {
+            let instance_a = SingleInstance::with_backend(UNIQ_ID, Backend::Event).unwrap();
+            assert!(instance_a.is_single());
+            let instance_b = SingleInstance::with_backend(UNIQ_ID, Backend::Event).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backend(UNIQ_ID, Backend::Event).unwrap();
         assert!(instance_c.is_single());
     }
+
+    #[cfg(all(feature = "named-pipe", target_os = "windows"))]
+    #[test]
+    fn test_single_instance_with_named_pipe_backend() {
+        {
+            let instance_a = SingleInstance::with_backend(UNIQ_ID, Backend::NamedPipe).unwrap();
+            assert!(instance_a.is_single());
+            assert!(instance_a.pipe().is_some());
+            let instance_b = SingleInstance::with_backend(UNIQ_ID, Backend::NamedPipe).unwrap();
+            assert!(!instance_b.is_single());
+        }
+        let instance_c = SingleInstance::with_backend(UNIQ_ID, Backend::NamedPipe).unwrap();
+        assert!(instance_c.is_single());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    mod cross_process {
+        use super::*;
+        use std::env;
+        use std::io::{BufRead, BufReader, Read};
+        use std::process::{Command, Stdio};
+
+        static CROSS_PROCESS_ID: &str = "aa2d0258-ffe9-11e7-ba89-0ed5f89f718b-child";
+        static CHILD_ENV: &str = "SINGLE_INSTANCE_TEST_CHILD";
+
+        /// Runs this test binary again as a child process which takes the lock and
+        /// holds it until its stdin is closed.
+        #[test]
+        fn test_single_instance_across_processes() {
+            if env::var_os(CHILD_ENV).is_some() {
+                let instance = SingleInstance::new(CROSS_PROCESS_ID).unwrap();
+                println!("child is single: {}", instance.is_single());
+                let _ = std::io::stdin().read_to_end(&mut Vec::new());
+                return;
+            }
+
+            let mut child = Command::new(env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "tests::cross_process::test_single_instance_across_processes",
+                    "--nocapture",
+                ])
+                .env(CHILD_ENV, "1")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            let stdout = BufReader::new(child.stdout.take().unwrap());
+            let mut lines = stdout.lines().map(|line| line.unwrap());
+            let child_is_single = lines
+                .by_ref()
+                .find(|line| line.contains("child is single: "))
+                .unwrap();
+            assert!(child_is_single.ends_with("child is single: true"));
+
+            let instance_a = SingleInstance::new(CROSS_PROCESS_ID).unwrap();
+            assert!(!instance_a.is_single());
+
+            // Closing stdin lets the child exit, its remaining output still has to
+            // be drained for the child's test harness to finish cleanly.
+            drop(child.stdin.take());
+            lines.for_each(drop);
+            assert!(child.wait().unwrap().success());
+
+            let instance_b = SingleInstance::new(CROSS_PROCESS_ID).unwrap();
+            assert!(instance_b.is_single());
+        }
+
+        /// Runs this test binary again as a child process holding a lock file
+        /// and waits for it to exit once its stdin is closed.
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        #[test]
+        fn test_wait_for_exit_across_processes() {
+            let path = env::temp_dir().join(format!("{}-wait-for-exit", CROSS_PROCESS_ID));
+            let path = path.to_str().unwrap();
+            if env::var_os(CHILD_ENV).is_some() {
+                let instance = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+                println!("child is single: {}", instance.is_single());
+                let _ = std::io::stdin().read_to_end(&mut Vec::new());
+                return;
+            }
+
+            let mut child = Command::new(env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "tests::cross_process::test_wait_for_exit_across_processes",
+                    "--nocapture",
+                ])
+                .env(CHILD_ENV, "1")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            let stdout = BufReader::new(child.stdout.take().unwrap());
+            let mut lines = stdout.lines().map(|line| line.unwrap());
+            let child_is_single = lines
+                .by_ref()
+                .find(|line| line.contains("child is single: "))
+                .unwrap();
+            assert!(child_is_single.ends_with("child is single: true"));
+
+            let instance = SingleInstance::with_backend(path, Backend::FileLock).unwrap();
+            assert!(!instance.is_single());
+            assert_eq!(instance.holder_pid(), Some(child.id()));
+            assert!(!instance.wait_for_exit(Some(std::time::Duration::from_millis(10))).unwrap());
+
+            drop(child.stdin.take());
+            assert!(instance.wait_for_exit(Some(std::time::Duration::from_secs(10))).unwrap());
+            lines.for_each(drop);
+            assert!(child.wait().unwrap().success());
+            drop(instance);
+            std::fs::remove_file(path).unwrap();
+        }
+
+        /// Runs this test binary again as a child process holding a name with
+        /// the default backend, which ignores requests to take over and is
+        /// killed instead.
+        #[cfg(all(abstract_socket, target_os = "linux"))]
+        #[test]
+        fn test_acquire_or_steal_across_processes() {
+            let name = format!("{}-steal", CROSS_PROCESS_ID);
+            if env::var_os(CHILD_ENV).is_some() {
+                let instance = SingleInstance::new(&name).unwrap();
+                println!("child is single: {}", instance.is_single());
+                let _ = std::io::stdin().read_to_end(&mut Vec::new());
+                return;
+            }
+
+            let mut child = Command::new(env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "tests::cross_process::test_acquire_or_steal_across_processes",
+                    "--nocapture",
+                ])
+                .env(CHILD_ENV, "1")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            let stdout = BufReader::new(child.stdout.take().unwrap());
+            let mut lines = stdout.lines().map(|line| line.unwrap());
+            let child_is_single = lines
+                .by_ref()
+                .find(|line| line.contains("child is single: "))
+                .unwrap();
+            assert!(child_is_single.ends_with("child is single: true"));
+
+            let mut instance = SingleInstance::new(&name).unwrap();
+            assert!(!instance.is_single());
+            assert_eq!(instance.holder_pid(), Some(child.id()));
+            let timeout = std::time::Duration::from_secs(5);
+            assert!(instance.acquire_or_steal(timeout, Some(KillSignal::Kill)).unwrap());
+            assert!(instance.is_single());
+            lines.for_each(drop);
+            assert!(!child.wait().unwrap().success());
+        }
+    }
 }
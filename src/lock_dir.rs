@@ -0,0 +1,29 @@
+//! Process wide directory that instance names are resolved into, for
+//! platforms where apps cannot pick an arbitrary lock file location.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+static LOCK_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Sets the directory instance names are resolved into.
+///
+/// A name without a path separator becomes a lock file inside this directory,
+/// a name with one is used as a path as is.
+pub fn set_lock_dir<P: Into<PathBuf>>(dir: P) {
+    *LOCK_DIR.lock().unwrap_or_else(|e| e.into_inner()) = Some(dir.into());
+}
+
+/// Returns the directory set with `set_lock_dir`, if any.
+pub fn lock_dir() -> Option<PathBuf> {
+    LOCK_DIR.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Returns the lock file path for `name` inside `dir`.
+pub(crate) fn resolve(dir: &Path, name: &str) -> PathBuf {
+    if name.contains('/') {
+        PathBuf::from(name)
+    } else {
+        dir.join(name)
+    }
+}
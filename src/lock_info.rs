@@ -0,0 +1,100 @@
+//! What the holder of a name records about itself, for other instances and
+//! external tools.
+//!
+//! The record is text of `key=value` lines, such as:
+//!
+//! ```text
+//! pid=1234
+//! start_time=5678
+//! version=1
+//! exe=/usr/bin/my-app
+//! ```
+//!
+//! `pid` and `start_time` are always there, the start time telling a reused
+//! PID apart: in clock ticks since boot on linux and android, in
+//! microseconds since 1970 on macos, in 100 nanosecond intervals since 1601
+//! on windows, and 0 on other platforms, which cannot tell it. `version` is the version of the protocol
+//! instances talk to one another with, and `exe` the path of the holder's
+//! executable, if known. Lines with other keys are ignored, so that more may
+//! be added. A record of only a PID and a start time separated by a space,
+//! as written before, is read too.
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+/// The record the holder of a lock file writes into it on unix and windows,
+/// and into a file mapping next to its mutex on windows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockInfo {
+    /// The PID of the holder.
+    pub pid: u32,
+    /// When the holder started, see the module documentation.
+    pub start_time: u64,
+    /// The path of the holder's executable.
+    pub exe: Option<PathBuf>,
+    /// The protocol version of the holder, or 0 if it did not record one.
+    pub version: u8,
+}
+
+impl LockInfo {
+    /// Returns the record of this process, which started at `start_time`.
+    pub(crate) fn current(start_time: u64) -> Self {
+        LockInfo {
+            pid: process::id(),
+            start_time,
+            exe: env::current_exe().ok(),
+            version: ::notify::PROTOCOL_VERSION,
+        }
+    }
+
+    /// Returns the record as text to write.
+    pub(crate) fn encode(&self) -> String {
+        let mut record = format!("pid={}\nstart_time={}\nversion={}\n", self.pid, self.start_time, self.version);
+        // A path with a line break would end the record early.
+        if let Some(exe) = self.exe.as_ref().and_then(|exe| exe.to_str()).filter(|exe| !exe.contains('\n')) {
+            record.push_str(&format!("exe={}\n", exe));
+        }
+        record
+    }
+
+    /// Parses a record, returning `None` for other contents.
+    pub fn parse(contents: &str) -> Option<Self> {
+        if !contents.contains('=') {
+            return Self::parse_legacy(contents);
+        }
+        let (mut pid, mut start_time, mut exe, mut version) = (None, None, None, 0);
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once('=')?;
+            match key {
+                "pid" => pid = Some(value.parse().ok().filter(|pid| *pid > 0)?),
+                "start_time" => start_time = Some(value.parse().ok()?),
+                "version" => version = value.parse().ok()?,
+                "exe" => exe = Some(PathBuf::from(value)),
+                _ => {}
+            }
+        }
+        Some(LockInfo {
+            pid: pid?,
+            start_time: start_time?,
+            exe,
+            version,
+        })
+    }
+
+    /// Parses a record of a PID and its start time separated by a space.
+    fn parse_legacy(contents: &str) -> Option<Self> {
+        let mut fields = contents.split_whitespace();
+        let pid = fields.next()?.parse().ok().filter(|pid| *pid > 0)?;
+        let start_time = fields.next()?.parse().ok()?;
+        match fields.next() {
+            None => Some(LockInfo {
+                pid,
+                start_time,
+                exe: None,
+                version: 0,
+            }),
+            Some(_) => None,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Running apps on macos, as Launch Services knows them.
+//!
+//! `Backend::BundleId` treats the name as a bundle identifier, and the
+//! instance as single if no other app with it is running. This is how macos
+//! itself tells apps apart, so it also covers copies of the app launched from
+//! elsewhere on disk. The running app can then be brought to the front:
+//!
+//! ```rust,no_run
+//! use single_instance::{macos, Backend, SingleInstance};
+//!
+//! let bundle_id = macos::bundle_identifier().unwrap();
+//! let instance = SingleInstance::with_backend(&bundle_id, Backend::BundleId).unwrap();
+//! if !instance.is_single() {
+//!     macos::activate(&bundle_id);
+//! }
+//! ```
+//!
+//! Apps that are not bundled can instead have the instance holding a name
+//! bring itself to the front with `activate_current` when asked to by
+//! another one, see `SingleInstance::activate_app_on_request`.
+
+use libc::{getpid, pid_t};
+use std::ffi::{CStr, CString};
+use std::io;
+use std::mem;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+type Id = *mut c_void;
+type Sel = *mut c_void;
+
+/// `NSApplicationActivateAllWindows`.
+const ACTIVATE_ALL_WINDOWS: usize = 1 << 0;
+
+#[link(name = "objc")]
+extern "C" {
+    fn objc_getClass(name: *const c_char) -> Id;
+    fn sel_registerName(name: *const c_char) -> Sel;
+    fn objc_msgSend();
+    fn objc_autoreleasePoolPush() -> *mut c_void;
+    fn objc_autoreleasePoolPop(pool: *mut c_void);
+}
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {}
+
+/// A dispatch queue, of which only the address is used.
+#[repr(C)]
+struct DispatchQueue {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    static _dispatch_main_q: DispatchQueue;
+    fn dispatch_async_f(queue: *const DispatchQueue, context: *mut c_void, work: extern "C" fn(*mut c_void));
+}
+
+/// Returns the bundle identifier of the running app, or `None` if it is not
+/// bundled.
+pub fn bundle_identifier() -> Option<String> {
+    unsafe {
+        let send: unsafe extern "C" fn(Id, Sel) -> Id = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+        let pool = objc_autoreleasePoolPush();
+        let bundle = send(class(b"NSBundle\0"), sel(b"mainBundle\0"));
+        let id = send(bundle, sel(b"bundleIdentifier\0"));
+        let id = if id.is_null() {
+            None
+        } else {
+            let utf8 = send(id, sel(b"UTF8String\0")) as *const c_char;
+            Some(CStr::from_ptr(utf8).to_string_lossy().into_owned())
+        };
+        objc_autoreleasePoolPop(pool);
+        id
+    }
+}
+
+/// Brings another running app with the bundle identifier `bundle_id` to the
+/// front, returning `false` if there is none.
+pub fn activate(bundle_id: &str) -> bool {
+    unsafe {
+        let activate: unsafe extern "C" fn(Id, Sel, usize) -> i8 =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        with_others(bundle_id, |app| activate(app, sel(b"activateWithOptions:\0"), ACTIVATE_ALL_WINDOWS) != 0)
+            .unwrap_or(false)
+    }
+}
+
+/// Unhides this app, restores its minimized windows and brings it to the
+/// front, on the main thread once its event loop runs, as AppKit requires.
+pub fn activate_current() {
+    unsafe { dispatch_async_f(&_dispatch_main_q, ptr::null_mut(), activate_on_main) }
+}
+
+extern "C" fn activate_on_main(_: *mut c_void) {
+    unsafe {
+        let send: unsafe extern "C" fn(Id, Sel) -> Id = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let send_id: unsafe extern "C" fn(Id, Sel, Id) = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let send_bool: unsafe extern "C" fn(Id, Sel, i8) = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let is: unsafe extern "C" fn(Id, Sel) -> i8 = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let count: unsafe extern "C" fn(Id, Sel) -> usize = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let at: unsafe extern "C" fn(Id, Sel, usize) -> Id = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+        let pool = objc_autoreleasePoolPush();
+        let app = send(class(b"NSApplication\0"), sel(b"sharedApplication\0"));
+        send_id(app, sel(b"unhide:\0"), ptr::null_mut());
+        let windows = send(app, sel(b"windows\0"));
+        for i in 0..count(windows, sel(b"count\0")) {
+            let window = at(windows, sel(b"objectAtIndex:\0"), i);
+            if is(window, sel(b"isMiniaturized\0")) != 0 {
+                send_id(window, sel(b"deminiaturize:\0"), ptr::null_mut());
+            }
+        }
+        send_bool(app, sel(b"activateIgnoringOtherApps:\0"), 1);
+        objc_autoreleasePoolPop(pool);
+    }
+}
+
+/// Returns whether another app with the bundle identifier `bundle_id` is
+/// running.
+#[cfg(feature = "bundle-id")]
+pub(crate) fn others_running(bundle_id: &str) -> io::Result<bool> {
+    with_others(bundle_id, |_| true)
+}
+
+/// Calls `f` on each running app with the bundle identifier `bundle_id` other
+/// than this process, until it returns `true`, and returns whether it did.
+fn with_others<F: FnMut(Id) -> bool>(bundle_id: &str, mut f: F) -> io::Result<bool> {
+    let bundle_id = CString::new(bundle_id)?;
+    unsafe {
+        let send_ptr: unsafe extern "C" fn(Id, Sel, *const c_void) -> Id =
+            mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let count: unsafe extern "C" fn(Id, Sel) -> usize = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let at: unsafe extern "C" fn(Id, Sel, usize) -> Id = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let pid: unsafe extern "C" fn(Id, Sel) -> pid_t = mem::transmute(objc_msgSend as unsafe extern "C" fn());
+
+        let pool = objc_autoreleasePoolPush();
+        let bundle_id = send_ptr(
+            class(b"NSString\0"),
+            sel(b"stringWithUTF8String:\0"),
+            bundle_id.as_ptr() as _,
+        );
+        let apps = send_ptr(
+            class(b"NSRunningApplication\0"),
+            sel(b"runningApplicationsWithBundleIdentifier:\0"),
+            bundle_id,
+        );
+        let mut found = false;
+        for i in 0..count(apps, sel(b"count\0")) {
+            let app = at(apps, sel(b"objectAtIndex:\0"), i);
+            if pid(app, sel(b"processIdentifier\0")) != getpid() && f(app) {
+                found = true;
+                break;
+            }
+        }
+        objc_autoreleasePoolPop(pool);
+        Ok(found)
+    }
+}
+
+unsafe fn class(name: &[u8]) -> Id {
+    objc_getClass(name.as_ptr() as _)
+}
+
+unsafe fn sel(name: &[u8]) -> Sel {
+    sel_registerName(name.as_ptr() as _)
+}
@@ -0,0 +1,143 @@
+//! Names suiting the mechanism that claims them.
+//!
+//! Names are passed to the backends as given as long as they suit them, so
+//! that paths and object names keep working as before. Names a backend would
+//! reject are sanitized instead: a windows mutex name with a backslash after
+//! its `Global\` or `Local\` namespace, a semaphore name with a slash after the
+//! leading one, an abstract socket name longer than a socket address holds or
+//! a lock file name longer than a file name may be. Separators are replaced
+//! with `_`, and names too long are cut short and end with a hash of the whole
+//! name, so that names differing only past the cut stay apart.
+//!
+//! With sanitization disabled by `set_sanitize`, such names fail with
+//! `SingleInstanceError::InvalidName` instead, and so do empty names always.
+
+use error::{Result, SingleInstanceError};
+use fnv;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SANITIZE: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables sanitizing names a backend would reject, enabled by
+/// default.
+pub fn set_sanitize(enabled: bool) {
+    SANITIZE.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns whether names a backend would reject are sanitized.
+pub fn sanitize() -> bool {
+    SANITIZE.load(Ordering::SeqCst)
+}
+
+/// Returns `name` if it is not empty, fails otherwise.
+pub(crate) fn non_empty(name: &str) -> Result<&str> {
+    if name.is_empty() {
+        Err(SingleInstanceError::InvalidName("the name is empty".to_owned()))
+    } else {
+        Ok(name)
+    }
+}
+
+/// Returns `name` sanitized by `fix` if `valid` is false, or fails with
+/// `reason` if sanitization is disabled.
+fn check<'a, F>(name: &'a str, valid: bool, reason: &str, fix: F) -> Result<Cow<'a, str>>
+where
+    F: FnOnce() -> String,
+{
+    if valid {
+        Ok(Cow::Borrowed(name))
+    } else if sanitize() {
+        Ok(Cow::Owned(fix()))
+    } else {
+        Err(SingleInstanceError::InvalidName(format!("{}: {}", reason, name)))
+    }
+}
+
+/// Returns `part` of `name` cut short to `max` bytes at a character boundary,
+/// ending with the hash of `name`.
+fn shorten(name: &str, part: &str, max: usize) -> String {
+    let hash = format!("-{:08x}", fnv::hash(name));
+    let mut end = max.saturating_sub(hash.len()).min(part.len());
+    while !part.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &part[..end], hash)
+}
+
+/// Returns `name` suiting a windows named object, a mutex, an event or a
+/// semaphore: no backslash past its namespace and at most `MAX_PATH`
+/// characters.
+#[cfg(any(target_os = "windows", target_os = "cygwin"))]
+pub(crate) fn object(name: &str) -> Result<Cow<'_, str>> {
+    const MAX_PATH: usize = 260;
+
+    let name = non_empty(name)?;
+    let prefix = ["Global\\", "Local\\"]
+        .iter()
+        .find(|prefix| name.starts_with(*prefix))
+        .map_or("", |prefix| *prefix);
+    let rest = &name[prefix.len()..];
+    let len = name.encode_utf16().count();
+    check(name, !rest.contains('\\') && len <= MAX_PATH, "not a valid object name", || {
+        let rest = rest.replace('\\', "_");
+        if len > MAX_PATH {
+            // Cut by bytes, which are at least as many as UTF-16 units.
+            format!("{}{}", prefix, shorten(name, &rest, MAX_PATH - prefix.len()))
+        } else {
+            format!("{}{}", prefix, rest)
+        }
+    })
+}
+
+/// Returns `name` suiting an abstract unix socket address, which holds at
+/// most 107 bytes.
+#[cfg(abstract_socket)]
+pub(crate) fn abstract_socket(name: &str) -> Result<Cow<'_, str>> {
+    const MAX: usize = 107;
+
+    let name = non_empty(name)?;
+    check(name, name.len() <= MAX, "too long for an abstract socket", || {
+        shorten(name, name, MAX)
+    })
+}
+
+/// Returns `name` suiting a lock file: its last component, the file name, at
+/// most 255 bytes long. The directories are left to the file system.
+#[cfg(all(unix, not(target_os = "cygwin")))]
+pub(crate) fn file(name: &str) -> Result<Cow<'_, str>> {
+    const NAME_MAX: usize = 255;
+
+    let name = non_empty(name)?;
+    let (dir, file) = match name.rfind('/') {
+        Some(slash) => name.split_at(slash + 1),
+        None => ("", name),
+    };
+    check(name, file.len() <= NAME_MAX, "the file name is too long", || {
+        format!("{}{}", dir, shorten(name, file, NAME_MAX))
+    })
+}
+
+/// Returns `name` suiting a POSIX semaphore or shared memory object: no
+/// slash past the leading one, which is optional, and short enough for the
+/// platform, which on macos and ios means 31 bytes.
+#[cfg(any(all(feature = "robust-mutex", target_os = "linux"), posix_semaphore))]
+pub(crate) fn posix_object(name: &str) -> Result<Cow<'_, str>> {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    const MAX: usize = 31;
+    // Linux prepends `sem.` to the name of a semaphore's file.
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    const MAX: usize = 251;
+
+    let name = non_empty(name)?;
+    let rest = name.strip_prefix('/').unwrap_or(name);
+    let len = rest.len() + 1;
+    check(name, !rest.is_empty() && !rest.contains('/') && len <= MAX, "not a valid POSIX object name", || {
+        let rest = rest.replace('/', "_");
+        if len > MAX || rest.is_empty() {
+            format!("/{}", shorten(name, &rest, MAX - 1))
+        } else {
+            format!("/{}", rest)
+        }
+    })
+}
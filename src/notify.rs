@@ -0,0 +1,1286 @@
+//! Notifications from instances that failed to claim a name to the one
+//! holding it.
+//!
+//! The instance holding a name can listen with
+//! `SingleInstance::on_second_instance`. Every instance failing to claim the
+//! name afterwards connects and sends its PID and working directory, and does
+//! so again with `SingleInstance::activate` to ask it to be activated, which
+//! the holder handles with `SingleInstance::on_activate`. Instances can also
+//! send data with `SingleInstance::send`, which the holder receives from
+//! `SingleInstance::messages` and may reply to, send requests with
+//! `SingleInstance::send_and_wait_reply` answered by the handler set with
+//! `SingleInstance::on_request`, stay connected with
+//! `SingleInstance::subscribe` to receive what it sends with
+//! `SingleInstance::broadcast`, forward URLs with `SingleInstance::forward_urls`
+//! to the handler set with `SingleInstance::on_urls`, and files to open with
+//! `SingleInstance::forward_open` to the one set with `SingleInstance::on_open`,
+//! or ask it to exit with
+//! `SingleInstance::request_takeover`, also handing over its state with
+//! `SingleInstance::request_handover`. On unix this goes through a
+//! unix socket of the user, abstract on linux and android and in a directory
+//! only the user may enter elsewhere, and on windows through a loopback TCP
+//! port, both derived from a hash of the name. Only processes of the same user
+//! are listened to unless the holder calls `SingleInstance::allow_other_users`,
+//! which on unix also has it listen on a socket shared by all users, where
+//! instances find it that have none of their own user to connect to.
+//!
+//! Every message starts with `PROTOCOL_VERSION` and is answered with the
+//! holder's version and a status before any reply, so that instances of
+//! different versions tell each other apart.
+
+use fnv;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "android")]
+use std::os::android::net::SocketAddrExt;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::io::{BorrowedFd, OwnedFd};
+#[cfg(unix)]
+pub(crate) use std::os::unix::net::UnixStream as Stream;
+#[cfg(unix)]
+use std::os::unix::net::{SocketAddr, UnixListener as Listener};
+#[cfg(windows)]
+pub(crate) use std::net::TcpStream as Stream;
+#[cfg(windows)]
+use std::net::{Ipv4Addr, SocketAddr, TcpListener as Listener};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, BorrowedHandle, OwnedHandle};
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+use zbus::zvariant::OwnedValue;
+
+#[cfg(feature = "serde")]
+use json_lines::{self, JsonMessage};
+#[cfg(all(feature = "appkit", target_os = "macos"))]
+use macos;
+#[cfg(any(all(unix, not(target_os = "cygwin")), windows))]
+use passing;
+#[cfg(all(feature = "notify", any(target_os = "linux", target_os = "macos", windows)))]
+use desktop_notification;
+use peer;
+#[cfg(windows)]
+use winapi::shared::windef::HWND;
+#[cfg(windows)]
+use windows;
+#[cfg(all(feature = "x11", target_os = "linux"))]
+use x11;
+
+/// The names of the environment variables sent along with every message.
+static FORWARDED_ENV: Mutex<Vec<OsString>> = Mutex::new(Vec::new());
+
+/// How many messages the receiver of `SingleInstance::async_messages` holds
+/// before senders have to wait.
+#[cfg(feature = "tokio")]
+const ASYNC_BUFFER: usize = 32;
+
+/// How often an instance taking over the name tries to claim it.
+pub(crate) const TAKEOVER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long an instance waits for a connection to the holder on windows,
+/// where connecting to a port nobody listens on is retried for seconds.
+#[cfg(windows)]
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How long the holder waits for more of a message before dropping the
+/// connection, so that a sender that never finishes ties nothing up.
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The version of the protocol, the first byte of every message and of the
+/// holder's answer to it, so that instances speaking different versions fail
+/// cleanly.
+pub(crate) const PROTOCOL_VERSION: u8 = 1;
+
+/// The largest message the holder accepts by default, see
+/// `SingleInstance::set_max_message_size`.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+/// How much of a rejected message the holder reads at most before closing
+/// the connection.
+const REJECTED_DRAIN_LIMIT: u64 = DEFAULT_MAX_MESSAGE_SIZE as u64;
+
+/// The status answered to a message that was accepted.
+const ACCEPTED: u8 = 0;
+/// The status answered to a message of another protocol version.
+const UNSUPPORTED_VERSION: u8 = 1;
+/// The status answered to a message larger than the holder accepts.
+const TOO_LARGE: u8 = 2;
+
+/// The kind of a message sent by an instance failing to claim the name.
+const SECOND_INSTANCE: u8 = 0;
+/// The kind of a message sent by `SingleInstance::activate`.
+const ACTIVATE: u8 = 1;
+/// The kind of a message sent by `SingleInstance::send`.
+const MESSAGE: u8 = 2;
+/// The kind of a message sent by `SingleInstance::request_takeover`.
+const TAKEOVER: u8 = 3;
+/// The kind of a message sent by `SingleInstance::request_handover`.
+const HANDOVER: u8 = 4;
+/// The kind of a message sent by `SingleInstance::send_and_wait_reply`.
+const REQUEST: u8 = 5;
+
+/// The kind of a message sent by `SingleInstance::subscribe`.
+const SUBSCRIBE: u8 = 6;
+
+/// The kind of a message sent by `SingleInstance::forward_urls`.
+const URLS: u8 = 7;
+/// The kind of a message sent by `SingleInstance::forward_open`.
+const OPEN: u8 = 8;
+
+/// The first byte of the reply to a request the holder handled, telling it
+/// apart from one dropped without a reply, and the acknowledgement of a
+/// subscription.
+const HANDLED: u8 = 1;
+
+/// Another instance that tried to claim the name held by this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecondInstance {
+    /// The process ID of the other instance, taken from the connection on
+    /// linux, android, macos, ios and windows, and as it told elsewhere.
+    pub pid: u32,
+    /// The working directory of the other instance, empty if it had none.
+    pub cwd: PathBuf,
+    /// The environment variables the other instance forwarded, see
+    /// `SingleInstance::forward_env`.
+    pub env: Vec<(OsString, OsString)>,
+    /// The activation token the other instance was started with, sent with
+    /// `SingleInstance::activate`, which the holder needs to raise its window
+    /// on Wayland with `xdg_activation_v1`, or as the startup ID on X11.
+    pub activation_token: Option<String>,
+}
+
+type Handler = Box<dyn FnMut(SecondInstance) + Send>;
+type StateHandler = Box<dyn FnMut(SecondInstance) -> Vec<u8> + Send>;
+type RequestHandler = Box<dyn FnMut(SecondInstance, Vec<u8>) -> Vec<u8> + Send>;
+type ListHandler<T> = Box<dyn FnMut(SecondInstance, Vec<T>) + Send>;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+type ActionHandler = Box<dyn FnMut(SecondInstance, String, Option<OwnedValue>) + Send>;
+
+/// The handlers for each kind of message.
+#[derive(Default)]
+struct Handlers {
+    second_instance: Option<Handler>,
+    activate: Option<Handler>,
+    takeover: Option<Handler>,
+    handover: Option<StateHandler>,
+    request: Option<RequestHandler>,
+    urls: Option<ListHandler<String>>,
+    open: Option<ListHandler<PathBuf>>,
+    messages: Option<Sender<Message>>,
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    action: Option<ActionHandler>,
+    #[cfg(all(feature = "x11", target_os = "linux"))]
+    x11_window: Option<u32>,
+    #[cfg(all(feature = "appkit", target_os = "macos"))]
+    activate_app: bool,
+    /// The `HWND` to activate, as an address so that it can be sent.
+    #[cfg(windows)]
+    window: Option<usize>,
+    #[cfg(feature = "serde")]
+    json_lines: Option<Sender<JsonMessage>>,
+}
+
+impl Handlers {
+    /// Raises the window or app registered to be activated, if any.
+    fn raise(&self) {
+        #[cfg(all(feature = "x11", target_os = "linux"))]
+        if let Some(window) = self.x11_window {
+            // Intentionally discard any errors, there may be no X server or
+            // window manager.
+            let _ = x11::activate(window);
+        }
+        #[cfg(all(feature = "appkit", target_os = "macos"))]
+        if self.activate_app {
+            macos::activate_current();
+        }
+        #[cfg(windows)]
+        if let Some(window) = self.window {
+            windows::activate_window(window as HWND);
+        }
+    }
+}
+
+/// Passes requests arriving other than through the notifier, such as from the
+/// desktop over D-Bus, to its handlers.
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+#[derive(Clone)]
+pub(crate) struct Dispatcher(Arc<Mutex<Handlers>>);
+
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+impl Dispatcher {
+    fn handlers(&self) -> std::sync::MutexGuard<'_, Handlers> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn activate(&self, sender: SecondInstance) {
+        let mut handlers = self.handlers();
+        handlers.raise();
+        if let Some(ref mut handler) = handlers.activate {
+            handler(sender);
+        }
+    }
+
+    pub(crate) fn urls(&self, sender: SecondInstance, urls: Vec<String>) {
+        if let Some(ref mut handler) = self.handlers().urls {
+            handler(sender, urls);
+        }
+    }
+
+    pub(crate) fn open(&self, sender: SecondInstance, paths: Vec<PathBuf>) {
+        if let Some(ref mut handler) = self.handlers().open {
+            handler(sender, paths);
+        }
+    }
+
+    pub(crate) fn action(&self, sender: SecondInstance, name: String, parameter: Option<OwnedValue>) {
+        if let Some(ref mut handler) = self.handlers().action {
+            handler(sender, name, parameter);
+        }
+    }
+}
+
+/// What a connection to the notifier carries.
+enum Received {
+    /// A message of a kind.
+    Message(u8, Message),
+    /// A message not accepted, with the status to answer.
+    Rejected(Stream, u8),
+    /// JSON lines, starting with the bytes read so far.
+    #[cfg(feature = "serde")]
+    JsonLines(Vec<u8>, Stream),
+}
+
+/// Whether `SingleInstance::forward_urls` or `SingleInstance::forward_open`
+/// delivered what it forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardResult {
+    /// This instance holds the name, so nothing was forwarded and it should
+    /// handle them itself.
+    Single,
+    /// The holder received them and its handler returned.
+    Delivered,
+    /// The holder does not listen or has no handler for them.
+    NotDelivered,
+}
+
+/// An open file or socket passed along with a message.
+#[cfg(unix)]
+pub(crate) type Passed<'a> = BorrowedFd<'a>;
+#[cfg(windows)]
+pub(crate) type Passed<'a> = BorrowedHandle<'a>;
+
+/// Data sent by another instance with `SingleInstance::send`, received from
+/// `SingleInstance::messages`.
+pub struct Message {
+    /// The instance that sent the message.
+    pub sender: SecondInstance,
+    /// The data sent.
+    pub data: Vec<u8>,
+    /// The file descriptors sent with `SingleInstance::send_fds`.
+    #[cfg(unix)]
+    pub fds: Vec<OwnedFd>,
+    /// The handles sent with `SingleInstance::send_handles`.
+    #[cfg(windows)]
+    pub handles: Vec<OwnedHandle>,
+    stream: Stream,
+}
+
+impl Message {
+    /// Sends `data` back to the sender, which is otherwise answered with no
+    /// data once the message is dropped.
+    pub fn reply(mut self, data: &[u8]) -> io::Result<()> {
+        self.stream.write_all(data)
+    }
+}
+
+/// Threads accepting notifications, stopped when dropped.
+pub(crate) struct Notifier {
+    #[cfg(unix)]
+    name: String,
+    connections: Connections,
+    #[allow(dead_code)] // Only kept to stop accepting when dropped.
+    accepting: Accepting,
+    /// Accepting them on the socket shared by all users while other users are
+    /// allowed.
+    #[cfg(unix)]
+    accepting_shared: Mutex<Option<Accepting>>,
+    polled: Mutex<Option<Receiver<Message>>>,
+}
+
+/// A thread accepting connections, stopped when dropped.
+struct Accepting {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+/// The handlers and options of a stopped notifier, to start another one with.
+pub(crate) struct Settings {
+    shared: Arc<AtomicBool>,
+    max_message_size: Arc<AtomicUsize>,
+    attempts: Arc<AtomicU64>,
+    handlers: Arc<Mutex<Handlers>>,
+}
+
+/// Starts accepting notifications for `name`, dropped until handlers are set.
+pub(crate) fn listen(name: &str) -> io::Result<Notifier> {
+    listen_with(
+        name,
+        Settings {
+            shared: Arc::new(AtomicBool::new(false)),
+            max_message_size: Arc::new(AtomicUsize::new(DEFAULT_MAX_MESSAGE_SIZE)),
+            attempts: Arc::new(AtomicU64::new(0)),
+            handlers: Arc::new(Mutex::new(Handlers::default())),
+        },
+    )
+}
+
+/// Starts accepting notifications for `name` with the handlers and options
+/// of a stopped notifier.
+pub(crate) fn listen_with(name: &str, settings: Settings) -> io::Result<Notifier> {
+    let Settings {
+        shared,
+        max_message_size,
+        attempts,
+        handlers,
+    } = settings;
+    let connections = Connections {
+        shared,
+        max_message_size,
+        attempts,
+        handlers,
+        // Kept apart from the handlers, which may broadcast themselves.
+        subscribers: Arc::new(Mutex::new(Vec::new())),
+    };
+    let notifier = Notifier {
+        #[cfg(unix)]
+        name: name.to_owned(),
+        accepting: Accepting::start(bind(name)?, connections.clone(), false)?,
+        connections,
+        #[cfg(unix)]
+        accepting_shared: Mutex::new(None),
+        polled: Mutex::new(None),
+    };
+    if notifier.connections.shared.load(Ordering::SeqCst) {
+        notifier.allow_other_users(true)?;
+    }
+    Ok(notifier)
+}
+
+impl Accepting {
+    /// Accepts connections on `listener` on a thread of its own, served with
+    /// `connections`. Those on the socket shared by all users, if `shared`,
+    /// are only served while other users are allowed.
+    fn start(listener: Listener, connections: Connections, shared: bool) -> io::Result<Self> {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let accepting = Accepting {
+            addr: listener.local_addr()?,
+            stopped: stopped.clone(),
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                // Each connection is served on a thread of its own, so that
+                // neither a slow sender nor a slow handler holds up the others.
+                if let Ok(stream) = stream {
+                    let connections = connections.clone();
+                    thread::spawn(move || connections.serve(stream, shared));
+                }
+            }
+        });
+        Ok(accepting)
+    }
+}
+
+/// The handlers and options the threads serving connections share with the
+/// notifier.
+#[derive(Clone)]
+struct Connections {
+    shared: Arc<AtomicBool>,
+    max_message_size: Arc<AtomicUsize>,
+    attempts: Arc<AtomicU64>,
+    handlers: Arc<Mutex<Handlers>>,
+    subscribers: Arc<Mutex<Vec<Stream>>>,
+}
+
+impl Connections {
+    /// Receives a message on `stream`, accepted on the socket shared by all
+    /// users if `shared`, and hands it to its handler.
+    fn serve(&self, stream: Stream, shared: bool) {
+        // Intentionally discard connections from other users unless allowed,
+        // and those that fail, stall or send garbage.
+        let allowed = self.shared.load(Ordering::SeqCst);
+        if !(allowed || !shared && peer::is_same_user(&stream).unwrap_or(false)) {
+            return;
+        }
+        if stream.set_read_timeout(Some(RECEIVE_TIMEOUT)).is_err() {
+            return;
+        }
+        let max = self.max_message_size.load(Ordering::SeqCst);
+        let (kind, mut message) = match receive(stream, max) {
+            Ok(Received::Message(kind, message)) => (kind, message),
+            Ok(Received::Rejected(mut stream, status)) => {
+                let _ = stream.write_all(&[PROTOCOL_VERSION, status]);
+                // The rest is read for the sender to get the answer
+                // rather than the connection reset, up to a limit and for
+                // as long as it keeps sending.
+                let _ = io::copy(&mut (&mut stream).take(REJECTED_DRAIN_LIMIT), &mut io::sink());
+                return;
+            }
+            #[cfg(feature = "serde")]
+            Ok(Received::JsonLines(start, stream)) => {
+                let messages = self.handlers.lock().unwrap_or_else(|e| e.into_inner()).json_lines.clone();
+                // JSON lines stay connected, waiting for more as long as
+                // the sender likes.
+                if let (Some(messages), Ok(())) = (messages, stream.set_read_timeout(None)) {
+                    json_lines::serve(start, stream, messages);
+                }
+                return;
+            }
+            Err(_) => return,
+        };
+        // The sender may have stopped reading already.
+        let _ = message.stream.write_all(&[PROTOCOL_VERSION, ACCEPTED]);
+        let mut handlers = self.handlers.lock().unwrap_or_else(|e| e.into_inner());
+        let handler = match kind {
+            SECOND_INSTANCE => {
+                self.attempts.fetch_add(1, Ordering::SeqCst);
+                handlers.second_instance.as_mut()
+            }
+            ACTIVATE => {
+                let token = String::from_utf8(std::mem::take(&mut message.data)).ok();
+                message.sender.activation_token = token.filter(|token| !token.is_empty());
+                handlers.raise();
+                handlers.activate.as_mut()
+            }
+            TAKEOVER => handlers.takeover.as_mut(),
+            MESSAGE => {
+                if let Some(ref messages) = handlers.messages {
+                    let _ = messages.send(message);
+                }
+                return;
+            }
+            SUBSCRIBE => {
+                // Acknowledged while the subscribers are locked, so
+                // that nothing broadcast after the ack is missed.
+                let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+                if message.stream.write_all(&[HANDLED]).is_ok() {
+                    subscribers.push(message.stream);
+                }
+                return;
+            }
+            URLS => {
+                if let (Some(urls), Some(handler)) = (decode_list(&message.data), handlers.urls.as_mut()) {
+                    let urls = urls.into_iter().map(|url| String::from_utf8_lossy(url).into_owned()).collect();
+                    handler(message.sender.clone(), urls);
+                    let _ = message.reply(&[HANDLED]);
+                }
+                return;
+            }
+            OPEN => {
+                if let (Some(paths), Some(handler)) = (decode_list(&message.data), handlers.open.as_mut()) {
+                    let cwd = &message.sender.cwd;
+                    let paths = paths.into_iter().map(|path| resolve(cwd, &decode(path))).collect();
+                    handler(message.sender.clone(), paths);
+                    let _ = message.reply(&[HANDLED]);
+                }
+                return;
+            }
+            REQUEST => {
+                if let Some(ref mut request) = handlers.request {
+                    let data = std::mem::take(&mut message.data);
+                    let mut reply = vec![HANDLED];
+                    reply.extend(request(message.sender.clone(), data));
+                    let _ = message.reply(&reply);
+                }
+                return;
+            }
+            HANDOVER => {
+                // The state is sent before the holder is told to exit.
+                let sender = message.sender.clone();
+                if let Some(ref mut state) = handlers.handover {
+                    let state = state(sender.clone());
+                    let _ = message.reply(&state);
+                }
+                if let Some(ref mut takeover) = handlers.takeover {
+                    takeover(sender);
+                }
+                return;
+            }
+            _ => None,
+        };
+        if let Some(handler) = handler {
+            handler(message.sender);
+        }
+    }
+}
+
+impl Notifier {
+    /// Returns the handlers and options, to keep them once this is stopped.
+    pub(crate) fn settings(&self) -> Settings {
+        Settings {
+            shared: self.connections.shared.clone(),
+            max_message_size: self.connections.max_message_size.clone(),
+            attempts: self.connections.attempts.clone(),
+            handlers: self.connections.handlers.clone(),
+        }
+    }
+
+    #[cfg(all(feature = "x11", target_os = "linux"))]
+    pub(crate) fn set_x11_window(&self, window: u32) {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).x11_window = Some(window);
+    }
+
+    #[cfg(all(feature = "appkit", target_os = "macos"))]
+    pub(crate) fn activate_app_on_request(&self) {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).activate_app = true;
+    }
+
+    #[cfg(windows)]
+    pub(crate) fn set_window(&self, window: HWND) {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).window = Some(window as usize);
+    }
+
+    /// Returns how many instances failed to claim the name since listening.
+    pub(crate) fn attempt_count(&self) -> u64 {
+        self.connections.attempts.load(Ordering::SeqCst)
+    }
+
+    /// Sets the size of the largest message accepted from now on.
+    pub(crate) fn set_max_message_size(&self, size: usize) {
+        self.connections.max_message_size.store(size, Ordering::SeqCst);
+    }
+
+    /// Sets whether processes of other users are listened to.
+    pub(crate) fn allow_other_users(&self, allow: bool) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            let mut accepting = self.accepting_shared.lock().unwrap_or_else(|e| e.into_inner());
+            if !allow {
+                *accepting = None;
+            } else if accepting.is_none() {
+                *accepting = Some(Accepting::start(bind_shared(&self.name)?, self.connections.clone(), true)?);
+            }
+        }
+        self.connections.shared.store(allow, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub(crate) fn on_second_instance<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance) + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).second_instance = Some(Box::new(handler));
+    }
+
+    pub(crate) fn on_activate<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance) + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).activate = Some(Box::new(handler));
+    }
+
+    pub(crate) fn on_takeover<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance) + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).takeover = Some(Box::new(handler));
+    }
+
+    pub(crate) fn on_handover<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance) -> Vec<u8> + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).handover = Some(Box::new(handler));
+    }
+
+    pub(crate) fn on_request<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance, Vec<u8>) -> Vec<u8> + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).request = Some(Box::new(handler));
+    }
+
+    pub(crate) fn on_urls<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance, Vec<String>) + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).urls = Some(Box::new(handler));
+    }
+
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    pub(crate) fn on_action<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance, String, Option<OwnedValue>) + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).action = Some(Box::new(handler));
+    }
+
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    pub(crate) fn dispatcher(&self) -> Dispatcher {
+        Dispatcher(self.connections.handlers.clone())
+    }
+
+    pub(crate) fn on_open<F>(&self, handler: F)
+    where
+        F: FnMut(SecondInstance, Vec<PathBuf>) + Send + 'static,
+    {
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).open = Some(Box::new(handler));
+    }
+
+    /// Sends `data` to every subscribed instance, returning how many it was
+    /// sent to. Those that disconnected are dropped.
+    pub(crate) fn broadcast(&self, data: &[u8]) -> usize {
+        let mut frame = (data.len() as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(data);
+        let mut subscribers = self.connections.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain_mut(|stream| stream.write_all(&frame).is_ok());
+        subscribers.len()
+    }
+
+    /// Returns a receiver of the messages sent from now on, replacing the one
+    /// returned before.
+    pub(crate) fn messages(&self) -> Receiver<Message> {
+        let (sender, receiver) = mpsc::channel();
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).messages = Some(sender);
+        receiver
+    }
+
+    /// Returns a receiver of the messages sent from now on, forwarded from a
+    /// thread of its own, replacing the one returned before.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn async_messages(&self) -> tokio::sync::mpsc::Receiver<Message> {
+        let messages = self.messages();
+        let (sender, receiver) = tokio::sync::mpsc::channel(ASYNC_BUFFER);
+        thread::spawn(move || {
+            for message in messages {
+                if sender.blocking_send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Returns a message sent since the last call, if any, receiving them
+    /// from the first call on.
+    pub(crate) fn poll_message(&self) -> Option<Message> {
+        let mut polled = self.polled.lock().unwrap_or_else(|e| e.into_inner());
+        polled.get_or_insert_with(|| self.messages()).try_recv().ok()
+    }
+
+    /// Returns a receiver of the JSON lines sent from now on, replacing the
+    /// one returned before.
+    #[cfg(feature = "serde")]
+    pub(crate) fn json_lines(&self) -> Receiver<JsonMessage> {
+        let (sender, receiver) = mpsc::channel();
+        self.connections.handlers.lock().unwrap_or_else(|e| e.into_inner()).json_lines = Some(sender);
+        receiver
+    }
+}
+
+/// Tells the instance holding `name`, if it listens, that this one failed to
+/// claim it.
+pub(crate) fn send(name: &str) {
+    // Intentionally discard any errors, nobody may be listening.
+    let _ = connect(name).and_then(|mut stream| write(&mut stream, SECOND_INSTANCE, &[], &[]));
+    #[cfg(all(feature = "notify", any(target_os = "linux", target_os = "macos", windows)))]
+    desktop_notification::show();
+}
+
+/// Returns the activation token this process was started with, or an empty
+/// one.
+pub(crate) fn activation_token() -> String {
+    // Launchers pass the token for the launched application to activate its
+    // window with, on Wayland or with X11 startup notification.
+    env::var("XDG_ACTIVATION_TOKEN")
+        .or_else(|_| env::var("DESKTOP_STARTUP_ID"))
+        .unwrap_or_default()
+}
+
+/// Asks the instance holding `name` to be activated with `token`, returning
+/// `false` if it does not listen.
+pub(crate) fn activate(name: &str, token: &str) -> io::Result<bool> {
+    notify_holder(name, ACTIVATE, token.as_bytes())
+}
+
+/// Asks the instance holding `name` to exit, returning `false` if it does not
+/// listen.
+pub(crate) fn takeover(name: &str) -> io::Result<bool> {
+    notify_holder(name, TAKEOVER, &[])
+}
+
+fn notify_holder(name: &str, kind: u8, data: &[u8]) -> io::Result<bool> {
+    match connect_holder(name)? {
+        Some(mut stream) => write(&mut stream, kind, data, &[]).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Sends `data` and `passed` to the instance holding `name` and returns its
+/// reply, or `None` if it does not listen.
+pub(crate) fn message(name: &str, data: &[u8], passed: &[Passed]) -> io::Result<Option<Vec<u8>>> {
+    request(name, MESSAGE, data, passed)
+}
+
+/// Sends `urls` to the instance holding `name`, returning whether its handler
+/// received them.
+pub(crate) fn urls<I, S>(name: &str, urls: I) -> io::Result<ForwardResult>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let urls: Vec<S> = urls.into_iter().collect();
+    forward(name, URLS, urls.iter().map(|url| url.as_ref().as_bytes()))
+}
+
+/// Sends `paths` to the instance holding `name` to be opened, returning
+/// whether its handler received them.
+pub(crate) fn open<I, P>(name: &str, paths: I) -> io::Result<ForwardResult>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let paths: Vec<Vec<u8>> = paths.into_iter().map(|path| encode(path.as_ref())).collect();
+    forward(name, OPEN, paths.iter().map(Vec::as_slice))
+}
+
+/// Returns `path` made absolute against `cwd`, the working directory of the
+/// instance that sent it, and canonicalized if it exists.
+fn resolve(cwd: &Path, path: &Path) -> PathBuf {
+    let path = cwd.join(path);
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// Sends `items` as a message of `kind`, returning whether the holder's
+/// handler acknowledged it.
+fn forward<'a, I>(name: &str, kind: u8, items: I) -> io::Result<ForwardResult>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    Ok(match request(name, kind, &encode_list(items), &[])? {
+        Some(ref reply) if reply[..] == [HANDLED] => ForwardResult::Delivered,
+        _ => ForwardResult::NotDelivered,
+    })
+}
+
+/// Encodes `items` each preceded by its length.
+fn encode_list<'a, I>(items: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let mut data = Vec::new();
+    for item in items {
+        data.extend_from_slice(&(item.len() as u32).to_le_bytes());
+        data.extend_from_slice(item);
+    }
+    data
+}
+
+/// Decodes items encoded by `encode_list`, or `None` if malformed.
+fn decode_list(mut data: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut items = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 4 {
+            return None;
+        }
+        let len = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        if data.len() - 4 < len {
+            return None;
+        }
+        items.push(&data[4..4 + len]);
+        data = &data[4 + len..];
+    }
+    Some(items)
+}
+
+/// Subscribes to what the instance holding `name` broadcasts, returning a
+/// receiver of it, or `None` if it does not listen.
+pub(crate) fn subscribe(name: &str) -> io::Result<Option<Receiver<Vec<u8>>>> {
+    let mut stream = match connect_holder(name)? {
+        Some(stream) => stream,
+        None => return Ok(None),
+    };
+    write(&mut stream, SUBSCRIBE, &[], &[])?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut ack = Vec::new();
+    (&mut stream).take(3).read_to_end(&mut ack)?;
+    if answer(&ack)? != Some(&[HANDLED]) {
+        return Ok(None);
+    }
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Each broadcast is framed by its length, until the holder is gone.
+        let mut len = [0; 4];
+        while stream.read_exact(&mut len).is_ok() {
+            let mut data = vec![0; u32::from_le_bytes(len) as usize];
+            if stream.read_exact(&mut data).is_err() || sender.send(data).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(Some(receiver))
+}
+
+/// Sends `data` to the instance holding `name` as a request and returns the
+/// reply of its request handler, or `None` if it does not listen or has no
+/// request handler. Fails with `ErrorKind::TimedOut` once `timeout` passed.
+pub(crate) fn request_reply(name: &str, data: &[u8], timeout: Duration) -> io::Result<Option<Vec<u8>>> {
+    let deadline = Instant::now() + timeout;
+    let remaining = || match deadline.saturating_duration_since(Instant::now()) {
+        remaining if remaining.is_zero() => Err(io::Error::from(ErrorKind::TimedOut)),
+        remaining => Ok(remaining),
+    };
+    // Sockets report a timeout as `WouldBlock` on unix.
+    let timed_out = |e: io::Error| match e.kind() {
+        ErrorKind::WouldBlock => io::Error::from(ErrorKind::TimedOut),
+        _ => e,
+    };
+    let mut stream = match connect_holder(name)? {
+        Some(stream) => stream,
+        None => return Ok(None),
+    };
+    stream.set_write_timeout(Some(remaining()?))?;
+    write(&mut stream, REQUEST, data, &[]).map_err(timed_out)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        stream.set_read_timeout(Some(remaining()?))?;
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => reply.extend_from_slice(&buf[..len]),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(timed_out(e)),
+        }
+    }
+    Ok(match answer(&reply)?.and_then(<[u8]>::split_first) {
+        Some((&HANDLED, reply)) => Some(reply.to_vec()),
+        _ => None,
+    })
+}
+
+/// Asks the instance holding `name` for its state and to exit, returning the
+/// state or `None` if it does not listen.
+pub(crate) fn handover(name: &str) -> io::Result<Option<Vec<u8>>> {
+    request(name, HANDOVER, &[], &[])
+}
+
+fn request(name: &str, kind: u8, data: &[u8], passed: &[Passed]) -> io::Result<Option<Vec<u8>>> {
+    let mut stream = match connect_holder(name)? {
+        Some(stream) => stream,
+        None => return Ok(None),
+    };
+    write(&mut stream, kind, data, passed)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply)?;
+    Ok(answer(&reply)?.map(<[u8]>::to_vec))
+}
+
+/// Returns the reply within the holder's `answer` to a message, or `None` if
+/// it closed the connection without answering. Fails if it did not accept the
+/// message.
+fn answer(answer: &[u8]) -> io::Result<Option<&[u8]>> {
+    let (version, status, reply) = match *answer {
+        [] => return Ok(None),
+        [version, status, ref reply @ ..] if version == PROTOCOL_VERSION => (version, status, reply),
+        [version, ..] => {
+            let message = format!(
+                "the instance holding the name speaks protocol version {}, not {}",
+                version, PROTOCOL_VERSION
+            );
+            return Err(io::Error::new(ErrorKind::Unsupported, message));
+        }
+    };
+    match status {
+        ACCEPTED => Ok(Some(reply)),
+        TOO_LARGE => Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "the message is larger than the instance holding the name accepts",
+        )),
+        _ => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("protocol version {} rejected with status {}", version, status),
+        )),
+    }
+}
+
+/// Connects to the instance holding `name`, returning `None` if it does not
+/// listen.
+fn connect_holder(name: &str) -> io::Result<Option<Stream>> {
+    match connect(name) {
+        Ok(stream) => Ok(Some(stream)),
+        Err(ref e) if matches!(e.kind(), ErrorKind::ConnectionRefused | ErrorKind::NotFound | ErrorKind::TimedOut) => {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Sets the names of the environment variables sent along with every message
+/// from now on.
+pub(crate) fn forward_env(names: Vec<OsString>) {
+    *FORWARDED_ENV.lock().unwrap_or_else(|e| e.into_inner()) = names;
+}
+
+/// Writes a message of `kind`: the protocol version, the kind, the PID, the length of the working
+/// directory, the working directory, the length of the forwarded environment
+/// variables, their names and values, and the number of objects `passed`, on
+/// windows followed by their handles, and then `data`.
+fn write(stream: &mut Stream, kind: u8, data: &[u8], passed: &[Passed]) -> io::Result<()> {
+    let cwd = encode(&env::current_dir().unwrap_or_default());
+    let vars: Vec<Vec<u8>> = FORWARDED_ENV
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(|name| Some([encode(Path::new(name)), encode(Path::new(&env::var_os(name)?))]))
+        .flatten()
+        .collect();
+    let vars = encode_list(vars.iter().map(Vec::as_slice));
+    let mut message = vec![PROTOCOL_VERSION, kind];
+    message.extend_from_slice(&process::id().to_le_bytes());
+    message.extend_from_slice(&(cwd.len() as u32).to_le_bytes());
+    message.extend_from_slice(&cwd);
+    message.extend_from_slice(&(vars.len() as u32).to_le_bytes());
+    message.extend_from_slice(&vars);
+    message.extend_from_slice(&(passed.len() as u32).to_le_bytes());
+    #[cfg(windows)]
+    for handle in passed {
+        message.extend_from_slice(&(handle.as_raw_handle() as usize as u64).to_le_bytes());
+    }
+    message.extend_from_slice(data);
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    {
+        if !passed.is_empty() {
+            let sent = passing::send(stream, &message, passed)?;
+            return stream.write_all(&message[sent..]);
+        }
+    }
+    #[cfg(target_os = "cygwin")]
+    {
+        if !passed.is_empty() {
+            return Err(ErrorKind::Unsupported.into());
+        }
+    }
+    stream.write_all(&message)
+}
+
+/// Reads a message written by `write` until the sender stops writing, or the
+/// start of JSON lines. Messages of another protocol version or larger than
+/// `max` bytes are rejected.
+fn receive(mut stream: Stream, max: usize) -> io::Result<Received> {
+    let mut start = [0; 4096];
+    #[cfg(all(unix, not(target_os = "cygwin")))]
+    let (len, fds) = passing::recv(&stream, &mut start)?;
+    #[cfg(target_os = "cygwin")]
+    let (len, fds) = (stream.read(&mut start)?, Vec::new());
+    #[cfg(windows)]
+    let len = stream.read(&mut start)?;
+    #[cfg(feature = "serde")]
+    {
+        if start[..len].first() == Some(&b'{') {
+            return Ok(Received::JsonLines(start[..len].to_vec(), stream));
+        }
+    }
+    if start[..len].first() != Some(&PROTOCOL_VERSION) {
+        return Ok(Received::Rejected(stream, UNSUPPORTED_VERSION));
+    }
+    let mut message = start[..len].to_vec();
+    (&mut stream).take((max + 1).saturating_sub(len) as u64).read_to_end(&mut message)?;
+    if message.len() > max {
+        return Ok(Received::Rejected(stream, TOO_LARGE));
+    }
+
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "malformed message");
+    let mut rest = &message[..];
+    let mut take = |len: usize| {
+        if rest.len() < len {
+            return Err(invalid());
+        }
+        let (taken, remaining) = rest.split_at(len);
+        rest = remaining;
+        Ok(taken)
+    };
+    let u32_at = |bytes: &[u8]| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let kind = take(2)?[1];
+    let pid = u32_at(take(4)?);
+    // The ID the sender claims is only taken where it cannot be checked, and
+    // handles are only ever duplicated from the process that connected.
+    let pid = match peer::pid(&stream)? {
+        Some(peer) if peer != pid => return Err(invalid()),
+        Some(peer) => peer,
+        #[cfg(windows)]
+        None => return Err(invalid()),
+        #[cfg(unix)]
+        None => pid,
+    };
+    let cwd_len = u32_at(take(4)?) as usize;
+    let cwd = decode(take(cwd_len)?);
+    let vars_len = u32_at(take(4)?) as usize;
+    let vars = decode_list(take(vars_len)?).ok_or_else(invalid)?;
+    if vars.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let env = vars
+        .chunks(2)
+        .map(|var| (decode(var[0]).into_os_string(), decode(var[1]).into_os_string()))
+        .collect();
+    let count = u32_at(take(4)?) as usize;
+    #[cfg(unix)]
+    {
+        if fds.len() != count {
+            return Err(invalid());
+        }
+    }
+    #[cfg(windows)]
+    let handles = {
+        let values = take(count.checked_mul(8).ok_or_else(invalid)?)?;
+        let values: Vec<u64> = values
+            .chunks(8)
+            .map(|value| u64::from_le_bytes([
+                value[0], value[1], value[2], value[3], value[4], value[5], value[6], value[7],
+            ]))
+            .collect();
+        passing::duplicate(pid, &values)?
+    };
+    let data = rest.to_vec();
+    Ok(Received::Message(
+        kind,
+        Message {
+            sender: SecondInstance {
+                pid,
+                cwd,
+                env,
+                activation_token: None,
+            },
+            data,
+            #[cfg(unix)]
+            fds,
+            #[cfg(windows)]
+            handles,
+            stream,
+        },
+    ))
+}
+
+#[cfg(unix)]
+fn encode(path: &std::path::Path) -> Vec<u8> {
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn decode(path: &[u8]) -> PathBuf {
+    PathBuf::from(std::ffi::OsStr::from_bytes(path))
+}
+
+#[cfg(windows)]
+fn encode(path: &std::path::Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(windows)]
+fn decode(path: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(path).into_owned())
+}
+
+#[cfg(unix)]
+fn bind(name: &str) -> io::Result<Listener> {
+    bind_to(name, false)
+}
+
+#[cfg(unix)]
+fn bind_shared(name: &str) -> io::Result<Listener> {
+    bind_to(name, true)
+}
+
+#[cfg(unix)]
+fn connect(name: &str) -> io::Result<Stream> {
+    // Holders allowing other users also listen where those find them.
+    connect_to(name, false).or_else(|error| connect_to(name, true).map_err(|_| error))
+}
+
+/// Returns the socket address of the abstract name for `name`, see
+/// `endpoint`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn addr(name: &str, shared: bool) -> io::Result<SocketAddr> {
+    SocketAddr::from_abstract_name(&endpoint(name, shared)[1..])
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_to(name: &str, shared: bool) -> io::Result<Listener> {
+    Listener::bind_addr(&addr(name, shared)?)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn connect_to(name: &str, shared: bool) -> io::Result<Stream> {
+    Stream::connect_addr(&addr(name, shared)?)
+}
+
+/// Returns the abstract name notifications for `name` are sent to by this
+/// user, as usually written with a leading `@`.
+#[cfg_attr(not(any(feature = "serde", all(test, target_os = "linux"))), allow(dead_code))] // Only needed for `json_lines::address`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn address(name: &str) -> String {
+    endpoint(name, false)
+}
+
+/// Returns the abstract name the holder of `name` listens on for this user,
+/// or for all users if `shared`. Abstract names are seen by all users, so the
+/// user's ID tells those of holders run by different users apart.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn endpoint(name: &str, shared: bool) -> String {
+    if shared {
+        format!("@single-instance-{:08x}", fnv::hash(name))
+    } else {
+        format!("@single-instance-{}-{:08x}", unsafe { libc::geteuid() }, fnv::hash(name))
+    }
+}
+
+/// Returns the path of the socket notifications for `name` are sent to by
+/// this user.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))] // Only needed for `json_lines::address`.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub(crate) fn address(name: &str) -> String {
+    path(name, false).display().to_string()
+}
+
+/// Returns the path of the socket the holder of `name` listens on for this
+/// user, in `user_dir`, or for all users in the temporary directory if
+/// `shared`.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn path(name: &str, shared: bool) -> PathBuf {
+    let file = format!("single-instance-{:08x}.sock", fnv::hash(name));
+    if shared {
+        env::temp_dir().join(file)
+    } else {
+        user_dir_path().join(file)
+    }
+}
+
+/// Returns the directory of this user's sockets: `XDG_RUNTIME_DIR`, or else
+/// one of the user's own in the temporary directory.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn user_dir_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => env::temp_dir().join(format!("single-instance-{}", unsafe { libc::geteuid() })),
+    }
+}
+
+/// Returns the directory of this user's sockets, created if missing. Fails
+/// unless it belongs to the user and nobody else may enter it, as another user
+/// could otherwise have created it to listen in their place, and the holder
+/// relies on it to only be connected to by the user where `peer::is_same_user`
+/// cannot tell.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn user_dir() -> io::Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+
+    let dir = user_dir_path();
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(ref e) if e.kind() == ErrorKind::AlreadyExists => {}
+        result => result?,
+    }
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::geteuid() } || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} is not a directory only this user may enter", dir.display()),
+        ));
+    }
+    Ok(dir)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn bind_to(name: &str, shared: bool) -> io::Result<Listener> {
+    if !shared {
+        user_dir()?;
+    }
+    let path = path(name, shared);
+    let listener = match Listener::bind(&path) {
+        // A socket nobody accepts connections on was left behind by a holder
+        // that crashed. One that does belongs to another holder, as with
+        // `SingleInstance::with_limit`, and is left alone.
+        Err(ref e) if e.kind() == ErrorKind::AddrInUse && Stream::connect(&path).is_err() => {
+            fs::remove_file(&path)?;
+            Listener::bind(&path)
+        }
+        result => result,
+    }?;
+    if shared {
+        peer::share(&path)?;
+    }
+    Ok(listener)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn connect_to(name: &str, shared: bool) -> io::Result<Stream> {
+    if !shared {
+        user_dir()?;
+    }
+    Stream::connect(path(name, shared))
+}
+
+/// Returns the loopback address notifications for `name` are sent to, on a
+/// port in the dynamic range 49152 to 65535.
+#[cfg(windows)]
+fn addr(name: &str) -> SocketAddr {
+    let port = 49152 + (fnv::hash(&format!("{}.notify", name)) % 16384) as u16;
+    SocketAddr::from((Ipv4Addr::LOCALHOST, port))
+}
+
+/// Returns the loopback address notifications for `name` are sent to.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))] // Only needed for `json_lines::address`.
+#[cfg(windows)]
+pub(crate) fn address(name: &str) -> String {
+    addr(name).to_string()
+}
+
+#[cfg(windows)]
+fn bind(name: &str) -> io::Result<Listener> {
+    Listener::bind(addr(name))
+}
+
+#[cfg(windows)]
+fn connect(name: &str) -> io::Result<Stream> {
+    Stream::connect_timeout(&addr(name), CONNECT_TIMEOUT)
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        // Let subscribers know that nothing more will be broadcast.
+        for stream in self.connections.subscribers.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for Accepting {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake up the thread, which then stops accepting.
+        #[cfg(unix)]
+        let _ = Stream::connect_addr(&self.addr);
+        #[cfg(windows)]
+        let _ = Stream::connect_timeout(&self.addr, CONNECT_TIMEOUT);
+        #[cfg(unix)]
+        if let Some(path) = self.addr.as_pathname() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Passing open files and sockets to the instance holding a name.
+//!
+//! On unix the file descriptors are sent as `SCM_RIGHTS` ancillary data on
+//! the socket the message goes through. On windows the holder duplicates the
+//! handles out of the sending process, which waits for the holder's reply
+//! before closing them.
+
+use std::io;
+#[cfg(unix)]
+use std::mem;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+use std::ptr;
+#[cfg(windows)]
+use winapi::shared::minwindef::FALSE;
+#[cfg(windows)]
+use winapi::um::handleapi::DuplicateHandle;
+#[cfg(windows)]
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcess};
+#[cfg(windows)]
+use winapi::um::winnt::{DUPLICATE_SAME_ACCESS, HANDLE, PROCESS_DUP_HANDLE};
+
+/// The most file descriptors passed with one message.
+#[cfg(unix)]
+pub const MAX_FDS: usize = 64;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const SEND_FLAGS: libc::c_int = 0;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const RECV_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+const RECV_FLAGS: libc::c_int = 0;
+
+/// Sends the start of `bytes` along with `fds`, returning how many bytes were
+/// sent.
+#[cfg(unix)]
+pub fn send(stream: &UnixStream, bytes: &[u8], fds: &[BorrowedFd]) -> io::Result<usize> {
+    if fds.len() > MAX_FDS {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many file descriptors"));
+    }
+    let fds: Vec<RawFd> = fds.iter().map(AsRawFd::as_raw_fd).collect();
+    let size = mem::size_of_val(&fds[..]) as u32;
+    let mut control = vec![0u64; control_len(size)];
+    let mut iov = libc::iovec {
+        iov_base: bytes.as_ptr() as *mut _,
+        iov_len: bytes.len(),
+    };
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut _;
+        msg.msg_controllen = libc::CMSG_SPACE(size) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size) as _;
+        ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+        loop {
+            match libc::sendmsg(stream.as_raw_fd(), &msg, SEND_FLAGS) {
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                -1 => return Err(io::Error::last_os_error()),
+                sent => return Ok(sent as usize),
+            }
+        }
+    }
+}
+
+/// Receives the start of a message into `buf` along with the file
+/// descriptors sent with it, returning how many bytes were received.
+#[cfg(unix)]
+pub fn recv(stream: &UnixStream, buf: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+    let size = (MAX_FDS * mem::size_of::<RawFd>()) as u32;
+    let mut control = vec![0u64; control_len(size)];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut _,
+        iov_len: buf.len(),
+    };
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut _;
+        msg.msg_controllen = libc::CMSG_SPACE(size) as _;
+        let received = loop {
+            match libc::recvmsg(stream.as_raw_fd(), &mut msg, RECV_FLAGS) {
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+                -1 => return Err(io::Error::last_os_error()),
+                received => break received as usize,
+            }
+        };
+
+        let mut fds = Vec::new();
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let len = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / mem::size_of::<RawFd>();
+                for i in 0..len {
+                    fds.push(OwnedFd::from_raw_fd(ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "too many file descriptors"));
+        }
+        Ok((received, fds))
+    }
+}
+
+/// Returns the number of `u64`s holding `size` bytes of ancillary data, so
+/// that the buffer is aligned for the headers in it.
+#[cfg(unix)]
+fn control_len(size: u32) -> usize {
+    (unsafe { libc::CMSG_SPACE(size) } as usize).div_ceil(mem::size_of::<u64>())
+}
+
+/// Duplicates `handles` of the process `pid` into this one.
+#[cfg(windows)]
+pub fn duplicate(pid: u32, handles: &[u64]) -> io::Result<Vec<OwnedHandle>> {
+    if handles.is_empty() {
+        return Ok(Vec::new());
+    }
+    unsafe {
+        let process = OpenProcess(PROCESS_DUP_HANDLE, FALSE, pid);
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let process = OwnedHandle::from_raw_handle(process as _);
+        let mut duplicates = Vec::with_capacity(handles.len());
+        for &handle in handles {
+            let mut duplicate: HANDLE = ptr::null_mut();
+            if DuplicateHandle(
+                process.as_raw_handle() as HANDLE,
+                handle as usize as HANDLE,
+                GetCurrentProcess(),
+                &mut duplicate,
+                0,
+                FALSE,
+                DUPLICATE_SAME_ACCESS,
+            ) == 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            duplicates.push(OwnedHandle::from_raw_handle(duplicate as _));
+        }
+        Ok(duplicates)
+    }
+}
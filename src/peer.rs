@@ -0,0 +1,259 @@
+//! Checking who is on the other end of a connection to the notifier.
+//!
+//! Its process ID is taken from `SO_PEERCRED` on linux and android, from
+//! `LOCAL_PEERPID` on macos and ios, and from the TCP table on windows, and is
+//! not known elsewhere.
+//!
+//! On linux and android the user is taken from `SO_PEERCRED`, on macos and
+//! the BSDs from `getpeereid`. Elsewhere on unix the socket is in a directory
+//! only its owner may enter, which the file system enforces, and connections
+//! on the socket shared by all users are refused unless they are allowed. On windows the
+//! process owning the other end of the loopback connection is looked up in
+//! the TCP table and the user of its token compared to this process's.
+
+use std::io;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+use std::os::unix::io::AsRawFd;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+use std::path::Path;
+
+#[cfg(windows)]
+use std::mem;
+#[cfg(windows)]
+use std::net::{IpAddr, SocketAddr};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawHandle, FromRawHandle, OwnedHandle};
+#[cfg(windows)]
+use std::ptr;
+#[cfg(windows)]
+use winapi::shared::iprtrmib::TCP_TABLE_OWNER_PID_CONNECTIONS;
+#[cfg(windows)]
+use winapi::shared::minwindef::{DWORD, FALSE};
+#[cfg(windows)]
+use winapi::shared::tcpmib::MIB_TCPTABLE_OWNER_PID;
+#[cfg(windows)]
+use winapi::shared::winerror::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR};
+#[cfg(windows)]
+use winapi::shared::ws2def::AF_INET;
+#[cfg(windows)]
+use winapi::um::iphlpapi::GetExtendedTcpTable;
+#[cfg(windows)]
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcess, OpenProcessToken};
+#[cfg(windows)]
+use winapi::um::securitybaseapi::{EqualSid, GetTokenInformation};
+#[cfg(windows)]
+use winapi::um::winnt::{TokenUser, HANDLE, PROCESS_QUERY_LIMITED_INFORMATION, TOKEN_QUERY, TOKEN_USER};
+
+use notify::Stream;
+
+/// The credentials of a unix socket peer, as returned for `SO_PEERCRED`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+struct Ucred {
+    pid: libc::pid_t,
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+/// Returns whether the process on the other end of `stream` runs as the same
+/// user as this one.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn is_same_user(stream: &Stream) -> io::Result<bool> {
+    Ok(credentials(stream)?.uid == unsafe { libc::geteuid() })
+}
+
+/// Returns the ID of the process on the other end of `stream`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn pid(stream: &Stream) -> io::Result<Option<u32>> {
+    Ok(Some(credentials(stream)?.pid as u32))
+}
+
+/// Returns the credentials of the process on the other end of `stream`, as
+/// they were when it connected.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn credentials(stream: &Stream) -> io::Result<Ucred> {
+    let mut cred = Ucred { pid: 0, uid: 0, gid: 0 };
+    let mut len = std::mem::size_of::<Ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut Ucred as *mut _,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred)
+}
+
+/// Returns whether the process on the other end of `stream` runs as the same
+/// user as this one.
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly",
+    target_os = "openbsd",
+    target_os = "netbsd"
+))]
+pub(crate) fn is_same_user(stream: &Stream) -> io::Result<bool> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid == unsafe { libc::geteuid() })
+}
+
+/// Returns the ID of the process on the other end of `stream`.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub(crate) fn pid(stream: &Stream) -> io::Result<Option<u32>> {
+    let mut pid: libc::pid_t = 0;
+    let mut len = std::mem::size_of::<libc::pid_t>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_LOCAL,
+            libc::LOCAL_PEERPID,
+            &mut pid as *mut libc::pid_t as *mut _,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(pid as u32))
+}
+
+/// Returns the ID of the process on the other end of `stream`, which is not
+/// known here.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios"))))]
+pub(crate) fn pid(_stream: &Stream) -> io::Result<Option<u32>> {
+    Ok(None)
+}
+
+/// Returns whether the process on the other end of `stream` runs as the same
+/// user as this one, which the directory the socket is in ensures here. Never
+/// asked for connections on the socket shared by all users.
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))
+))]
+pub(crate) fn is_same_user(_stream: &Stream) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Lets everyone connect to the socket file at `path`.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub(crate) fn share(path: &Path) -> io::Result<()> {
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o777))
+}
+
+/// Returns the ID of the process on the other end of `stream`.
+#[cfg(windows)]
+pub(crate) fn pid(stream: &Stream) -> io::Result<Option<u32>> {
+    owning_pid(stream.peer_addr()?)
+}
+
+/// Returns whether the process on the other end of `stream` runs as the same
+/// user as this one.
+#[cfg(windows)]
+pub(crate) fn is_same_user(stream: &Stream) -> io::Result<bool> {
+    let pid = match owning_pid(stream.peer_addr()?)? {
+        Some(pid) => pid,
+        None => return Ok(false),
+    };
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid);
+        if process.is_null() {
+            // Processes of other users cannot be opened.
+            return Ok(false);
+        }
+        let process = OwnedHandle::from_raw_handle(process as _);
+        let theirs = token_user(process.as_raw_handle() as HANDLE)?;
+        let ours = token_user(GetCurrentProcess())?;
+        let theirs = &*(theirs.as_ptr() as *const TOKEN_USER);
+        let ours = &*(ours.as_ptr() as *const TOKEN_USER);
+        Ok(EqualSid(theirs.User.Sid, ours.User.Sid) != 0)
+    }
+}
+
+/// Returns the ID of the process whose end of a loopback connection is at
+/// `peer`.
+#[cfg(windows)]
+fn owning_pid(peer: SocketAddr) -> io::Result<Option<u32>> {
+    let ip = match peer.ip() {
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => return Ok(None),
+    };
+    let mut size: DWORD = 0;
+    let mut table: Vec<u64> = Vec::new();
+    loop {
+        let result = unsafe {
+            GetExtendedTcpTable(
+                table.as_mut_ptr() as *mut _,
+                &mut size,
+                FALSE,
+                AF_INET as u32,
+                TCP_TABLE_OWNER_PID_CONNECTIONS,
+                0,
+            )
+        };
+        match result {
+            NO_ERROR => break,
+            ERROR_INSUFFICIENT_BUFFER => table = vec![0; (size as usize).div_ceil(mem::size_of::<u64>())],
+            error => return Err(io::Error::from_raw_os_error(error as i32)),
+        }
+    }
+    let table = table.as_ptr() as *const MIB_TCPTABLE_OWNER_PID;
+    let rows = unsafe {
+        std::slice::from_raw_parts((*table).table.as_ptr(), (*table).dwNumEntries as usize)
+    };
+    // Addresses and ports are in network byte order.
+    Ok(rows
+        .iter()
+        .find(|row| {
+            row.dwLocalAddr == u32::from_ne_bytes(ip.octets())
+                && row.dwLocalPort == u32::from(peer.port().to_be())
+        })
+        .map(|row| row.dwOwningPid))
+}
+
+/// Returns the `TOKEN_USER` of `process`, in a buffer aligned for it.
+#[cfg(windows)]
+pub(crate) unsafe fn token_user(process: HANDLE) -> io::Result<Vec<u64>> {
+    let mut token = ptr::null_mut();
+    if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let token = OwnedHandle::from_raw_handle(token as _);
+    let mut len: DWORD = 0;
+    GetTokenInformation(token.as_raw_handle() as HANDLE, TokenUser, ptr::null_mut(), 0, &mut len);
+    let mut user = vec![0u64; (len as usize).div_ceil(mem::size_of::<u64>())];
+    if GetTokenInformation(token.as_raw_handle() as HANDLE, TokenUser, user.as_mut_ptr() as *mut _, len, &mut len) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(user)
+}
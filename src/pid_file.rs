@@ -0,0 +1,115 @@
+//! PID files, with detection of ones left behind by crashed instances.
+
+use libc::{kill, pid_t, EPERM};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// A PID file naming this process, removed when dropped unless it persists.
+pub struct PidFile {
+    path: PathBuf,
+    /// Whether the file is kept when dropped, see `set_persist`.
+    persist: bool,
+}
+
+/// Returns the PID file at `path` naming this process, or `None` if it names
+/// another running instance.
+///
+/// A PID file naming a process that no longer runs is replaced. With
+/// `same_executable` set, so is one naming a process running another
+/// executable, where `/proc` tells.
+pub fn try_create(path: &Path, same_executable: bool) -> io::Result<Option<PidFile>> {
+    if create(path)? {
+        return Ok(Some(PidFile {
+            path: path.to_owned(),
+            persist: false,
+        }));
+    }
+    if is_held(path, same_executable)? {
+        return Ok(None);
+    }
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    // Another instance replacing the stale file first wins.
+    if create(path)? {
+        Ok(Some(PidFile {
+            path: path.to_owned(),
+            persist: false,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Creates the PID file at `path`, returning `false` if it already exists.
+///
+/// The PID is written to a temporary file first which is then linked into
+/// place, so that other instances never read a partially written file.
+fn create(path: &Path) -> io::Result<bool> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}", process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    fs::write(&tmp, format!("{}\n", process::id()))?;
+    let linked = fs::hard_link(&tmp, path);
+    let _ = fs::remove_file(&tmp);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(ref e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns whether the PID file at `path` names a running instance.
+fn is_held(path: &Path, same_executable: bool) -> io::Result<bool> {
+    let pid = match read_pid(path) {
+        Ok(Some(pid)) => pid,
+        Ok(None) => return Ok(false),
+        // Removed by its instance in the meantime.
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if pid == process::id() as pid_t {
+        return Ok(true);
+    }
+    if unsafe { kill(pid, 0) } != 0 && io::Error::last_os_error().raw_os_error() != Some(EPERM) {
+        return Ok(false);
+    }
+    if same_executable {
+        if let (Ok(theirs), Ok(ours)) = (fs::read_link(format!("/proc/{}/exe", pid)), fs::read_link("/proc/self/exe")) {
+            return Ok(theirs == ours);
+        }
+    }
+    Ok(true)
+}
+
+/// Returns the PID in the file at `path`, or `None` if it holds none.
+fn read_pid(path: &Path) -> io::Result<Option<pid_t>> {
+    Ok(fs::read_to_string(path)?.trim().parse().ok().filter(|pid| *pid > 0))
+}
+
+impl PidFile {
+    /// Keeps the file when dropped, naming this process until another
+    /// instance finds it no longer runs and replaces it, if `persist` is set.
+    pub fn set_persist(&mut self, persist: bool) {
+        self.persist = persist;
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if self.persist {
+            return;
+        }
+        // Leave the file alone if another instance replaced it meanwhile.
+        if let Ok(Some(pid)) = read_pid(&self.path) {
+            if pid == process::id() as pid_t {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
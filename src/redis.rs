@@ -0,0 +1,263 @@
+//! Leases on a Redis server, for at most one instance across hosts.
+//!
+//! `RedisLock` is a `CustomBackend`: the instance name is claimed as a Redis
+//! key with `SET name token NX PX lease`, where the random token tells this
+//! instance's lease from others. While held the lease is renewed every third
+//! of its duration, and it is deleted when the instance is dropped. A crashed
+//! instance's lease expires on its own.
+//!
+//! ```no_run
+//! use single_instance::redis::RedisLock;
+//! use single_instance::SingleInstance;
+//! use std::time::Duration;
+//!
+//! let lock = RedisLock::new("redis.example.com:6379", Duration::from_secs(30));
+//! let instance = SingleInstance::with_custom_backend("my-app", Box::new(lock)).unwrap();
+//! ```
+
+use backend::CustomBackend;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Extends the lease `KEYS[1]` to `ARGV[2]` milliseconds if it is still held
+/// with the token `ARGV[1]`.
+const RENEW: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+                     return redis.call('pexpire', KEYS[1], ARGV[2]) else return 0 end";
+
+/// Deletes the lease `KEYS[1]` if it is still held with the token `ARGV[1]`.
+const RELEASE: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then \
+                       return redis.call('del', KEYS[1]) else return 0 end";
+
+/// Tokens handed out by this process, so that leases taken at the same time
+/// still differ.
+static TOKENS: AtomicUsize = AtomicUsize::new(0);
+
+/// A lease on a Redis server, passed to `SingleInstance::with_custom_backend`.
+pub struct RedisLock {
+    addr: String,
+    password: Option<String>,
+    lease: Duration,
+    held: Option<Lease>,
+}
+
+/// A lease taken by `RedisLock::acquire`.
+struct Lease {
+    key: String,
+    token: String,
+    conn: Arc<Mutex<Connection>>,
+    valid: Arc<AtomicBool>,
+    stop: Sender<()>,
+    renewer: JoinHandle<()>,
+}
+
+impl RedisLock {
+    /// Returns a lock on the Redis server at `addr`, e.g. `127.0.0.1:6379`,
+    /// taking leases of `lease`.
+    ///
+    /// The lease is how long the name stays claimed after its holder crashed
+    /// or lost its connection to the server.
+    pub fn new(addr: &str, lease: Duration) -> Self {
+        RedisLock {
+            addr: addr.to_owned(),
+            password: None,
+            lease,
+            held: None,
+        }
+    }
+
+    /// Authenticates with `password` on connecting.
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_owned());
+        self
+    }
+}
+
+impl CustomBackend for RedisLock {
+    fn acquire(&mut self, name: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mut conn = Connection::open(&self.addr, self.password.as_deref(), self.lease)?;
+        let token = token();
+        let lease = millis(self.lease);
+        let reply = conn.command(&["SET", name, &token, "NX", "PX", &lease])?;
+        if reply == Reply::Nil {
+            return Ok(false);
+        }
+
+        let conn = Arc::new(Mutex::new(conn));
+        let valid = Arc::new(AtomicBool::new(true));
+        let (stop, stopped) = mpsc::channel();
+        let renewer = {
+            let renewal = Renewal {
+                addr: self.addr.clone(),
+                password: self.password.clone(),
+                lease: self.lease,
+                key: name.to_owned(),
+                token: token.clone(),
+                conn: conn.clone(),
+                valid: valid.clone(),
+            };
+            thread::spawn(move || {
+                let mut renewed = Instant::now();
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(renewal.lease / 3) {
+                    match renewal.renew() {
+                        Ok(true) => renewed = Instant::now(),
+                        Ok(false) => break,
+                        // Keep trying until the lease may have expired.
+                        Err(_) if renewed.elapsed() < renewal.lease => {}
+                        Err(_) => break,
+                    }
+                }
+                renewal.valid.store(false, Ordering::SeqCst);
+            })
+        };
+        self.held = Some(Lease {
+            key: name.to_owned(),
+            token,
+            conn,
+            valid,
+            stop,
+            renewer,
+        });
+        Ok(true)
+    }
+
+    fn is_held(&self) -> bool {
+        self.held
+            .as_ref()
+            .is_some_and(|lease| lease.valid.load(Ordering::SeqCst))
+    }
+
+    fn release(&mut self) {
+        if let Some(lease) = self.held.take() {
+            drop(lease.stop);
+            let _ = lease.renewer.join();
+            let mut conn = lease.conn.lock().unwrap_or_else(|e| e.into_inner());
+            // Intentionally discard any errors, the lease then expires.
+            let _ = conn.command(&["EVAL", RELEASE, "1", &lease.key, &lease.token]);
+        }
+    }
+}
+
+/// What the renewer thread of a lease needs.
+struct Renewal {
+    addr: String,
+    password: Option<String>,
+    lease: Duration,
+    key: String,
+    token: String,
+    conn: Arc<Mutex<Connection>>,
+    valid: Arc<AtomicBool>,
+}
+
+impl Renewal {
+    /// Extends the lease, returning `false` if it was lost. The connection is
+    /// reopened if it failed.
+    fn renew(&self) -> io::Result<bool> {
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let args = ["EVAL", RENEW, "1", &self.key, &self.token, &millis(self.lease)];
+        let reply = match conn.command(&args) {
+            Ok(reply) => reply,
+            Err(_) => {
+                *conn = Connection::open(&self.addr, self.password.as_deref(), self.lease)?;
+                conn.command(&args)?
+            }
+        };
+        Ok(reply == Reply::Integer(1))
+    }
+}
+
+/// A reply of the server.
+#[derive(Debug, PartialEq, Eq)]
+enum Reply {
+    Status(String),
+    Integer(i64),
+    Bulk(Vec<u8>),
+    Nil,
+}
+
+/// A connection speaking the Redis protocol.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl Connection {
+    /// Connects to `addr`, authenticating with `password` if given. Calls
+    /// time out after `timeout`.
+    fn open(addr: &str, password: Option<&str>, timeout: Duration) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let mut conn = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+        };
+        if let Some(password) = password {
+            conn.command(&["AUTH", password])?;
+        }
+        Ok(conn)
+    }
+
+    /// Sends the command `args` and returns its reply. Error replies are
+    /// returned as errors.
+    fn command(&mut self, args: &[&str]) -> io::Result<Reply> {
+        let mut request = format!("*{}\r\n", args.len());
+        for arg in args {
+            request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        self.stream.write_all(request.as_bytes())?;
+        self.reply()
+    }
+
+    fn reply(&mut self) -> io::Result<Reply> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        let line = line.trim_end_matches("\r\n");
+        let mut chars = line.chars();
+        let kind = chars.next();
+        let rest = chars.as_str();
+        let number = || rest.parse::<i64>().map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+        match kind {
+            Some('+') => Ok(Reply::Status(rest.to_owned())),
+            Some('-') => Err(io::Error::other(rest.to_owned())),
+            Some(':') => Ok(Reply::Integer(number()?)),
+            Some('$') => match number()? {
+                -1 => Ok(Reply::Nil),
+                len => {
+                    // The data is followed by a line break.
+                    let mut data = vec![0; len as usize + 2];
+                    self.reader.read_exact(&mut data)?;
+                    data.truncate(len as usize);
+                    Ok(Reply::Bulk(data))
+                }
+            },
+            _ => Err(io::Error::new(ErrorKind::InvalidData, format!("unexpected reply {:?}", line))),
+        }
+    }
+}
+
+/// Returns a token unlikely to be used by any other instance on any host.
+fn token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or(0);
+    format!(
+        "{}-{}-{}",
+        process::id(),
+        nanos,
+        TOKENS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn millis(duration: Duration) -> String {
+    duration.as_millis().max(1).to_string()
+}
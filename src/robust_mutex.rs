@@ -0,0 +1,153 @@
+//! Robust process shared mutexes in POSIX shared memory.
+
+use libc::{
+    c_void, close, ftruncate, mmap, munmap, pthread_mutex_consistent, pthread_mutex_init, pthread_mutex_t,
+    pthread_mutex_trylock, pthread_mutex_unlock, pthread_mutexattr_destroy, pthread_mutexattr_init,
+    pthread_mutexattr_setpshared, pthread_mutexattr_setrobust, pthread_mutexattr_t, shm_open, EBUSY, EOWNERDEAD,
+    MAP_FAILED, MAP_SHARED, O_CREAT, O_RDWR, PROT_READ, PROT_WRITE, PTHREAD_MUTEX_ROBUST, PTHREAD_PROCESS_SHARED,
+};
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+
+const UNINITIALIZED: u32 = 0;
+const INITIALIZING: u32 = 1;
+const READY: u32 = 2;
+
+/// The layout of the shared memory object.
+#[repr(C)]
+struct Shared {
+    state: AtomicU32,
+    mutex: pthread_mutex_t,
+}
+
+/// A locked mutex, unlocked when dropped.
+pub struct RobustMutex {
+    shared: *mut Shared,
+    fd: i32,
+    owner_died: bool,
+}
+
+unsafe impl Send for RobustMutex {}
+unsafe impl Sync for RobustMutex {}
+
+impl RobustMutex {
+    /// Returns whether the previous owner exited without unlocking the mutex.
+    pub fn owner_died(&self) -> bool {
+        self.owner_died
+    }
+}
+
+/// Returns the mutex in the shared memory object `name` locked, creating and
+/// initializing it if needed, or `None` if another instance holds it.
+///
+/// A leading `/` is added to `name` if missing. The mutex belongs to the
+/// calling thread: it must be dropped there, and is given up as if its owner
+/// crashed when that thread exits.
+pub fn try_lock(name: &str) -> io::Result<Option<RobustMutex>> {
+    let name = if name.starts_with('/') {
+        CString::new(name)?
+    } else {
+        CString::new(format!("/{}", name))?
+    };
+
+    unsafe {
+        let fd = shm_open(name.as_ptr(), O_RDWR | O_CREAT, 0o600);
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let shared = match map(fd) {
+            Ok(shared) => shared,
+            Err(e) => {
+                close(fd);
+                return Err(e);
+            }
+        };
+
+        let mutex = &mut (*shared).mutex;
+        let owner_died = match pthread_mutex_trylock(mutex) {
+            0 => false,
+            EOWNERDEAD => {
+                pthread_mutex_consistent(mutex);
+                true
+            }
+            rc => {
+                unmap(shared, fd);
+                return if rc == EBUSY {
+                    Ok(None)
+                } else {
+                    Err(io::Error::from_raw_os_error(rc))
+                };
+            }
+        };
+        Ok(Some(RobustMutex {
+            shared,
+            fd,
+            owner_died,
+        }))
+    }
+}
+
+/// Maps the shared memory object `fd`, initializing the mutex if no other
+/// process did yet.
+unsafe fn map(fd: i32) -> io::Result<*mut Shared> {
+    let size = mem::size_of::<Shared>();
+    // Growing the object zeroes it, which leaves the state uninitialized.
+    if ftruncate(fd, size as _) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let addr = mmap(ptr::null_mut(), size, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    if addr == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    let shared = addr as *mut Shared;
+
+    let state = &(*shared).state;
+    if state
+        .compare_exchange(UNINITIALIZED, INITIALIZING, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok()
+    {
+        let mut attr: pthread_mutexattr_t = mem::zeroed();
+        pthread_mutexattr_init(&mut attr);
+        pthread_mutexattr_setpshared(&mut attr, PTHREAD_PROCESS_SHARED);
+        pthread_mutexattr_setrobust(&mut attr, PTHREAD_MUTEX_ROBUST);
+        let rc = pthread_mutex_init(&mut (*shared).mutex, &attr);
+        pthread_mutexattr_destroy(&mut attr);
+        if rc != 0 {
+            state.store(UNINITIALIZED, Ordering::Release);
+            munmap(addr, size);
+            return Err(io::Error::from_raw_os_error(rc));
+        }
+        state.store(READY, Ordering::Release);
+    } else {
+        while state.load(Ordering::Acquire) != READY {
+            thread::yield_now();
+        }
+    }
+    Ok(shared)
+}
+
+unsafe fn unmap(shared: *mut Shared, fd: i32) {
+    munmap(shared as *mut c_void, mem::size_of::<Shared>());
+    close(fd);
+}
+
+/// The shared memory object holding the mutex.
+impl AsRawFd for RobustMutex {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for RobustMutex {
+    fn drop(&mut self) {
+        unsafe {
+            pthread_mutex_unlock(&mut (*self.shared).mutex);
+            unmap(self.shared, self.fd);
+        }
+    }
+}
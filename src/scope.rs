@@ -0,0 +1,332 @@
+//! Whom an instance name is unique among.
+//!
+//! A name is unique wherever the mechanism claiming it is seen: within the
+//! session on windows, and on unix among all processes seeing the same
+//! abstract socket or lock file. A `Scope` narrows or widens that by deriving
+//! the name claimed from the one given, see `Scope::apply`, so that e.g. two
+//! users running the same application do not get in each other's way:
+//!
+//! ```no_run
+//! use single_instance::{Scope, SingleInstance};
+//!
+//! let instance = SingleInstance::with_scope("my-app", Scope::User).unwrap();
+//! ```
+
+use backend::Backend;
+use error::Result;
+#[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+use error::SingleInstanceError;
+use fnv;
+use std::env;
+use std::fs;
+use std::io;
+#[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+use std::path::Path;
+use std::path::PathBuf;
+use SingleInstance;
+
+#[cfg(target_os = "cygwin")]
+use cygwin::{GetCurrentProcessId, ProcessIdToSessionId};
+#[cfg(target_os = "windows")]
+use winapi::um::processthreadsapi::{GetCurrentProcess, GetCurrentProcessId, ProcessIdToSessionId};
+#[cfg(windows)]
+use peer;
+#[cfg(windows)]
+use std::ptr;
+#[cfg(windows)]
+use widestring::U16CStr;
+#[cfg(windows)]
+use winapi::shared::sddl::ConvertSidToStringSidW;
+#[cfg(windows)]
+use winapi::um::winbase::LocalFree;
+#[cfg(windows)]
+use winapi::um::winnt::TOKEN_USER;
+
+/// Whom a name is unique among, passed to `SingleInstance::with_scope`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// One instance per user. The name is suffixed with the user's ID on
+    /// unix, so that each user gets a lock file of their own in a shared
+    /// directory such as `/tmp` instead of all but its owner failing with
+    /// `EACCES`, and with the user's SID on windows.
+    User,
+    /// One instance on the machine, across all users and sessions, e.g. for
+    /// services. The name is put into the `Global\` namespace on windows,
+    /// replacing a `Local\` prefix, and a mutex there that another user's
+    /// process created and denies access to counts as held. On unix it names
+    /// a lock file in `/run/lock` or `/var/lock` where they exist and in
+    /// `/tmp` elsewhere, claimed with `Backend::FileLock` unless the builder
+    /// is given other backends. Its creator makes it writable for all users,
+    /// and claiming it fails with `SingleInstanceError::PermissionDenied` if
+    /// the process may not create or open it there. Android and ios apps are
+    /// sandboxed, so names are left as they are.
+    Machine,
+    /// One instance per login session, e.g. for each user logged in through
+    /// Terminal Services or fast user switching on windows, where names are
+    /// only local to the session within the `Local\` namespace, unlike those
+    /// in the `Global\` one or the ports and files of other backends. The
+    /// name is suffixed with the ID of the session: the windows session, or
+    /// on unix the logind session in `XDG_SESSION_ID`, or else the audit
+    /// session on linux and the process session, as `getsid` returns it, of
+    /// a terminal elsewhere.
+    Session,
+    /// One instance per display, e.g. for a user running the application on
+    /// both a local and a remote X server. The name is suffixed with the
+    /// Wayland display in `WAYLAND_DISPLAY`, or else the X display in
+    /// `DISPLAY` without its screen number, and left as it is without either,
+    /// as usually on windows and macos.
+    Display,
+    /// One instance per directory, e.g. per project for a language server or
+    /// a project daemon. The name is suffixed with a hash of the canonical
+    /// path of the directory, so that paths reaching it through symbolic
+    /// links or `..` agree, and claiming it fails if it does not exist.
+    Path(PathBuf),
+    /// One instance per container, e.g. per Docker or Podman container even
+    /// if they share `/tmp` through a volume. The name is suffixed with the
+    /// ID of the mount namespace on linux and android, as which containers
+    /// tell one another and the host apart, and left as it is elsewhere.
+    ///
+    /// Without it, lock files are shared by all containers seeing them
+    /// through a volume, such as one mounted from the host for one instance
+    /// on the whole host, while abstract sockets are shared by those sharing
+    /// the network namespace, which containers only do with host networking.
+    Container,
+    /// One instance per host, e.g. for home directories shared over NFS,
+    /// where lock files are otherwise shared by all hosts mounting them, if
+    /// NFS locks them at all, and one instance running on any of them makes
+    /// all others fail. The name is suffixed with the host name. Without it,
+    /// such names stay shared, for one instance across all hosts with
+    /// working NFS locks.
+    Host,
+}
+
+impl Scope {
+    /// Returns the name claimed for `name` within this scope.
+    pub fn apply(&self, name: &str) -> Result<String> {
+        match *self {
+            Scope::User => Ok(format!("{}-{}", name, user_id()?)),
+            Scope::Machine => machine_name(name),
+            Scope::Session => Ok(format!("{}-session-{}", name, session_id()?)),
+            Scope::Display => Ok(match display() {
+                Some(display) => format!("{}-display-{}", name, display),
+                None => name.to_owned(),
+            }),
+            Scope::Path(ref path) => {
+                let path = fs::canonicalize(path)?;
+                Ok(format!("{}-{:08x}", name, fnv::hash(&path.to_string_lossy())))
+            }
+            Scope::Host => Ok(format!("{}-host-{}", name, host_name()?)),
+            Scope::Container => Ok(match mount_namespace()? {
+                Some(namespace) => format!("{}-mnt-{}", name, namespace),
+                None => name.to_owned(),
+            }),
+        }
+    }
+
+    /// Returns the backend a name within this scope is claimed with unless
+    /// another one is given: machine-wide names on unix are lock files,
+    /// whatever the platform's default.
+    pub(crate) fn backend(&self) -> Backend {
+        match *self {
+            #[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+            Scope::Machine => Backend::FileLock,
+            _ => Backend::Default,
+        }
+    }
+
+    /// Returns `instance`, created for `name` as returned by `apply`, with
+    /// what this scope needs set up, or the error it failed with in terms of
+    /// this scope.
+    #[cfg_attr(
+        not(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios")))),
+        allow(unused_variables)
+    )]
+    pub(crate) fn claimed(&self, name: &str, instance: Result<SingleInstance>) -> Result<SingleInstance> {
+        match (self, instance) {
+            #[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+            (&Scope::Machine, Err(SingleInstanceError::Io(ref error))) if error.kind() == io::ErrorKind::PermissionDenied => {
+                Err(SingleInstanceError::PermissionDenied(format!("cannot lock {} machine-wide: {}", name, error)))
+            }
+            #[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+            (&Scope::Machine, Ok(instance)) => {
+                if let (true, Some(path)) = (instance.is_single(), instance.lock_path()) {
+                    use std::os::unix::fs::PermissionsExt;
+
+                    // Let other users open the file to find it locked, which
+                    // they cannot with a write lock otherwise. Intentionally
+                    // discard any errors, e.g. for a file owned by another
+                    // user, which then decides.
+                    let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o666));
+                }
+                Ok(instance)
+            }
+            (_, instance) => instance,
+        }
+    }
+}
+
+impl SingleInstance {
+    /// Returns a new SingleInstance object claiming `name` within `scope`,
+    /// see `Scope::apply`.
+    pub fn with_scope(name: &str, scope: Scope) -> Result<Self> {
+        let name = scope.apply(name)?;
+        scope.claimed(&name, Self::with_backend(&name, scope.backend()))
+    }
+}
+
+/// Returns the ID of the windows session this process runs in.
+#[cfg(any(target_os = "windows", target_os = "cygwin"))]
+fn session_id() -> io::Result<u32> {
+    let mut session = 0;
+    if unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(session)
+}
+
+/// Returns the ID of the login session this process runs in.
+#[cfg(all(unix, not(target_os = "cygwin")))]
+fn session_id() -> io::Result<String> {
+    if let Some(id) = env::var_os("XDG_SESSION_ID").filter(|id| !id.is_empty()) {
+        return Ok(id.to_string_lossy().into_owned());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // Processes outside of an audit session read `u32::MAX`.
+        if let Ok(id) = fs::read_to_string("/proc/self/sessionid") {
+            if id.trim().parse::<u32>().is_ok_and(|id| id != u32::MAX) {
+                return Ok(id.trim().to_owned());
+            }
+        }
+    }
+    match unsafe { libc::getsid(0) } {
+        -1 => Err(io::Error::last_os_error()),
+        sid => Ok(sid.to_string()),
+    }
+}
+
+/// Returns `value` with any character but alphanumerics, `-` and `.`
+/// replaced by `_`, to suffix names that are paths with.
+fn path_safe(value: &str) -> String {
+    value.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect()
+}
+
+/// Returns the name of this host, see `path_safe`.
+#[cfg(unix)]
+fn host_name() -> io::Result<String> {
+    let mut name = [0u8; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr() as *mut _, name.len()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+    Ok(path_safe(&String::from_utf8_lossy(&name[..len])))
+}
+
+/// Returns the DNS name of this host, see `path_safe`.
+#[cfg(windows)]
+fn host_name() -> io::Result<String> {
+    use winapi::um::sysinfoapi::{ComputerNameDnsHostname, GetComputerNameExW};
+
+    let mut len = 0;
+    unsafe { GetComputerNameExW(ComputerNameDnsHostname, ptr::null_mut(), &mut len) };
+    let mut name = vec![0u16; len as usize];
+    if unsafe { GetComputerNameExW(ComputerNameDnsHostname, name.as_mut_ptr(), &mut len) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(path_safe(&String::from_utf16_lossy(&name[..len as usize])))
+}
+
+/// Returns the display this process shows its windows on, see `path_safe`.
+fn display() -> Option<String> {
+    let display = match env::var("WAYLAND_DISPLAY") {
+        Ok(display) if !display.is_empty() => display,
+        _ => {
+            let display = env::var("DISPLAY").ok().filter(|display| !display.is_empty())?;
+            // `:0` and `:0.1` are screens of the same display.
+            match display.rfind(':') {
+                Some(colon) => match display[colon..].find('.') {
+                    Some(dot) => display[..colon + dot].to_owned(),
+                    None => display,
+                },
+                None => display,
+            }
+        }
+    };
+    Some(path_safe(&display))
+}
+
+/// Returns the inode of the mount namespace this process runs in, such as
+/// `4026531841` for `mnt:[4026531841]`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mount_namespace() -> io::Result<Option<String>> {
+    let link = fs::read_link("/proc/self/ns/mnt")?;
+    let link = link.to_string_lossy();
+    match link.strip_prefix("mnt:[").and_then(|link| link.strip_suffix(']')) {
+        Some(inode) => Ok(Some(inode.to_owned())),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected namespace {}", link))),
+    }
+}
+
+/// Returns `None`, as containers are not told apart by namespaces here.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn mount_namespace() -> io::Result<Option<String>> {
+    Ok(None)
+}
+
+/// Returns `name` in the `Global\` namespace.
+#[cfg(any(target_os = "windows", target_os = "cygwin"))]
+fn machine_name(name: &str) -> Result<String> {
+    let name = name.strip_prefix("Local\\").or_else(|| name.strip_prefix("Global\\")).unwrap_or(name);
+    Ok(format!("Global\\{}", name))
+}
+
+/// Returns the path of the lock file for `name` in the system-wide lock
+/// directory.
+#[cfg(all(unix, not(any(target_os = "cygwin", target_os = "android", target_os = "ios"))))]
+fn machine_name(name: &str) -> Result<String> {
+    if name.contains('/') {
+        return Err(SingleInstanceError::InvalidName(format!("a machine-wide name is no path: {}", name)));
+    }
+    let dir = ["/run/lock", "/var/lock"]
+        .iter()
+        .map(Path::new)
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| Path::new("/tmp"));
+    Ok(format!("{}/{}.lock", dir.display(), name))
+}
+
+/// Returns `name` as it is, which sandboxed apps claim for themselves.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+fn machine_name(name: &str) -> Result<String> {
+    Ok(name.to_owned())
+}
+
+/// Returns the ID of the user running this process.
+#[cfg(all(unix, not(target_os = "cygwin")))]
+fn user_id() -> io::Result<String> {
+    Ok(unsafe { libc::getuid() }.to_string())
+}
+
+/// Returns the ID of the user running this process.
+#[cfg(target_os = "cygwin")]
+fn user_id() -> io::Result<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(std::fs::metadata("/proc/self")?.uid().to_string())
+}
+
+/// Returns the SID of the user running this process.
+#[cfg(windows)]
+fn user_id() -> io::Result<String> {
+    unsafe {
+        let user = peer::token_user(GetCurrentProcess())?;
+        let user = &*(user.as_ptr() as *const TOKEN_USER);
+        let mut sid = ptr::null_mut();
+        if ConvertSidToStringSidW(user.User.Sid, &mut sid) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let string = U16CStr::from_ptr_str(sid).to_string_lossy();
+        LocalFree(sid as _);
+        Ok(string)
+    }
+}
@@ -0,0 +1,52 @@
+//! POSIX named semaphores, shared by the unix backends.
+
+use libc::{sem_close, sem_open, sem_post, sem_t, sem_trywait, EAGAIN, O_CREAT, SEM_FAILED};
+use std::ffi::CString;
+use std::io;
+use std::os::raw::c_uint;
+
+/// A taken semaphore, posted back when dropped.
+pub struct Semaphore {
+    sem: *mut sem_t,
+}
+
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+/// Returns the named semaphore `name` taken, creating it with a count of one
+/// if needed, or `None` if another instance holds it.
+///
+/// A leading `/` is added to `name` if missing.
+pub fn try_acquire(name: &str) -> io::Result<Option<Semaphore>> {
+    let name = if name.starts_with('/') {
+        CString::new(name)?
+    } else {
+        CString::new(format!("/{}", name))?
+    };
+
+    unsafe {
+        let sem = sem_open(name.as_ptr(), O_CREAT, 0o600 as c_uint, 1 as c_uint);
+        if sem == SEM_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        if sem_trywait(sem) != 0 {
+            let err = io::Error::last_os_error();
+            sem_close(sem);
+            return if err.raw_os_error() == Some(EAGAIN) {
+                Ok(None)
+            } else {
+                Err(err)
+            };
+        }
+        Ok(Some(Semaphore { sem }))
+    }
+}
+
+impl Drop for Semaphore {
+    fn drop(&mut self) {
+        unsafe {
+            sem_post(self.sem);
+            sem_close(self.sem);
+        }
+    }
+}
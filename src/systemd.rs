@@ -0,0 +1,35 @@
+//! Runtime directories managed by systemd.
+//!
+//! A service with `RuntimeDirectory=` set gets a directory under `/run` which
+//! systemd creates before starting it and removes once it stopped, and whose
+//! path is passed in `$RUNTIME_DIRECTORY`. Lock files kept there neither pile
+//! up in `/tmp` nor survive the service being restarted.
+
+use std::env;
+use std::path::PathBuf;
+
+/// Returns the directory `Backend::Systemd` keeps lock files in: the service's
+/// first runtime directory, otherwise the user's runtime directory from
+/// `$XDG_RUNTIME_DIR`, otherwise `/run/lock`.
+pub fn runtime_dir() -> PathBuf {
+    if let Some(dirs) = env::var_os("RUNTIME_DIRECTORY") {
+        // Several directories are separated by colons.
+        if let Some(dir) = env::split_paths(&dirs).find(|dir| !dir.as_os_str().is_empty()) {
+            return dir;
+        }
+    }
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/run/lock"))
+}
+
+/// Returns the lock file path for `name`: `name` itself if it contains a path
+/// separator, otherwise `name` inside `runtime_dir`.
+pub fn lock_path(name: &str) -> PathBuf {
+    if name.contains('/') {
+        PathBuf::from(name)
+    } else {
+        runtime_dir().join(name)
+    }
+}
@@ -0,0 +1,61 @@
+//! System V semaphores, shared by the unix backends.
+
+use fnv;
+use libc::{c_short, key_t, sembuf, semget, semop, EAGAIN, IPC_CREAT, IPC_NOWAIT, SEM_UNDO};
+use std::io;
+
+/// A taken semaphore, given back when dropped.
+pub struct SysvSemaphore {
+    id: i32,
+}
+
+/// Returns the System V semaphore keyed by `name` taken, creating it if
+/// needed, or `None` if another instance holds it.
+///
+/// The key is a hash of `name`, so that every program derives the same one.
+///
+/// The semaphore counts its holders: taking it waits for zero and increments
+/// it in one operation. The increment is made with `SEM_UNDO`, so the kernel
+/// takes it back when the holder exits, even if it crashes.
+pub fn try_acquire(name: &str) -> io::Result<Option<SysvSemaphore>> {
+    unsafe {
+        let id = semget(fnv::hash(name) as key_t, 1, IPC_CREAT | 0o600);
+        if id == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut ops = [
+            sembuf {
+                sem_num: 0,
+                sem_op: 0,
+                sem_flg: IPC_NOWAIT as c_short,
+            },
+            sembuf {
+                sem_num: 0,
+                sem_op: 1,
+                sem_flg: SEM_UNDO as c_short,
+            },
+        ];
+        if semop(id, ops.as_mut_ptr(), ops.len() as _) != 0 {
+            let err = io::Error::last_os_error();
+            return if err.raw_os_error() == Some(EAGAIN) {
+                Ok(None)
+            } else {
+                Err(err)
+            };
+        }
+        Ok(Some(SysvSemaphore { id }))
+    }
+}
+
+impl Drop for SysvSemaphore {
+    fn drop(&mut self) {
+        let mut op = sembuf {
+            sem_num: 0,
+            sem_op: -1,
+            sem_flg: SEM_UNDO as c_short,
+        };
+        unsafe {
+            semop(self.id, &mut op, 1);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! TCP ports on the loopback interface.
+//!
+//! The instance holding a port through `Backend::TcpPort` can accept
+//! connections on `SingleInstance::tcp_listener`, other instances reach it by
+//! connecting to `127.0.0.1` on the port returned by `port`.
+
+use fnv;
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, TcpListener};
+
+/// Returns the port `name` is claimed on: the port number if `name` is one,
+/// otherwise a port in the dynamic range 49152 to 65535 derived from a hash of
+/// `name`.
+pub fn port(name: &str) -> u16 {
+    name.parse()
+        .unwrap_or_else(|_| 49152 + (fnv::hash(name) % 16384) as u16)
+}
+
+/// Returns a listener bound to the port for `name` on the loopback interface,
+/// or `None` if another instance holds it.
+pub(crate) fn try_bind(name: &str) -> io::Result<Option<TcpListener>> {
+    match TcpListener::bind((Ipv4Addr::LOCALHOST, port(name))) {
+        Ok(listener) => Ok(Some(listener)),
+        Err(ref e) if e.kind() == ErrorKind::AddrInUse => Ok(None),
+        Err(e) => Err(e),
+    }
+}
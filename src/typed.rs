@@ -0,0 +1,115 @@
+//! Typed messages between instances.
+//!
+//! `TypedChannel` sends values of any serde type instead of bytes over the
+//! channel of `SingleInstance::send` and `SingleInstance::messages`, encoded
+//! as JSON by default:
+//!
+//! ```rust,no_run
+//! use single_instance::typed::TypedChannel;
+//! use single_instance::SingleInstance;
+//!
+//! let mut instance = SingleInstance::new("my-app").unwrap();
+//! if instance.is_single() {
+//!     let channel = TypedChannel::<Vec<String>>::new(&mut instance).unwrap();
+//!     for received in channel {
+//!         let (sender, files) = received.unwrap();
+//!         println!("{} asked to open {:?}", sender.pid, files);
+//!     }
+//! } else {
+//!     let files: Vec<String> = std::env::args().skip(1).collect();
+//!     TypedChannel::send(&instance, &files).unwrap();
+//! }
+//! ```
+//!
+//! With the `msgpack` feature, `TypedChannel::with_codec` and
+//! `TypedChannel::send_with_codec` encode values as MessagePack instead, which
+//! is more compact where JSON's overhead matters. Both sides must use the same
+//! codec.
+
+use error::Result;
+use notify::{Message, SecondInstance};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::sync::mpsc::Receiver;
+use SingleInstance;
+
+/// How values are encoded on a `TypedChannel`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// JSON through `serde_json`.
+    #[default]
+    Json,
+    /// MessagePack through `rmp-serde`, with structs encoded as arrays.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl Codec {
+    fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            Codec::Json => Ok(serde_json::to_vec(value)?),
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => Ok(rmp_serde::to_vec(value)?),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, data: &[u8]) -> Result<T> {
+        match self {
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => Ok(rmp_serde::from_slice(data)?),
+        }
+    }
+}
+
+/// Values of type `T` sent to the instance holding a name.
+pub struct TypedChannel<T> {
+    messages: Receiver<Message>,
+    codec: Codec,
+    _values: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> TypedChannel<T> {
+    /// Returns a channel receiving the values other instances send with
+    /// `TypedChannel::send` from now on, see `SingleInstance::messages`.
+    pub fn new(instance: &mut SingleInstance) -> Result<Self> {
+        Self::with_codec(instance, Codec::Json)
+    }
+
+    /// Like `new`, but decoding values sent with `codec`.
+    pub fn with_codec(instance: &mut SingleInstance, codec: Codec) -> Result<Self> {
+        Ok(TypedChannel {
+            messages: instance.messages()?,
+            codec,
+            _values: PhantomData,
+        })
+    }
+
+    /// Sends `value` to the instance holding the name `instance` failed to
+    /// claim, returning `false` if it does not listen. Blocks until the holder
+    /// received it.
+    pub fn send(instance: &SingleInstance, value: &T) -> Result<bool> {
+        Self::send_with_codec(instance, value, Codec::Json)
+    }
+
+    /// Like `send`, but encoding `value` with `codec`.
+    pub fn send_with_codec(instance: &SingleInstance, value: &T, codec: Codec) -> Result<bool> {
+        Ok(instance.send(&codec.encode(value)?)?.is_some())
+    }
+
+    /// Blocks until another instance sends a value and returns it along with
+    /// the sender, or `None` once no more values can arrive.
+    pub fn recv(&self) -> Option<Result<(SecondInstance, T)>> {
+        let message = self.messages.recv().ok()?;
+        Some(self.codec.decode(&message.data).map(|value| (message.sender.clone(), value)))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Iterator for TypedChannel<T> {
+    type Item = Result<(SecondInstance, T)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
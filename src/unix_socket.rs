@@ -0,0 +1,61 @@
+//! Unix domain sockets bound in the file system.
+//!
+//! The instance holding a socket through `Backend::UnixSocket` can accept
+//! connections on `SingleInstance::unix_listener`, other instances reach it by
+//! connecting to the socket at `path`.
+
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+
+/// A bound socket, removed from the file system when dropped.
+pub(crate) struct UnixSocket {
+    pub(crate) listener: UnixListener,
+    path: PathBuf,
+}
+
+/// Returns the path of the socket for `name`: `name` itself if it contains a
+/// path separator, otherwise `name` inside the temporary directory.
+pub fn path(name: &str) -> PathBuf {
+    if name.contains('/') {
+        PathBuf::from(name)
+    } else {
+        env::temp_dir().join(name)
+    }
+}
+
+/// Returns a socket bound at the path for `name`, or `None` if another
+/// instance holds it.
+///
+/// A socket file nobody listens on any more, left behind by an instance that
+/// crashed, is removed and bound again.
+pub(crate) fn try_bind(name: &str) -> io::Result<Option<UnixSocket>> {
+    let path = path(name);
+    match UnixListener::bind(&path) {
+        Ok(listener) => return Ok(Some(UnixSocket { listener, path })),
+        Err(ref e) if e.kind() == ErrorKind::AddrInUse => {}
+        Err(e) => return Err(e),
+    }
+
+    match UnixStream::connect(&path) {
+        Ok(_) => return Ok(None),
+        Err(ref e) if e.kind() == ErrorKind::ConnectionRefused => {}
+        Err(e) => return Err(e),
+    }
+    fs::remove_file(&path)?;
+    match UnixListener::bind(&path) {
+        Ok(listener) => Ok(Some(UnixSocket { listener, path })),
+        // Another instance cleaned up and bound the socket first.
+        Err(ref e) if e.kind() == ErrorKind::AddrInUse => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        // Intentionally discard any remove errors.
+        let _ = fs::remove_file(&self.path);
+    }
+}
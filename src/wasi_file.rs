@@ -0,0 +1,174 @@
+//! Lock files on wasi, which has no advisory locks: the file existing is the
+//! lock.
+//!
+//! The holder writes a random identity into the file, so that it only ever
+//! removes its own, and keeps touching it from a thread of its own while it
+//! runs. A file not touched for `STALE_AFTER` was left behind by a crashed
+//! instance and is replaced, like a PID file naming a process that no longer
+//! runs. Without threads, as on wasm32-wasip1, the file says so and is never
+//! taken for stale.
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the holder touches its file.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a file with a heartbeat may go untouched before it is stale.
+const STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// Marks a file whose holder touches it, after the identity.
+const HEARTBEAT: &str = "heartbeat";
+
+/// A lock file naming this instance, removed when dropped.
+pub struct LockFile {
+    path: PathBuf,
+    identity: String,
+    /// Stops the heartbeat when dropped.
+    stop: Option<Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+/// Returns the path of the lock file for `name`.
+///
+/// A name with a path separator is used as given, and has to point into a
+/// preopened directory. Other names are resolved into the first of `TMPDIR`,
+/// `/tmp` and the current directory that is preopened, as nothing outside
+/// them can be reached.
+pub fn lock_path(name: &str) -> io::Result<PathBuf> {
+    if name.contains('/') {
+        return Ok(PathBuf::from(name));
+    }
+    let dirs = env::var_os("TMPDIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(vec![PathBuf::from("/tmp"), PathBuf::from(".")]);
+    for dir in dirs {
+        if fs::metadata(&dir).map(|metadata| metadata.is_dir()).unwrap_or(false) {
+            return Ok(dir.join(name));
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::NotFound,
+        "no preopened directory to put the lock file in",
+    ))
+}
+
+/// Returns the lock file at `path` naming this instance, or `None` if it
+/// names another running instance.
+///
+/// A file whose holder stopped touching it is replaced. Instances replacing
+/// the same stale file at the same time may both end up single.
+pub fn try_create(path: &Path) -> io::Result<Option<LockFile>> {
+    if let Some(lock) = create(path)? {
+        return Ok(Some(lock));
+    }
+    if is_held(path)? {
+        return Ok(None);
+    }
+    match fs::remove_file(path) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    // Another instance replacing the stale file first wins.
+    create(path)
+}
+
+/// Creates the lock file at `path`, returning `None` if it already exists.
+fn create(path: &Path) -> io::Result<Option<LockFile>> {
+    let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == ErrorKind::AlreadyExists => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut lock = LockFile {
+        path: path.to_owned(),
+        identity: identity(),
+        stop: None,
+        heartbeat: None,
+    };
+    // Until the identity is written the file counts as held, as its holder
+    // only just created it.
+    let touched = file.try_clone().and_then(|touched| {
+        let (stop, stopped) = mpsc::channel();
+        let heartbeat = thread::Builder::new()
+            .name("single-instance-heartbeat".to_owned())
+            .spawn(move || touch_until_stopped(&touched, &stopped))?;
+        lock.stop = Some(stop);
+        lock.heartbeat = Some(heartbeat);
+        Ok(())
+    });
+    let contents = match touched {
+        Ok(()) => format!("{} {}\n", lock.identity, HEARTBEAT),
+        Err(_) => format!("{}\n", lock.identity),
+    };
+    if let Err(e) = file.write_all(contents.as_bytes()) {
+        // Without an identity it would never be removed.
+        let _ = fs::remove_file(path);
+        return Err(e);
+    }
+    Ok(Some(lock))
+}
+
+/// Touches `file` every `HEARTBEAT_INTERVAL` until `stopped` disconnects.
+fn touch_until_stopped(file: &File, stopped: &mpsc::Receiver<()>) {
+    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_INTERVAL) {
+        // Intentionally discard any errors, the next beat tries again.
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+/// Returns a random identity for a new lock file, as wasi has no process IDs.
+fn identity() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Returns whether the lock file at `path` names a running instance.
+fn is_held(path: &Path) -> io::Result<bool> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        // Removed by its instance in the meantime.
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if contents.split_whitespace().nth(1) != Some(HEARTBEAT) {
+        return Ok(true);
+    }
+    let modified = fs::metadata(path)?.modified()?;
+    Ok(modified.elapsed().map(|elapsed| elapsed < STALE_AFTER).unwrap_or(true))
+}
+
+impl LockFile {
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // Stop touching the file before it is removed.
+        self.stop = None;
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+        // Leave the file alone if another instance replaced it meanwhile.
+        if let Ok(contents) = fs::read_to_string(&self.path) {
+            if contents.split_whitespace().next() == Some(&self.identity[..]) {
+                let _ = fs::remove_file(&self.path);
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Locks of the Web Locks API, `navigator.locks`, shared by the tabs and
+//! workers of an origin.
+//!
+//! A lock is requested with `ifAvailable`, for the callback to be called with
+//! `null` right away if another holder has it. Otherwise it is held until the
+//! promise the callback returns resolves, which dropping the `WebLock` does.
+//! The `LockManager` of web-sys needs `--cfg=web_sys_unstable_apis`, so the
+//! little of it used is bound here instead.
+
+use error::{Result, SingleInstanceError};
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[wasm_bindgen]
+extern "C" {
+    /// The `LockManager` at `navigator.locks`.
+    type LockManager;
+
+    #[wasm_bindgen(method, catch)]
+    fn request(
+        this: &LockManager,
+        name: &str,
+        options: &JsValue,
+        callback: &JsValue,
+    ) -> std::result::Result<js_sys::Promise, JsValue>;
+}
+
+/// A held lock, released when dropped.
+pub struct WebLock {
+    /// Resolves the promise the lock is held until.
+    release: js_sys::Function,
+}
+
+/// What a request came to, once it did, and the waker of the last poll.
+#[derive(Default)]
+struct Shared {
+    outcome: Option<Result<Option<WebLock>>>,
+    settled: bool,
+    waker: Option<Waker>,
+}
+
+/// A future resolving to the lock requested with `request`, or `None` if
+/// another holder has it.
+pub struct Request {
+    shared: Rc<RefCell<Shared>>,
+}
+
+/// Requests the lock `name` unless another holder has it.
+pub fn request(name: &str) -> Request {
+    let shared = Rc::new(RefCell::new(Shared::default()));
+    if let Err(error) = start(name, &shared) {
+        settle(&shared, Err(error));
+    }
+    Request { shared }
+}
+
+fn start(name: &str, shared: &Rc<RefCell<Shared>>) -> Result<()> {
+    let locks = lock_manager()?;
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(&options, &"ifAvailable".into(), &JsValue::TRUE).map_err(js_error)?;
+
+    let granted = shared.clone();
+    let callback = Closure::once_into_js(move |lock: JsValue| -> JsValue {
+        if lock.is_null() {
+            settle(&granted, Ok(None));
+            return JsValue::UNDEFINED;
+        }
+        let mut release = None;
+        let held = js_sys::Promise::new(&mut |resolve, _reject| release = Some(resolve));
+        settle(&granted, Ok(release.map(|release| WebLock { release })));
+        held.into()
+    });
+    let promise = locks.request(name, &options, &callback).map_err(js_error)?;
+
+    // Only rejected before the callback is called, e.g. in an opaque origin,
+    // so the closure is left to the promise.
+    let failed = shared.clone();
+    let rejected = Closure::once(move |error: JsValue| settle(&failed, Err(js_error(error))));
+    let _ = promise.catch(&rejected);
+    rejected.forget();
+    Ok(())
+}
+
+/// Hands `outcome` to the request, unless it has one already, and wakes it.
+fn settle(shared: &Rc<RefCell<Shared>>, outcome: Result<Option<WebLock>>) {
+    let waker = {
+        let mut shared = shared.borrow_mut();
+        if shared.settled {
+            return;
+        }
+        shared.settled = true;
+        shared.outcome = Some(outcome);
+        shared.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Returns `navigator.locks` of the window or worker this runs in.
+fn lock_manager() -> Result<LockManager> {
+    let navigator: JsValue = match web_sys::window() {
+        Some(window) => window.navigator().into(),
+        None => js_sys::global().unchecked_into::<web_sys::WorkerGlobalScope>().navigator().into(),
+    };
+    let locks = js_sys::Reflect::get(&navigator, &"locks".into()).map_err(js_error)?;
+    if locks.is_undefined() {
+        return Err(SingleInstanceError::WebLocks(
+            "navigator.locks is missing, e.g. outside a secure context".to_owned(),
+        ));
+    }
+    Ok(locks.unchecked_into())
+}
+
+fn js_error(error: JsValue) -> SingleInstanceError {
+    let message = match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => error.message().into(),
+        None => format!("{:?}", error),
+    };
+    SingleInstanceError::WebLocks(message)
+}
+
+impl Future for Request {
+    type Output = Result<Option<WebLock>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.shared.borrow_mut();
+        match shared.outcome.take() {
+            Some(outcome) => Poll::Ready(outcome),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        // A lock granted from now on is released right away, as is one
+        // granted already but never polled for.
+        let mut shared = self.shared.borrow_mut();
+        shared.settled = true;
+        shared.outcome = None;
+    }
+}
+
+impl Drop for WebLock {
+    fn drop(&mut self) {
+        // Intentionally discard any errors, the page may be closing.
+        let _ = self.release.call0(&JsValue::UNDEFINED);
+    }
+}
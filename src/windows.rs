@@ -0,0 +1,104 @@
+//! Windows specific configuration.
+//!
+//! Packaged (MSIX or UWP) apps run in an AppContainer, where named objects
+//! live in a namespace private to the package and the `Global\` namespace
+//! cannot be used. With packaged app mode enabled, a `Global\` or `Local\`
+//! prefix is dropped when running in an AppContainer, so that every process
+//! of the package sees the same object, and an object the process is denied
+//! access to is reported as held by another instance instead of failing.
+//!
+//! `activate_window` brings a window to the foreground, restoring it if it
+//! is minimized or hidden, e.g. in the tray. The instance holding a name does
+//! so for the window registered with `SingleInstance::set_window` whenever
+//! another one calls `SingleInstance::activate`, which first allows it to take
+//! the foreground.
+
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::windef::HWND;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::{GetCurrentProcess, OpenProcessToken};
+use winapi::um::securitybaseapi::GetTokenInformation;
+use winapi::um::winnt::{TokenIsAppContainer, TOKEN_QUERY};
+use winapi::um::winuser::{IsIconic, IsWindowVisible, SetForegroundWindow, ShowWindow, SW_RESTORE, SW_SHOW};
+
+static PACKAGED_APP_MODE: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables packaged app mode, which packaged (MSIX or UWP) apps
+/// should enable. Named objects are then kept inside the AppContainer's own
+/// namespace, and one the app is denied access to counts as held by another
+/// instance.
+pub fn set_packaged_app_mode(enabled: bool) {
+    PACKAGED_APP_MODE.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns whether packaged app mode is enabled.
+pub fn packaged_app_mode() -> bool {
+    PACKAGED_APP_MODE.load(Ordering::SeqCst)
+}
+
+/// Returns whether the current process runs in an AppContainer.
+pub fn is_app_container() -> bool {
+    unsafe {
+        let mut token = ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+        let mut is_app_container: DWORD = 0;
+        let mut len = 0;
+        let ok = GetTokenInformation(
+            token,
+            TokenIsAppContainer,
+            &mut is_app_container as *mut DWORD as _,
+            mem::size_of::<DWORD>() as DWORD,
+            &mut len,
+        );
+        CloseHandle(token);
+        ok != 0 && is_app_container != 0
+    }
+}
+
+/// Restores `window` if it is minimized, shows it if it is hidden and brings
+/// it to the foreground, returning whether it got there. Windows only lets a
+/// process that may take the foreground do so, such as one another instance
+/// called `AllowSetForegroundWindow` for.
+// Window handles are checked by Windows rather than dereferenced.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn activate_window(window: HWND) -> bool {
+    unsafe {
+        if IsIconic(window) != 0 {
+            ShowWindow(window, SW_RESTORE);
+        } else if IsWindowVisible(window) == 0 {
+            ShowWindow(window, SW_SHOW);
+        }
+        SetForegroundWindow(window) != 0
+    }
+}
+
+/// Returns the object name to use for `name`.
+pub(crate) fn object_name(name: &str) -> &str {
+    if !packaged_app_mode() || !is_app_container() {
+        return name;
+    }
+    for prefix in &["Global\\", "Local\\"] {
+        if let Some(name) = name.strip_prefix(prefix) {
+            return name;
+        }
+    }
+    name
+}
+
+/// Returns the name of the pipe claimed for `name` with `Backend::NamedPipe`,
+/// which other instances can open as a file to talk to the one holding it:
+/// `name` itself if it starts with `\\.\pipe\`, otherwise `name` under that
+/// prefix.
+#[cfg(feature = "named-pipe")]
+pub fn pipe_name(name: &str) -> String {
+    if name.starts_with(r"\\.\pipe\") {
+        name.to_owned()
+    } else {
+        format!(r"\\.\pipe\{}", name)
+    }
+}
@@ -0,0 +1,88 @@
+//! Locks on files with `LockFileEx`.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::mem;
+use std::os::windows::io::{AsRawHandle, RawHandle};
+use std::path::Path;
+use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+use winapi::um::fileapi::LockFileEx;
+use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+use windows_holder;
+
+/// A held lock, released when dropped.
+pub struct FileLock {
+    file: File,
+    /// Whether this process is recorded as the holder in the file, see
+    /// `windows_holder`.
+    recorded: bool,
+    /// Whether the record is kept when dropped, see `set_persist`.
+    persist: bool,
+}
+
+/// Returns the file at `path` locked, creating it if needed, or `None` if
+/// another instance holds it. The lock is released when the file is closed.
+///
+/// Windows file locks are mandatory, so only the last byte of the largest
+/// possible file is locked: the contents, such as the holder's PID, remain
+/// readable and writable by other instances. The holder is recorded in the
+/// file, unless it has other contents, see `windows_holder`.
+pub fn try_lock(path: &Path) -> io::Result<Option<FileLock>> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    let rc = unsafe {
+        let mut overlapped: OVERLAPPED = mem::zeroed();
+        overlapped.u.s_mut().Offset = !0;
+        overlapped.u.s_mut().OffsetHigh = !0;
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+            0,
+            1,
+            0,
+            &mut overlapped,
+        )
+    };
+    if rc != 0 {
+        let recorded = windows_holder::record(&file).unwrap_or(false);
+        return Ok(Some(FileLock {
+            file,
+            recorded,
+            persist: false,
+        }));
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+        Ok(None)
+    } else {
+        Err(err)
+    }
+}
+
+impl AsRawHandle for FileLock {
+    fn as_raw_handle(&self) -> RawHandle {
+        self.file.as_raw_handle()
+    }
+}
+
+impl FileLock {
+    /// Keeps the holder recorded in the file when dropped, like a PID file
+    /// left behind, if `persist` is set.
+    pub fn set_persist(&mut self, persist: bool) {
+        self.persist = persist;
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        if self.recorded && !self.persist {
+            // Intentionally discard any errors, the record is then stale.
+            let _ = windows_holder::clear(&self.file);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! The process holding a name, published for other instances on windows.
+//!
+//! The holder of a mutex writes its PID into a named file mapping next to it,
+//! followed by its `LockInfo`, which lives as long as any instance keeps it
+//! open, and the holder of a lock file writes its `LockInfo` into the file,
+//! unless the file has other contents, and empties it again when releasing
+//! the lock. Mappings in the `Global\`
+//! namespace need `SeCreateGlobalPrivilege`, without which nothing is
+//! published.
+
+use lock_info::LockInfo;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::Path;
+use std::process;
+use std::ptr;
+use std::slice;
+use std::time::Duration;
+use widestring::U16CString;
+use winapi::shared::minwindef::{DWORD, FILETIME};
+use winapi::shared::winerror::{ERROR_INVALID_PARAMETER, WAIT_TIMEOUT};
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::memoryapi::{
+    CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery, FILE_MAP_READ,
+    FILE_MAP_WRITE,
+};
+use winapi::um::minwinbase::STILL_ACTIVE;
+use winapi::um::processthreadsapi::{GetCurrentProcess, GetExitCodeProcess, GetProcessTimes, OpenProcess};
+use winapi::um::synchapi::WaitForSingleObject;
+use winapi::um::winbase::{INFINITE, WAIT_OBJECT_0};
+use winapi::um::winnt::{
+    HANDLE, MEMORY_BASIC_INFORMATION, PAGE_READWRITE, PROCESS_QUERY_LIMITED_INFORMATION, SYNCHRONIZE,
+};
+
+/// How much of a lock file is read to find a record in it, enough for the
+/// path of an executable.
+const RECORD_LEN: u64 = 4096;
+
+/// The size of a mapping: the PID, then the record ended by a zero byte.
+const MAPPING_LEN: usize = mem::size_of::<DWORD>() + RECORD_LEN as usize;
+
+/// The PID of this process published for a name, withdrawn when dropped.
+pub struct Published(HANDLE);
+
+unsafe impl Send for Published {}
+
+impl Drop for Published {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Returns the name of the file mapping the holder of `name` publishes its
+/// PID in.
+fn mapping_name(name: &str) -> Option<U16CString> {
+    U16CString::from_str(::windows::object_name(&format!("{}.holder", name))).ok()
+}
+
+/// Publishes this process as the holder of `name`, or returns `None` if the
+/// mapping could not be created.
+pub fn publish(name: &str) -> Option<Published> {
+    let name = mapping_name(name)?;
+    unsafe {
+        let mapping = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            ptr::null_mut(),
+            PAGE_READWRITE,
+            0,
+            MAPPING_LEN as DWORD,
+            name.as_ptr(),
+        );
+        if mapping.is_null() {
+            return None;
+        }
+        // The mapping of a previous holder may still be open, it is reused.
+        let view = MapViewOfFile(mapping, FILE_MAP_WRITE, 0, 0, 0);
+        if view.is_null() {
+            CloseHandle(mapping);
+            return None;
+        }
+        *(view as *mut DWORD) = process::id();
+        // Readers of a mapping written before the record was added find the
+        // PID alone, and the record ends at the first zero byte.
+        let record = current().encode();
+        let record = &record.as_bytes()[..record.len().min(RECORD_LEN as usize - 1)];
+        let dest = (view as *mut u8).add(mem::size_of::<DWORD>());
+        ptr::copy_nonoverlapping(record.as_ptr(), dest, record.len());
+        *dest.add(record.len()) = 0;
+        UnmapViewOfFile(view);
+        Some(Published(mapping))
+    }
+}
+
+/// Returns the PID the holder of `name` published, or `None` if it did not.
+pub fn published(name: &str) -> Option<u32> {
+    let name = mapping_name(name)?;
+    unsafe {
+        let mapping = OpenFileMappingW(FILE_MAP_READ, 0, name.as_ptr());
+        if mapping.is_null() {
+            return None;
+        }
+        let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+        let pid = if view.is_null() {
+            0
+        } else {
+            let pid = *(view as *const DWORD);
+            UnmapViewOfFile(view);
+            pid
+        };
+        CloseHandle(mapping);
+        Some(pid).filter(|pid| *pid != 0)
+    }
+}
+
+/// Returns the record the holder of `name` published, or `None` if it did
+/// not or its process exited.
+pub fn published_info(name: &str) -> Option<LockInfo> {
+    let name = mapping_name(name)?;
+    unsafe {
+        let mapping = OpenFileMappingW(FILE_MAP_READ, 0, name.as_ptr());
+        if mapping.is_null() {
+            return None;
+        }
+        let view = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0);
+        CloseHandle(mapping);
+        if view.is_null() {
+            return None;
+        }
+        // A mapping written before the record was added only holds the PID.
+        let mut region: MEMORY_BASIC_INFORMATION = mem::zeroed();
+        let size = if VirtualQuery(view, &mut region, mem::size_of::<MEMORY_BASIC_INFORMATION>()) == 0 {
+            0
+        } else {
+            region.RegionSize.min(MAPPING_LEN)
+        };
+        let record = if size > mem::size_of::<DWORD>() {
+            let bytes = slice::from_raw_parts(
+                (view as *const u8).add(mem::size_of::<DWORD>()),
+                size - mem::size_of::<DWORD>(),
+            );
+            let len = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..len]).into_owned()
+        } else {
+            String::new()
+        };
+        UnmapViewOfFile(view);
+        LockInfo::parse(&record).filter(is_running)
+    }
+}
+
+/// Records this process as the holder of the locked `file`, returning `false`
+/// if the file has other contents and was left alone.
+pub fn record(file: &File) -> io::Result<bool> {
+    let mut contents = String::new();
+    let mut file = file;
+    if file.take(RECORD_LEN).read_to_string(&mut contents).is_err()
+        || !(contents.is_empty() || parse(&contents).is_some())
+    {
+        return Ok(false);
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(current().encode().as_bytes())?;
+    Ok(true)
+}
+
+/// Removes the record from the locked `file`.
+pub fn clear(file: &File) -> io::Result<()> {
+    file.set_len(0)
+}
+
+/// Returns the PID recorded as the holder of the lock file at `path`, or
+/// `None` if there is no record.
+pub fn find(path: &Path) -> Option<u32> {
+    let mut contents = String::new();
+    File::open(path)
+        .ok()?
+        .take(RECORD_LEN)
+        .read_to_string(&mut contents)
+        .ok()?;
+    parse(&contents)
+}
+
+/// Returns the record of the holder of the lock file at `path`, or `None` if
+/// there is none or its process exited.
+pub fn read(path: &Path) -> Option<LockInfo> {
+    let mut contents = String::new();
+    File::open(path)
+        .ok()?
+        .take(RECORD_LEN)
+        .read_to_string(&mut contents)
+        .ok()?;
+    LockInfo::parse(&contents).filter(is_running)
+}
+
+/// Parses a record, or the PID alone written before records were added.
+fn parse(contents: &str) -> Option<u32> {
+    match LockInfo::parse(contents) {
+        Some(info) => Some(info.pid),
+        None => contents.trim_end().parse().ok().filter(|pid| *pid != 0),
+    }
+}
+
+/// Returns the record of this process.
+fn current() -> LockInfo {
+    LockInfo::current(unsafe { start_time(GetCurrentProcess()) }.unwrap_or(0))
+}
+
+/// Returns whether the process `info` names still runs, rather than another
+/// one that got its PID.
+fn is_running(info: &LockInfo) -> bool {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, info.pid);
+        if process.is_null() {
+            return false;
+        }
+        let mut code = 0;
+        let running = GetExitCodeProcess(process, &mut code) != 0
+            && code == STILL_ACTIVE
+            && start_time(process) == Some(info.start_time);
+        CloseHandle(process);
+        running
+    }
+}
+
+/// Waits up to `timeout` for the process `pid` to exit, returning whether it
+/// did, also if it had exited before. With the `start_time` it recorded, a
+/// process that got its PID since is told apart.
+pub fn wait(pid: u32, start_time: Option<u64>, timeout: Option<Duration>) -> io::Result<bool> {
+    unsafe {
+        let process = OpenProcess(SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            let err = io::Error::last_os_error();
+            // No process has that PID anymore.
+            return match err.raw_os_error() {
+                Some(code) if code == ERROR_INVALID_PARAMETER as i32 => Ok(true),
+                _ => Err(err),
+            };
+        }
+        if start_time.is_some_and(|start_time| self::start_time(process) != Some(start_time)) {
+            CloseHandle(process);
+            return Ok(true);
+        }
+        let millis = match timeout {
+            Some(timeout) => timeout.as_millis().min(INFINITE as u128 - 1) as DWORD,
+            None => INFINITE,
+        };
+        let result = match WaitForSingleObject(process, millis) {
+            WAIT_OBJECT_0 => Ok(true),
+            WAIT_TIMEOUT => Ok(false),
+            _ => Err(io::Error::last_os_error()),
+        };
+        CloseHandle(process);
+        result
+    }
+}
+
+/// Returns the creation time of `process` in 100 nanosecond intervals since
+/// 1601.
+unsafe fn start_time(process: HANDLE) -> Option<u64> {
+    let (mut creation, mut exit, mut kernel, mut user): (FILETIME, FILETIME, FILETIME, FILETIME) = mem::zeroed();
+    if GetProcessTimes(process, &mut creation, &mut exit, &mut kernel, &mut user) == 0 {
+        return None;
+    }
+    Some((creation.dwHighDateTime as u64) << 32 | creation.dwLowDateTime as u64)
+}
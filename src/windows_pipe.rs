@@ -0,0 +1,44 @@
+//! Named pipes.
+
+use std::fs::File;
+use std::io;
+use std::os::windows::io::FromRawHandle;
+use std::ptr;
+use widestring::U16CString;
+use winapi::shared::winerror::{ERROR_ACCESS_DENIED, ERROR_PIPE_BUSY};
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+use winapi::um::namedpipeapi::CreateNamedPipeW;
+use winapi::um::winbase::{
+    FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+    PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+};
+
+/// Returns the first instance of the pipe for `name`, or `None` if another
+/// instance holds it.
+///
+/// Further pipe instances may be created by the holder to serve more than one
+/// client at a time.
+pub fn try_create(name: &str) -> io::Result<Option<File>> {
+    let name = U16CString::from_str(::windows::pipe_name(name))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let handle = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if handle != INVALID_HANDLE_VALUE {
+        return Ok(Some(unsafe { File::from_raw_handle(handle as _) }));
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(code) if code == ERROR_ACCESS_DENIED as i32 || code == ERROR_PIPE_BUSY as i32 => Ok(None),
+        _ => Err(err),
+    }
+}
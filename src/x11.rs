@@ -0,0 +1,31 @@
+//! Raising the window of the instance holding a name on X11.
+//!
+//! The window registered with `SingleInstance::set_x11_window` is mapped, in
+//! case it was hidden, e.g. in the tray, and then activated by sending
+//! `_NET_ACTIVE_WINDOW` to the root window, which EWMH compliant window
+//! managers handle by raising and focusing it.
+
+use std::error::Error;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ClientMessageEvent, ConnectionExt, EventMask};
+
+/// The source of an activation request from a pager, which window managers
+/// honor even where they prevent applications from stealing focus.
+const SOURCE_PAGER: u32 = 2;
+
+/// Maps and activates `window` on the display given by `DISPLAY`.
+pub(crate) fn activate(window: u32) -> Result<(), Box<dyn Error>> {
+    let (conn, screen) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen].root;
+    let active_window = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+    conn.map_window(window)?;
+    let event = ClientMessageEvent::new(32, window, active_window, [SOURCE_PAGER, 0, 0, 0, 0]);
+    conn.send_event(
+        false,
+        root,
+        EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+        event,
+    )?;
+    conn.flush()?;
+    Ok(())
+}